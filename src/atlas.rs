@@ -0,0 +1,217 @@
+use image::{DynamicImage, GenericImageView, RgbaImage};
+
+// Pixels around every image in the atlas; the border pixels of the image are
+// repeated in them, so the filtering doesn't blend in the neighbours.
+const PADDING: u32 = 1;
+
+// Packs many small images to a few big ones, so the images can be drawn from
+// one texture; see `Renderer1::add_atlas`.
+#[derive(Debug, Clone)]
+pub struct AtlasBuilder {
+    page_size: u32,
+    images: Vec<RgbaImage>,
+}
+
+// Place of an image in the atlas, in the pixels of its page from the top left
+// corner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtlasRegion {
+    pub page: usize,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// Images packed by `AtlasBuilder`; the regions are in the order the images
+// were added.
+#[derive(Debug, Clone)]
+pub struct Atlas {
+    pub pages: Vec<RgbaImage>,
+    pub regions: Vec<AtlasRegion>,
+}
+
+impl AtlasBuilder {
+    // The pages are at most `page_size` pixels wide and high, unless an image
+    // doesn't fit in it; such images get pages of their own.
+    pub fn new(page_size: u32) -> Self {
+        AtlasBuilder {
+            page_size,
+            images: Vec::new(),
+        }
+    }
+
+    // Adds the image; returns the index of its region in the atlas.
+    pub fn add(&mut self, image: &DynamicImage) -> usize {
+        self.images.push(image.to_rgba8());
+        self.images.len() - 1
+    }
+
+    // Places the images on shelves from the tallest one; a shelf is a row of
+    // images as high as its first image. A new page is started when a shelf
+    // doesn't fit on the page. The pages are cut below their last shelf.
+    pub fn build(self) -> Atlas {
+        let padded = |size: u32| size + 2 * PADDING;
+        let mut order: Vec<usize> = (0..self.images.len()).collect();
+        order.sort_by_key(|&index| std::cmp::Reverse(self.images[index].height()));
+
+        // Size of every page.
+        let mut pages: Vec<(u32, u32)> = Vec::new();
+        let mut regions = vec![None; self.images.len()];
+        // Page the shelves are placed on, the start and the height of its
+        // last shelf.
+        let mut current = None;
+        let (mut shelf_x, mut shelf_y, mut shelf_height) = (0, 0, 0);
+        for index in order {
+            let (width, height) = self.images[index].dimensions();
+            let (width, height) = (padded(width), padded(height));
+            if width > self.page_size || height > self.page_size {
+                pages.push((width, height));
+                regions[index] = Some((pages.len() - 1, PADDING, PADDING));
+                continue;
+            }
+            if shelf_x + width > self.page_size {
+                shelf_y += shelf_height;
+                shelf_x = 0;
+                shelf_height = 0;
+            }
+            let page = match current {
+                Some(page) if shelf_y + height <= self.page_size => page,
+                _ => {
+                    pages.push((self.page_size, 0));
+                    (shelf_x, shelf_y, shelf_height) = (0, 0, 0);
+                    current = Some(pages.len() - 1);
+                    pages.len() - 1
+                }
+            };
+            regions[index] = Some((page, shelf_x + PADDING, shelf_y + PADDING));
+            shelf_x += width;
+            shelf_height = shelf_height.max(height);
+            pages[page].1 = pages[page].1.max(shelf_y + shelf_height);
+        }
+
+        let mut page_images: Vec<RgbaImage> =
+            pages.iter().map(|&(width, height)| RgbaImage::new(width, height)).collect();
+        let regions = regions
+            .into_iter()
+            .zip(&self.images)
+            .map(|(region, image)| {
+                let (page, x, y) = region.unwrap();
+                copy_padded(&mut page_images[page], image, x, y);
+                AtlasRegion {
+                    page,
+                    x,
+                    y,
+                    width: image.width(),
+                    height: image.height(),
+                }
+            })
+            .collect();
+        Atlas {
+            pages: page_images,
+            regions,
+        }
+    }
+}
+
+// Copies the image to the page at the position with its border pixels
+// repeated in the padding around it.
+fn copy_padded(page: &mut RgbaImage, image: &RgbaImage, x: u32, y: u32) {
+    if image.width() == 0 || image.height() == 0 {
+        return;
+    }
+    let last = (image.width() - 1, image.height() - 1);
+    for page_y in y - PADDING..y + image.height() + PADDING {
+        for page_x in x - PADDING..x + image.width() + PADDING {
+            let source_x = page_x.saturating_sub(x).min(last.0);
+            let source_y = page_y.saturating_sub(y).min(last.1);
+            page.put_pixel(page_x, page_y, *image.get_pixel(source_x, source_y));
+        }
+    }
+}
+
+impl Atlas {
+    // Texture coordinates of the region: the left, top, right and bottom edge
+    // as the parts of the width and the height of its page.
+    pub fn uv(&self, index: usize) -> [f32; 4] {
+        let region = self.regions[index];
+        let (width, height) = self.pages[region.page].dimensions();
+        [
+            region.x as f32 / width as f32,
+            region.y as f32 / height as f32,
+            (region.x + region.width) as f32 / width as f32,
+            (region.y + region.height) as f32 / height as f32,
+        ]
+    }
+
+    // The region as an image.
+    pub fn image(&self, index: usize) -> DynamicImage {
+        let region = self.regions[index];
+        let page = DynamicImage::ImageRgba8(self.pages[region.page].clone());
+        page.view(region.x, region.y, region.width, region.height).to_image().into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, value: u8) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_pixel(width, height, image::Rgba([value, 0, 0, 255])))
+    }
+
+    fn overlap(a: &AtlasRegion, b: &AtlasRegion) -> bool {
+        a.page == b.page
+            && a.x < b.x + b.width + PADDING
+            && b.x < a.x + a.width + PADDING
+            && a.y < b.y + b.height + PADDING
+            && b.y < a.y + a.height + PADDING
+    }
+
+    #[test]
+    fn images_are_packed_apart_on_the_pages() {
+        let mut builder = AtlasBuilder::new(64);
+        let sizes = [(30, 10), (10, 30), (20, 20), (40, 8), (8, 8), (16, 24), (50, 50), (12, 12)];
+        for (index, &(width, height)) in sizes.iter().enumerate() {
+            assert_eq!(builder.add(&solid(width, height, index as u8)), index);
+        }
+        let atlas = builder.build();
+        assert_eq!(atlas.regions.len(), sizes.len());
+        for (index, region) in atlas.regions.iter().enumerate() {
+            let page = &atlas.pages[region.page];
+            assert!(region.x >= PADDING && region.y >= PADDING);
+            assert!(region.x + region.width + PADDING <= page.width());
+            assert!(region.y + region.height + PADDING <= page.height());
+            assert_eq!((region.width, region.height), sizes[index]);
+            assert_eq!(atlas.image(index), solid(sizes[index].0, sizes[index].1, index as u8));
+            for other in &atlas.regions[index + 1..] {
+                assert!(!overlap(region, other), "{region:?} overlaps {other:?}");
+            }
+        }
+        assert!(atlas.pages.len() < sizes.len());
+    }
+
+    #[test]
+    fn big_images_get_pages_of_their_own() {
+        let mut builder = AtlasBuilder::new(32);
+        builder.add(&solid(8, 8, 0));
+        builder.add(&solid(40, 20, 1));
+        let atlas = builder.build();
+        assert_eq!(atlas.pages.len(), 2);
+        assert_eq!(atlas.pages[atlas.regions[1].page].dimensions(), (42, 22));
+        // The page is cut below the image.
+        assert_eq!(atlas.pages[atlas.regions[0].page].dimensions(), (32, 10));
+    }
+
+    #[test]
+    fn borders_are_repeated_in_the_padding() {
+        let mut builder = AtlasBuilder::new(16);
+        let image = RgbaImage::from_fn(2, 1, |x, _| image::Rgba([x as u8, 1, 2, 3]));
+        builder.add(&DynamicImage::ImageRgba8(image));
+        let atlas = builder.build();
+        let page = &atlas.pages[0];
+        assert_eq!(page.get_pixel(0, 0), &image::Rgba([0, 1, 2, 3]));
+        assert_eq!(page.get_pixel(3, 2), &image::Rgba([1, 1, 2, 3]));
+        assert_eq!(atlas.uv(0), [1.0 / 16.0, 1.0 / 3.0, 3.0 / 16.0, 2.0 / 3.0]);
+    }
+}
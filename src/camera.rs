@@ -0,0 +1,124 @@
+use crate::math::{self, Affine2, Vector2};
+use winit::dpi::PhysicalSize;
+
+// Zoom is limited so the view transform can always be inverted.
+const MIN_ZOOM: f32 = 1.0e-3;
+const MAX_ZOOM: f32 = 1.0e3;
+
+// View over the scene. The shapes are placed in the world coordinates; the
+// camera pans, zooms and rotates them to the pixels of the render target
+// before the orthographic projection. The default camera shows the world
+// coordinates as the pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera2D {
+    // World point shown at the bottom left corner of the target.
+    pub position: Vector2<f32>,
+    // Pixels per world unit.
+    pub zoom: f32,
+    // Counter clockwise rotation of the world around the camera position in
+    // radians.
+    pub rotation: f32,
+}
+
+impl Default for Camera2D {
+    fn default() -> Self {
+        Camera2D {
+            position: Vector2 { x: 0.0, y: 0.0 },
+            zoom: 1.0,
+            rotation: 0.0,
+        }
+    }
+}
+
+impl Camera2D {
+    // Transform from the world coordinates to the pixels.
+    pub fn view(&self) -> Affine2 {
+        Affine2::rotation(self.rotation)
+            * Affine2::scale(self.zoom, self.zoom)
+            * Affine2::translation(-self.position.x, -self.position.y)
+    }
+
+    // View and the projection for the projection uniform.
+    pub fn matrix(&self, size: PhysicalSize<u32>) -> math::Matrix4x4<f32> {
+        math::ortho(0.0, size.width as f32, 0.0, size.height as f32, 0.0, 1.0) * self.view()
+    }
+
+    // World point drawn at the pixel; the pixels have the origin at the bottom
+    // left corner like the world.
+    pub fn screen_to_world(&self, pixel: Vector2<f32>) -> Vector2<f32> {
+        let unrotated = Affine2::rotation(-self.rotation).transform_point(pixel);
+        unrotated * (1.0 / self.zoom) + self.position
+    }
+
+    // Moves the view by the offset in pixels; the scene follows the offset.
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        let offset = Affine2::rotation(-self.rotation).transform_vector(Vector2 { x: dx, y: dy });
+        self.position = self.position - offset * (1.0 / self.zoom);
+    }
+
+    // Multiplies the zoom by the factor keeping the world point under the
+    // pixel in place.
+    pub fn zoom_around(&mut self, factor: f32, pixel: Vector2<f32>) {
+        let anchor = self.screen_to_world(pixel);
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.keep(anchor, pixel);
+    }
+
+    // Rotates the view counter clockwise by the angle in radians around the
+    // pixel.
+    pub fn rotate_around(&mut self, angle: f32, pixel: Vector2<f32>) {
+        let anchor = self.screen_to_world(pixel);
+        self.rotation += angle;
+        self.keep(anchor, pixel);
+    }
+
+    // Pans so the world point is drawn at the pixel.
+    fn keep(&mut self, world: Vector2<f32>, pixel: Vector2<f32>) {
+        let moved = self.view().transform_point(world);
+        self.pan(pixel.x - moved.x, pixel.y - moved.y);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: Vector2<f32>, b: Vector2<f32>) {
+        assert!((a - b).length() < 1.0e-3, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn default_camera_shows_pixels() {
+        let camera = Camera2D::default();
+        let point = Vector2 { x: 12.0, y: 34.0 };
+        assert_near(camera.view().transform_point(point), point);
+        assert_near(camera.screen_to_world(point), point);
+    }
+
+    #[test]
+    fn zoom_keeps_the_point_under_the_cursor() {
+        let mut camera = Camera2D::default();
+        camera.pan(30.0, -10.0);
+        camera.rotate_around(0.5, Vector2 { x: 50.0, y: 50.0 });
+        let cursor = Vector2 { x: 100.0, y: 60.0 };
+        let world = camera.screen_to_world(cursor);
+
+        camera.zoom_around(2.5, cursor);
+        assert!((camera.zoom - 2.5).abs() < 1.0e-6);
+        assert_near(camera.screen_to_world(cursor), world);
+        assert_near(camera.view().transform_point(world), cursor);
+    }
+
+    #[test]
+    fn pan_moves_the_scene_with_the_offset() {
+        let mut camera = Camera2D {
+            zoom: 2.0,
+            rotation: 1.0,
+            ..Camera2D::default()
+        };
+        let world = Vector2 { x: 5.0, y: 5.0 };
+        let before = camera.view().transform_point(world);
+        camera.pan(10.0, 20.0);
+        assert_near(camera.view().transform_point(world), before + Vector2 { x: 10.0, y: 20.0 });
+    }
+}
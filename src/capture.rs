@@ -0,0 +1,107 @@
+use std::sync::mpsc;
+
+use anyhow::*;
+use image::RgbaImage;
+
+// Copies the texture back to CPU. Only 8 bit RGBA/BGRA formats are supported,
+// that covers the headless target and the usual surface formats.
+pub fn read_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> Result<RgbaImage> {
+    let swap_red_and_blue = match texture.format() {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+        format => bail!("Reading back texture format {:?} is not supported", format),
+    };
+
+    let width = texture.width();
+    let height = texture.height();
+    let unpadded_bytes_per_row = 4 * width;
+    // Rows copied from a texture into a buffer need to be aligned.
+    let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(alignment) * alignment;
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Capture Buffer"),
+        size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Capture Encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture {
+            aspect: wgpu::TextureAspect::All,
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+        },
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        },
+        texture.size(),
+    );
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let (sender, receiver) = mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        // The receiver lives until the poll below is done.
+        let _ = sender.send(result);
+    });
+    device.poll(wgpu::Maintain::Wait);
+    receiver.recv()??;
+
+    let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+    {
+        let data = slice.get_mapped_range();
+        for row in data.chunks(padded_bytes_per_row as usize) {
+            pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+        }
+    }
+    buffer.unmap();
+
+    if swap_red_and_blue {
+        for pixel in pixels.chunks_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+
+    RgbaImage::from_raw(width, height, pixels).context("Captured frame has unexpected size")
+}
+
+// Capture state shared by the render targets. The capture is requested before
+// the frame is rendered and performed when the frame is presented.
+#[derive(Default)]
+pub struct FrameCapture {
+    requested: bool,
+    frame: Option<Result<RgbaImage>>,
+}
+
+impl FrameCapture {
+    pub fn request(&mut self) {
+        self.requested = true;
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.requested
+    }
+
+    pub fn store(&mut self, frame: Result<RgbaImage>) {
+        self.requested = false;
+        self.frame = Some(frame);
+    }
+
+    pub fn take(&mut self) -> Result<RgbaImage> {
+        self.frame.take().context("No frame was captured")?
+    }
+}
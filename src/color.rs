@@ -0,0 +1,36 @@
+use crate::raw::Gpu;
+
+// RGBA color with linear components in range 0.0 - 1.0.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[repr(C, packed)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Gpu for Color {}
+
+impl Color {
+    pub const TRANSPARENT: Color = Color::rgba(0.0, 0.0, 0.0, 0.0);
+    pub const BLACK: Color = Color::rgb(0.0, 0.0, 0.0);
+    pub const WHITE: Color = Color::rgb(1.0, 1.0, 1.0);
+    pub const RED: Color = Color::rgb(1.0, 0.0, 0.0);
+    pub const GREEN: Color = Color::rgb(0.0, 1.0, 0.0);
+    pub const BLUE: Color = Color::rgb(0.0, 0.0, 1.0);
+    pub const YELLOW: Color = Color::rgb(1.0, 1.0, 0.0);
+
+    pub const fn rgba(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Color { r, g, b, a }
+    }
+
+    pub const fn rgb(r: f32, g: f32, b: f32) -> Self {
+        Color::rgba(r, g, b, 1.0)
+    }
+
+    // Same color with the alpha multiplied by the opacity.
+    pub fn faded(self, opacity: f32) -> Self {
+        Color { a: self.a * opacity, ..self }
+    }
+}
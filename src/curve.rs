@@ -0,0 +1,115 @@
+use crate::math::Vector2;
+
+// Default largest distance in pixels between a curve and its flattened line.
+pub const DEFAULT_TOLERANCE: f32 = 0.25;
+
+// Upper bound of the segments of one curve so a tiny tolerance can't stall the
+// renderer.
+const MAX_SEGMENTS: usize = 1024;
+
+// Number of uniform steps needed to keep the flattened line within `tolerance`
+// of a curve of `degree` (Wang's formula); `second_difference` is the length of
+// the largest second difference of the control points.
+fn segment_count(degree: f32, second_difference: f32, tolerance: f32) -> usize {
+    let bound = degree * (degree - 1.0) / 8.0 * second_difference;
+    let count = (bound / tolerance.max(f32::EPSILON)).sqrt().ceil();
+    (count as usize).clamp(1, MAX_SEGMENTS)
+}
+
+// Appends the points of a quadratic Bézier curve after its start point.
+pub fn flatten_quadratic(
+    start: Vector2<f32>,
+    control: Vector2<f32>,
+    end: Vector2<f32>,
+    tolerance: f32,
+    points: &mut Vec<Vector2<f32>>,
+) {
+    let second_difference = (start - control * 2.0 + end).length();
+    let count = segment_count(2.0, second_difference, tolerance);
+    for step in 1..=count {
+        let t = step as f32 / count as f32;
+        let s = 1.0 - t;
+        points.push(start * (s * s) + control * (2.0 * s * t) + end * (t * t));
+    }
+}
+
+// Appends the points of a cubic Bézier curve after its start point.
+pub fn flatten_cubic(
+    start: Vector2<f32>,
+    control1: Vector2<f32>,
+    control2: Vector2<f32>,
+    end: Vector2<f32>,
+    tolerance: f32,
+    points: &mut Vec<Vector2<f32>>,
+) {
+    let second_difference = (start - control1 * 2.0 + control2)
+        .length()
+        .max((control1 - control2 * 2.0 + end).length());
+    let count = segment_count(3.0, second_difference, tolerance);
+    for step in 1..=count {
+        let t = step as f32 / count as f32;
+        let s = 1.0 - t;
+        points.push(
+            start * (s * s * s)
+                + control1 * (3.0 * s * s * t)
+                + control2 * (3.0 * s * t * t)
+                + end * (t * t * t),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f32, y: f32) -> Vector2<f32> {
+        Vector2 { x, y }
+    }
+
+    // Distance of `p` from the segment between `a` and `b`.
+    fn distance(p: Vector2<f32>, a: Vector2<f32>, b: Vector2<f32>) -> f32 {
+        let ab = b - a;
+        let t = ((p - a).dot(ab) / ab.dot(ab)).clamp(0.0, 1.0);
+        (p - (a + ab * t)).length()
+    }
+
+    #[test]
+    fn straight_curve_is_one_segment() {
+        let mut points = Vec::new();
+        flatten_quadratic(point(0.0, 0.0), point(5.0, 0.0), point(10.0, 0.0), 0.25, &mut points);
+        assert_eq!(points, vec![point(10.0, 0.0)]);
+    }
+
+    #[test]
+    fn flattened_cubic_is_within_tolerance() {
+        let (start, control1, control2, end) =
+            (point(0.0, 0.0), point(0.0, 100.0), point(100.0, 100.0), point(100.0, 0.0));
+        for tolerance in [2.0, 0.25, 0.05] {
+            let mut points = vec![start];
+            flatten_cubic(start, control1, control2, end, tolerance, &mut points);
+            assert_eq!(points.last(), Some(&end));
+            for step in 0..=1000 {
+                let t = step as f32 / 1000.0;
+                let s = 1.0 - t;
+                let p = start * (s * s * s)
+                    + control1 * (3.0 * s * s * t)
+                    + control2 * (3.0 * s * t * t)
+                    + end * (t * t * t);
+                let nearest = points
+                    .windows(2)
+                    .map(|segment| distance(p, segment[0], segment[1]))
+                    .fold(f32::MAX, f32::min);
+                assert!(nearest <= tolerance * 1.01, "{nearest} > {tolerance}");
+            }
+        }
+    }
+
+    #[test]
+    fn smaller_tolerance_needs_more_points() {
+        let mut coarse = Vec::new();
+        let mut fine = Vec::new();
+        flatten_quadratic(point(0.0, 0.0), point(50.0, 80.0), point(100.0, 0.0), 1.0, &mut coarse);
+        flatten_quadratic(point(0.0, 0.0), point(50.0, 80.0), point(100.0, 0.0), 0.1, &mut fine);
+        assert!(fine.len() > coarse.len());
+    }
+}
@@ -0,0 +1,82 @@
+use crate::renderer_1::LineCap;
+
+// Longest pattern of on and off lengths; longer patterns are cut.
+pub(crate) const MAX_DASH_LENGTHS: usize = 16;
+
+// Pattern of the dashes of a stroke, the lengths are measured along the
+// middle of the stroke.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dash {
+    // On and off lengths alternating, starting with a dash.
+    pattern: Vec<f32>,
+    pub(crate) offset: f32,
+    pub(crate) cap: LineCap,
+}
+
+impl Dash {
+    // Dashes and gaps of the lengths alternating, starting with a dash. A
+    // pattern with an odd number of lengths is repeated twice, as in SVG.
+    pub fn new(pattern: &[f32]) -> Self {
+        Dash {
+            pattern: pattern.to_vec(),
+            offset: 0.0,
+            cap: LineCap::Butt,
+        }
+    }
+
+    // Round dots `spacing` apart; the dots have the diameter of the stroke
+    // width.
+    pub fn dotted(spacing: f32) -> Self {
+        Dash::new(&[0.0, spacing]).cap(LineCap::Round)
+    }
+
+    // Moves the pattern back along the stroke by the length.
+    pub fn offset(mut self, offset: f32) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    // Shape of the ends of the dashes.
+    pub fn cap(mut self, cap: LineCap) -> Self {
+        self.cap = cap;
+        self
+    }
+
+    // On and off lengths with an even count, none of them negative. Empty
+    // when the pattern has no length, the stroke is solid then.
+    pub(crate) fn lengths(&self) -> Vec<f32> {
+        let mut lengths: Vec<f32> = self.pattern.iter().map(|length| length.max(0.0)).collect();
+        if lengths.len() % 2 == 1 {
+            lengths.extend_from_within(..);
+        }
+        lengths.truncate(MAX_DASH_LENGTHS);
+        if lengths.iter().sum::<f32>() <= 0.0 {
+            lengths.clear();
+        }
+        lengths
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn odd_patterns_are_repeated() {
+        assert_eq!(Dash::new(&[4.0, 2.0, 1.0]).lengths(), vec![4.0, 2.0, 1.0, 4.0, 2.0, 1.0]);
+        assert_eq!(Dash::new(&[4.0, 2.0]).lengths(), vec![4.0, 2.0]);
+    }
+
+    #[test]
+    fn patterns_without_length_are_solid() {
+        assert!(Dash::new(&[]).lengths().is_empty());
+        assert!(Dash::new(&[0.0, -2.0]).lengths().is_empty());
+        assert_eq!(Dash::dotted(6.0).lengths(), vec![0.0, 6.0]);
+    }
+
+    #[test]
+    fn long_patterns_are_cut() {
+        let pattern: Vec<f32> = (1..=20).map(|length| length as f32).collect();
+        assert_eq!(Dash::new(&pattern).lengths().len(), MAX_DASH_LENGTHS);
+    }
+}
@@ -0,0 +1,133 @@
+use crate::color::Color;
+use crate::math::Vector2;
+
+// Where the colors of a gradient change; the points are in the coordinates of
+// the shape before its transform.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum GradientKind {
+    // From the start to the end point, the color is the same along the lines
+    // perpendicular to them.
+    Linear { start: Vector2<f32>, end: Vector2<f32> },
+    // From the center to the circle with the radius.
+    Radial { center: Vector2<f32>, radius: f32 },
+    // Counter clockwise around the center, starting at the angle in radians
+    // from the x axis.
+    Conic { center: Vector2<f32>, angle: f32 },
+}
+
+// Paint with the colors interpolated between the stops. The colors before the
+// first stop and after the last one are the colors of these stops.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gradient {
+    pub(crate) kind: GradientKind,
+    // Offsets from 0.0 to 1.0 and their colors, sorted by the offset.
+    pub(crate) stops: Vec<(f32, Color)>,
+}
+
+impl Gradient {
+    fn new(kind: GradientKind) -> Self {
+        Gradient { kind, stops: Vec::new() }
+    }
+
+    pub fn linear(start: (f32, f32), end: (f32, f32)) -> Self {
+        let [start, end] = [start, end].map(|(x, y)| Vector2 { x, y });
+        Gradient::new(GradientKind::Linear { start, end })
+    }
+
+    pub fn radial(center: (f32, f32), radius: f32) -> Self {
+        let center = Vector2 { x: center.0, y: center.1 };
+        Gradient::new(GradientKind::Radial { center, radius })
+    }
+
+    // Colors going counter clockwise around the center; the offset 0.0 is at
+    // the angle in radians from the x axis and the offset 1.0 a full turn
+    // later.
+    pub fn conic(center: (f32, f32), angle: f32) -> Self {
+        let center = Vector2 { x: center.0, y: center.1 };
+        Gradient::new(GradientKind::Conic { center, angle })
+    }
+
+    // Adds the color at the offset from 0.0 to 1.0. Stops at the same offset
+    // make a sharp change from the color added first to the one added later.
+    pub fn stop(mut self, offset: f32, color: Color) -> Self {
+        let offset = offset.clamp(0.0, 1.0);
+        let index = self.stops.partition_point(|&(other, _)| other <= offset);
+        self.stops.insert(index, (offset, color));
+        self
+    }
+
+    // Colors at `width` evenly spaced offsets from 0.0 to 1.0. The colors are
+    // interpolated premultiplied, so transparent stops don't darken their
+    // neighbours; the result is not premultiplied.
+    pub(crate) fn ramp(&self, width: usize) -> Vec<Color> {
+        let premultiplied = |color: Color| [color.r * color.a, color.g * color.a, color.b * color.a, color.a];
+        (0..width)
+            .map(|index| {
+                let offset = index as f32 / (width - 1).max(1) as f32;
+                let next = self.stops.partition_point(|&(other, _)| other <= offset);
+                let [r, g, b, a] = match (self.stops.get(next.wrapping_sub(1)), self.stops.get(next)) {
+                    (None, None) => return Color::TRANSPARENT,
+                    (Some(&(_, color)), None) | (None, Some(&(_, color))) => return color,
+                    (Some(&(start, from)), Some(&(end, to))) => {
+                        let t = (offset - start) / (end - start);
+                        let (from, to) = (premultiplied(from), premultiplied(to));
+                        [0, 1, 2, 3].map(|channel| from[channel] + (to[channel] - from[channel]) * t)
+                    }
+                };
+                if a <= 0.0 {
+                    Color::TRANSPARENT
+                } else {
+                    Color::rgba(r / a, g / a, b / a, a)
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stops_are_interpolated() {
+        let gradient = Gradient::linear((0.0, 0.0), (1.0, 0.0))
+            .stop(1.0, Color::WHITE)
+            .stop(0.0, Color::BLACK);
+        let ramp = gradient.ramp(5);
+        assert_eq!(ramp[0], Color::BLACK);
+        assert_eq!(ramp[2], Color::rgb(0.5, 0.5, 0.5));
+        assert_eq!(ramp[4], Color::WHITE);
+    }
+
+    #[test]
+    fn colors_outside_of_the_stops_are_extended() {
+        let gradient = Gradient::radial((0.0, 0.0), 1.0)
+            .stop(0.25, Color::RED)
+            .stop(0.75, Color::BLUE);
+        let ramp = gradient.ramp(5);
+        assert_eq!(ramp[0], Color::RED);
+        assert_eq!(ramp[1], Color::RED);
+        assert_eq!(ramp[3], Color::BLUE);
+        assert_eq!(ramp[4], Color::BLUE);
+        assert_eq!(Gradient::conic((0.0, 0.0), 0.0).ramp(2), vec![Color::TRANSPARENT; 2]);
+    }
+
+    #[test]
+    fn transparent_stops_keep_the_color() {
+        let gradient = Gradient::linear((0.0, 0.0), (1.0, 0.0))
+            .stop(0.0, Color::RED)
+            .stop(1.0, Color::TRANSPARENT);
+        let middle = gradient.ramp(3)[1];
+        assert_eq!(middle, Color::rgba(1.0, 0.0, 0.0, 0.5));
+    }
+
+    #[test]
+    fn stops_at_the_same_offset_keep_their_order() {
+        let gradient = Gradient::linear((0.0, 0.0), (1.0, 0.0))
+            .stop(0.5, Color::RED)
+            .stop(0.5, Color::BLUE);
+        let ramp = gradient.ramp(3);
+        assert_eq!(ramp[0], Color::RED);
+        assert_eq!(ramp[1], Color::BLUE);
+    }
+}
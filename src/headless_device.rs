@@ -0,0 +1,174 @@
+use anyhow::Result;
+use image::RgbaImage;
+use log::{info, warn};
+use winit::dpi::PhysicalSize;
+
+use crate::camera::Camera2D;
+use crate::capture::{self, FrameCapture};
+use crate::render_common::{Projection, RenderBase};
+use crate::render_target::{FrameOutput, RenderTarget};
+
+// Same as `WindowedDevice` but without a window; frames are rendered into
+// a texture. Used on machines without a display (CI) and in tests.
+pub struct HeadlessDevice {
+    // Logical representaion of the GPU device.
+    pub device: wgpu::Device,
+    // Sends data and encoded commands to GPU
+    pub queue: wgpu::Queue,
+    // Texture every frame is rendered into. It can be copied back to CPU.
+    pub texture: wgpu::Texture,
+    pub format: wgpu::TextureFormat,
+    pub size: PhysicalSize<u32>,
+    capture: FrameCapture,
+}
+
+impl HeadlessDevice {
+    pub async fn new(size: PhysicalSize<u32>) -> Self {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            dx12_shader_compiler: Default::default(),
+            flags: wgpu::InstanceFlags::default(),
+            gles_minor_version: wgpu::Gles3MinorVersion::default(),
+        });
+
+        let adapter = match instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                compatible_surface: None,
+                force_fallback_adapter: false,
+            })
+            .await
+        {
+            Some(adapter) => adapter,
+            None => {
+                warn!("no GPU adapter found; falling back to the software adapter");
+                instance
+                    .request_adapter(&wgpu::RequestAdapterOptions {
+                        power_preference: wgpu::PowerPreference::default(),
+                        compatible_surface: None,
+                        force_fallback_adapter: true,
+                    })
+                    .await
+                    .expect("Neither GPU nor software adapter is available")
+            }
+        };
+        info!("headless adapter: {:?}", adapter.get_info());
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: Some("Headless GPU device"),
+                    features: wgpu::Features::empty(),
+                    // Software adapters usually don't support the default
+                    // limits.
+                    limits: wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+                },
+                None, // Trace path
+            )
+            .await
+            .unwrap();
+
+        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        let texture = Self::create_texture(&device, format, size);
+
+        Self {
+            device,
+            queue,
+            texture,
+            format,
+            size,
+            capture: FrameCapture::default(),
+        }
+    }
+
+    pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
+        if new_size.width > 0 && new_size.height > 0 {
+            self.size = new_size;
+            self.texture = Self::create_texture(&self.device, self.format, new_size);
+        }
+    }
+
+    // Prepares the renderer, renders a single frame and reads it back.
+    pub fn render_to_image(&mut self, renderer: &dyn RenderBase) -> Result<RgbaImage> {
+        self.render_to_image_with_camera(renderer, &Camera2D::default())
+    }
+
+    // Same as `render_to_image` with the scene seen through the camera.
+    pub fn render_to_image_with_camera(&mut self, renderer: &dyn RenderBase, camera: &Camera2D) -> Result<RgbaImage> {
+        let mut projection = Projection::new(self);
+        projection.update(self, self.size, camera);
+        let mut prepared = renderer.prepare(self, &projection.bind_group_layout);
+        self.capture_next_frame();
+        prepared.render(self, &projection);
+        self.take_captured_frame()
+    }
+
+    fn create_texture(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        size: PhysicalSize<u32>,
+    ) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Headless Render Target"),
+            size: wgpu::Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+}
+
+impl RenderTarget for HeadlessDevice {
+    fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+
+    fn queue(&self) -> &wgpu::Queue {
+        &self.queue
+    }
+
+    fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    fn size(&self) -> PhysicalSize<u32> {
+        self.size
+    }
+
+    fn prepare_encoder(
+        &self,
+    ) -> Result<(wgpu::CommandEncoder, wgpu::TextureView, FrameOutput), wgpu::SurfaceError> {
+        let view = self
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("GPU Encoder"),
+            });
+        Ok((encoder, view, FrameOutput::Offscreen))
+    }
+
+    fn present(&mut self, output: FrameOutput) {
+        if self.capture.is_requested() {
+            let frame = capture::read_texture(&self.device, &self.queue, &self.texture);
+            self.capture.store(frame);
+        }
+        output.present();
+    }
+
+    fn capture_next_frame(&mut self) {
+        self.capture.request();
+    }
+
+    fn take_captured_frame(&mut self) -> Result<RgbaImage> {
+        self.capture.take()
+    }
+}
@@ -1,12 +1,22 @@
-mod math;
+pub mod math;
+mod curve;
+mod triangulate;
 pub mod raw;
+pub mod color;
 pub mod renderer_1;
 pub mod renderer_1_test;
 //pub mod renderer_2;
 //pub mod renderer_2_test;
 pub mod statistics;
-mod texture;
+pub mod texture;
 pub mod windowed_device;
+pub mod headless_device;
+pub mod render_target;
+pub mod capture;
 pub mod constants;
 pub mod render_common;
-
+pub mod path;
+pub mod gradient;
+pub mod dash;
+pub mod camera;
+pub mod atlas;
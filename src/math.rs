@@ -1,4 +1,4 @@
-use std::ops::{Add, Div, Neg, Sub};
+use std::ops::{Add, Div, Mul, Neg, Sub};
 
 use crate::raw::Gpu;
 
@@ -24,7 +24,7 @@ where
 }
 
 // NOTE: This is highly questionable inmplementaion of `Add` trait.
-impl<'a, T> Add<T> for &'a Vector2<T>
+impl<T> Add<T> for &Vector2<T>
 where
     T: Add<Output = T> + Copy,
 {
@@ -38,7 +38,7 @@ where
     }
 }
 
-impl<'a, T> Add<&Vector2<T>> for &'a Vector2<T>
+impl<T> Add<&Vector2<T>> for &Vector2<T>
 where
     T: Add<Output = T> + Copy,
 {
@@ -52,6 +52,83 @@ where
     }
 }
 
+impl<T: Sized + Gpu> Gpu for Vector2<T> {}
+
+impl<T> Sub<Vector2<T>> for Vector2<T>
+where
+    T: Sub<Output = T>,
+{
+    type Output = Self;
+    #[inline]
+    fn sub(self, other: Self) -> Self::Output {
+        Self {
+            x: self.x - other.x,
+            y: self.y - other.y,
+        }
+    }
+}
+
+impl<T> Mul<T> for Vector2<T>
+where
+    T: Mul<Output = T> + Copy,
+{
+    type Output = Self;
+    #[inline]
+    fn mul(self, other: T) -> Self::Output {
+        Self {
+            x: self.x * other,
+            y: self.y * other,
+        }
+    }
+}
+
+impl<T> Neg for Vector2<T>
+where
+    T: Neg<Output = T>,
+{
+    type Output = Self;
+    #[inline]
+    fn neg(self) -> Self::Output {
+        Self {
+            x: -self.x,
+            y: -self.y,
+        }
+    }
+}
+
+impl Vector2<f32> {
+    #[inline]
+    pub fn dot(self, other: Self) -> f32 {
+        self.x * other.x + self.y * other.y
+    }
+
+    // Z component of the 3D cross product; positive when `other` is counter
+    // clockwise from `self`.
+    #[inline]
+    pub fn cross(self, other: Self) -> f32 {
+        self.x * other.y - self.y * other.x
+    }
+
+    #[inline]
+    pub fn length(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    #[inline]
+    pub fn normalized(self) -> Self {
+        self * (1.0 / self.length())
+    }
+
+    // Rotated by 90 degrees counter clockwise.
+    #[inline]
+    pub fn perpendicular(self) -> Self {
+        Self {
+            x: -self.y,
+            y: self.x,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 #[repr(C, packed)]
 pub struct Vector3<T> {
@@ -76,7 +153,7 @@ where
 }
 
 // NOTE: This is highly questionable inmplementaion of `Add` trait.
-impl<'a, T> Add<T> for &'a Vector3<T>
+impl<T> Add<T> for &Vector3<T>
 where
     T: Add<Output = T> + Copy,
 {
@@ -91,7 +168,7 @@ where
     }
 }
 
-impl<'a, T> Add<&Vector3<T>> for &'a Vector3<T>
+impl<T> Add<&Vector3<T>> for &Vector3<T>
 where
     T: Add<Output = T> + Copy,
 {
@@ -131,7 +208,7 @@ where
     }
 }
 
-impl<'a, T> Add<&Vector4<T>> for &'a Vector4<T>
+impl<T> Add<&Vector4<T>> for &Vector4<T>
 where
     T: Add<Output = T> + Copy,
 {
@@ -234,3 +311,210 @@ where
         c3r3: T::one(),
     }
 }
+
+// 2D affine transform; the columns are the images of the x and y axes and the
+// translation. Stored in column-major like the other matrixes.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[repr(C, packed)]
+pub struct Affine2 {
+    pub x_axis: Vector2<f32>,
+    pub y_axis: Vector2<f32>,
+    pub translation: Vector2<f32>,
+}
+
+impl Gpu for Affine2 {}
+
+impl Default for Affine2 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Affine2 {
+    pub const IDENTITY: Affine2 = Affine2 {
+        x_axis: Vector2 { x: 1.0, y: 0.0 },
+        y_axis: Vector2 { x: 0.0, y: 1.0 },
+        translation: Vector2 { x: 0.0, y: 0.0 },
+    };
+
+    pub fn translation(x: f32, y: f32) -> Self {
+        Affine2 {
+            translation: Vector2 { x, y },
+            ..Self::IDENTITY
+        }
+    }
+
+    // Counter clockwise rotation by the angle in radians.
+    pub fn rotation(angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Affine2 {
+            x_axis: Vector2 { x: cos, y: sin },
+            y_axis: Vector2 { x: -sin, y: cos },
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn scale(x: f32, y: f32) -> Self {
+        Affine2 {
+            x_axis: Vector2 { x, y: 0.0 },
+            y_axis: Vector2 { x: 0.0, y },
+            ..Self::IDENTITY
+        }
+    }
+
+    // Shears the x coordinates along y by the angle `x` and the y coordinates
+    // along x by the angle `y`, both in radians.
+    pub fn skew(x: f32, y: f32) -> Self {
+        Affine2 {
+            x_axis: Vector2 { x: 1.0, y: y.tan() },
+            y_axis: Vector2 { x: x.tan(), y: 1.0 },
+            ..Self::IDENTITY
+        }
+    }
+
+    // The same transform with the point (x, y) as its origin, e.g. rotation
+    // around the point.
+    pub fn around(self, x: f32, y: f32) -> Self {
+        Self::translation(x, y) * self * Self::translation(-x, -y)
+    }
+
+    pub fn transform_point(&self, point: Vector2<f32>) -> Vector2<f32> {
+        self.transform_vector(point) + self.translation
+    }
+
+    pub fn transform_vector(&self, vector: Vector2<f32>) -> Vector2<f32> {
+        self.x_axis * vector.x + self.y_axis * vector.y
+    }
+
+    pub fn determinant(&self) -> f32 {
+        self.x_axis.cross(self.y_axis)
+    }
+
+    // How much the transform scales lengths on average; used for the widths
+    // of lines.
+    pub fn average_scale(&self) -> f32 {
+        self.determinant().abs().sqrt()
+    }
+
+    // Transform undoing this one; `None` when it flattens the plane to a line
+    // or a point.
+    pub fn inverse(&self) -> Option<Self> {
+        let determinant = self.determinant();
+        if determinant == 0.0 || !determinant.is_finite() {
+            return None;
+        }
+        let linear = Affine2 {
+            x_axis: Vector2 {
+                x: self.y_axis.y / determinant,
+                y: -self.x_axis.y / determinant,
+            },
+            y_axis: Vector2 {
+                x: -self.y_axis.x / determinant,
+                y: self.x_axis.x / determinant,
+            },
+            translation: Vector2 { x: 0.0, y: 0.0 },
+        };
+        Some(Affine2 {
+            translation: -linear.transform_vector(self.translation),
+            ..linear
+        })
+    }
+}
+
+// `a * b` transforms by `b` first and then by `a`.
+impl Mul<Affine2> for Affine2 {
+    type Output = Affine2;
+    #[inline]
+    fn mul(self, other: Affine2) -> Affine2 {
+        Affine2 {
+            x_axis: self.transform_vector(other.x_axis),
+            y_axis: self.transform_vector(other.y_axis),
+            translation: self.transform_point(other.translation),
+        }
+    }
+}
+
+// Applies the 2D transform before the matrix, e.g. a view before the
+// projection.
+impl Mul<Affine2> for Matrix4x4<f32> {
+    type Output = Matrix4x4<f32>;
+    #[inline]
+    fn mul(self, other: Affine2) -> Matrix4x4<f32> {
+        let Affine2 {
+            x_axis,
+            y_axis,
+            translation,
+        } = other;
+        let column0 = [self.c0r0, self.c0r1, self.c0r2, self.c0r3];
+        let column1 = [self.c1r0, self.c1r1, self.c1r2, self.c1r3];
+        let column = |x: f32, y: f32, row: usize| column0[row] * x + column1[row] * y;
+        Matrix4x4 {
+            c0r0: column(x_axis.x, x_axis.y, 0),
+            c0r1: column(x_axis.x, x_axis.y, 1),
+            c0r2: column(x_axis.x, x_axis.y, 2),
+            c0r3: column(x_axis.x, x_axis.y, 3),
+            c1r0: column(y_axis.x, y_axis.y, 0),
+            c1r1: column(y_axis.x, y_axis.y, 1),
+            c1r2: column(y_axis.x, y_axis.y, 2),
+            c1r3: column(y_axis.x, y_axis.y, 3),
+            c3r0: column(translation.x, translation.y, 0) + self.c3r0,
+            c3r1: column(translation.x, translation.y, 1) + self.c3r1,
+            c3r2: column(translation.x, translation.y, 2) + self.c3r2,
+            c3r3: column(translation.x, translation.y, 3) + self.c3r3,
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: Vector2<f32>, b: Vector2<f32>) {
+        assert!((a - b).length() < 1.0e-5, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn transforms_are_applied_right_to_left() {
+        let transform = Affine2::translation(10.0, 0.0) * Affine2::scale(2.0, 3.0);
+        assert_near(
+            transform.transform_point(Vector2 { x: 1.0, y: 1.0 }),
+            Vector2 { x: 12.0, y: 3.0 },
+        );
+    }
+
+    #[test]
+    fn rotation_around_point() {
+        let transform = Affine2::rotation(std::f32::consts::FRAC_PI_2).around(5.0, 5.0);
+        assert_near(
+            transform.transform_point(Vector2 { x: 5.0, y: 5.0 }),
+            Vector2 { x: 5.0, y: 5.0 },
+        );
+        assert_near(
+            transform.transform_point(Vector2 { x: 6.0, y: 5.0 }),
+            Vector2 { x: 5.0, y: 6.0 },
+        );
+    }
+
+    #[test]
+    fn inverse_undoes_the_transform() {
+        let transform = Affine2::translation(3.0, -2.0) * Affine2::rotation(0.7) * Affine2::scale(2.0, 0.5);
+        let point = Vector2 { x: 4.0, y: 7.0 };
+        let inverse = transform.inverse().unwrap();
+        assert_near(inverse.transform_point(transform.transform_point(point)), point);
+        assert_eq!(Affine2::scale(0.0, 1.0).inverse(), None);
+    }
+
+    #[test]
+    fn skew_keeps_area() {
+        let transform = Affine2::skew(0.5, 0.0);
+        assert!((transform.determinant() - 1.0).abs() < 1.0e-6);
+        assert_near(
+            transform.transform_point(Vector2 { x: 0.0, y: 2.0 }),
+            Vector2 {
+                x: 2.0 * 0.5f32.tan(),
+                y: 2.0,
+            },
+        );
+    }
+}
@@ -0,0 +1,238 @@
+use crate::curve;
+use crate::math::{Affine2, Vector2};
+
+// Which parts of a path are inside when the path crosses itself or has
+// subpaths inside each other.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FillRule {
+    // Inside where the outline winds around the point any number of times in
+    // total; subpaths going the other way cut holes.
+    #[default]
+    NonZero,
+    // Inside where a ray from the point crosses the outline an odd number of
+    // times.
+    EvenOdd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PathElement {
+    MoveTo(Vector2<f32>),
+    LineTo(Vector2<f32>),
+    QuadTo(Vector2<f32>, Vector2<f32>),
+    CubicTo(Vector2<f32>, Vector2<f32>, Vector2<f32>),
+    Close,
+}
+
+// Outline made of straight lines and Bézier curves. It can have several
+// subpaths, every one starts with `move_to`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Path {
+    elements: Vec<PathElement>,
+}
+
+// Part of a path between two `move_to`s flattened to line segments.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Subpath {
+    pub points: Vec<Vector2<f32>>,
+    pub closed: bool,
+}
+
+impl Path {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Starts a new subpath at the point.
+    pub fn move_to(&mut self, x: f32, y: f32) -> &mut Self {
+        self.elements.push(PathElement::MoveTo(Vector2 { x, y }));
+        self
+    }
+
+    pub fn line_to(&mut self, x: f32, y: f32) -> &mut Self {
+        self.elements.push(PathElement::LineTo(Vector2 { x, y }));
+        self
+    }
+
+    // Quadratic Bézier curve with the control point (cx, cy).
+    pub fn quad_to(&mut self, cx: f32, cy: f32, x: f32, y: f32) -> &mut Self {
+        self.elements
+            .push(PathElement::QuadTo(Vector2 { x: cx, y: cy }, Vector2 { x, y }));
+        self
+    }
+
+    // Cubic Bézier curve with the control points (c1x, c1y) and (c2x, c2y).
+    pub fn cubic_to(&mut self, c1x: f32, c1y: f32, c2x: f32, c2y: f32, x: f32, y: f32) -> &mut Self {
+        self.elements.push(PathElement::CubicTo(
+            Vector2 { x: c1x, y: c1y },
+            Vector2 { x: c2x, y: c2y },
+            Vector2 { x, y },
+        ));
+        self
+    }
+
+    // Connects the current point with the start of the subpath; the next
+    // element continues from the start.
+    pub fn close(&mut self) -> &mut Self {
+        self.elements.push(PathElement::Close);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    // Path with every point and control point transformed; Bézier curves stay
+    // the same curves under affine transforms.
+    pub(crate) fn transformed(&self, transform: Affine2) -> Path {
+        let point = |point| transform.transform_point(point);
+        let elements = self
+            .elements
+            .iter()
+            .map(|element| match *element {
+                PathElement::MoveTo(to) => PathElement::MoveTo(point(to)),
+                PathElement::LineTo(to) => PathElement::LineTo(point(to)),
+                PathElement::QuadTo(control, to) => PathElement::QuadTo(point(control), point(to)),
+                PathElement::CubicTo(control1, control2, to) => {
+                    PathElement::CubicTo(point(control1), point(control2), point(to))
+                }
+                PathElement::Close => PathElement::Close,
+            })
+            .collect();
+        Path { elements }
+    }
+
+    // Subpaths with the curves flattened to line segments closer to them than
+    // `tolerance`. Elements before the first `move_to` start at the origin.
+    pub(crate) fn flatten(&self, tolerance: f32) -> Vec<Subpath> {
+        let mut subpaths = Vec::new();
+        let mut current = Subpath {
+            points: vec![Vector2 { x: 0.0, y: 0.0 }],
+            closed: false,
+        };
+        for element in &self.elements {
+            let last = *current.points.last().unwrap();
+            match *element {
+                PathElement::MoveTo(point) => {
+                    let previous = std::mem::replace(
+                        &mut current,
+                        Subpath {
+                            points: vec![point],
+                            closed: false,
+                        },
+                    );
+                    if previous.points.len() > 1 {
+                        subpaths.push(previous);
+                    }
+                }
+                PathElement::LineTo(point) => current.points.push(point),
+                PathElement::QuadTo(control, end) => {
+                    curve::flatten_quadratic(last, control, end, tolerance, &mut current.points)
+                }
+                PathElement::CubicTo(control1, control2, end) => curve::flatten_cubic(
+                    last,
+                    control1,
+                    control2,
+                    end,
+                    tolerance,
+                    &mut current.points,
+                ),
+                PathElement::Close => {
+                    let start = current.points[0];
+                    let previous = std::mem::replace(
+                        &mut current,
+                        Subpath {
+                            points: vec![start],
+                            closed: false,
+                        },
+                    );
+                    if previous.points.len() > 1 {
+                        subpaths.push(Subpath {
+                            closed: true,
+                            ..previous
+                        });
+                    }
+                }
+            }
+        }
+        if current.points.len() > 1 {
+            subpaths.push(current);
+        }
+        for subpath in &mut subpaths {
+            subpath.points.dedup();
+        }
+        subpaths
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f32, y: f32) -> Vector2<f32> {
+        Vector2 { x, y }
+    }
+
+    #[test]
+    fn subpaths_are_split_by_move_to_and_close() {
+        let mut path = Path::new();
+        path.move_to(0.0, 0.0)
+            .line_to(10.0, 0.0)
+            .line_to(10.0, 10.0)
+            .close()
+            .line_to(0.0, 10.0)
+            .move_to(20.0, 20.0)
+            .line_to(30.0, 20.0);
+
+        assert_eq!(
+            path.flatten(0.25),
+            vec![
+                Subpath {
+                    points: vec![point(0.0, 0.0), point(10.0, 0.0), point(10.0, 10.0)],
+                    closed: true,
+                },
+                Subpath {
+                    points: vec![point(0.0, 0.0), point(0.0, 10.0)],
+                    closed: false,
+                },
+                Subpath {
+                    points: vec![point(20.0, 20.0), point(30.0, 20.0)],
+                    closed: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn curves_end_at_their_end_points() {
+        let mut path = Path::new();
+        path.move_to(0.0, 0.0)
+            .quad_to(50.0, 50.0, 100.0, 0.0)
+            .cubic_to(100.0, -50.0, 0.0, -50.0, 0.0, 0.0);
+
+        let subpaths = path.flatten(0.25);
+        assert_eq!(subpaths.len(), 1);
+        let points = &subpaths[0].points;
+        assert!(points.len() > 4);
+        assert!(points.contains(&point(100.0, 0.0)));
+        assert_eq!(points.last(), Some(&point(0.0, 0.0)));
+    }
+
+    #[test]
+    fn transformed_paths_move_every_point() {
+        let mut path = Path::new();
+        path.move_to(1.0, 0.0).quad_to(1.0, 1.0, 0.0, 1.0).close();
+
+        let mut expected = Path::new();
+        expected.move_to(3.0, 0.0).quad_to(3.0, 2.0, 2.0, 2.0).close();
+        let transform = Affine2::translation(2.0, 0.0) * Affine2::scale(1.0, 2.0);
+        assert_eq!(path.transformed(transform), expected);
+    }
+
+    #[test]
+    fn empty_subpaths_are_skipped() {
+        let mut path = Path::new();
+        path.move_to(0.0, 0.0).move_to(5.0, 5.0).close().move_to(1.0, 1.0);
+
+        assert!(path.flatten(0.25).is_empty());
+    }
+}
@@ -1,32 +1,32 @@
-use log::info;
-use wgpu::{BindGroup, Buffer, util::DeviceExt, BindGroupLayout};
-use winit::{event_loop::EventLoop, window::Window, event::Event, event::WindowEvent::{Resized, CloseRequested, KeyboardInput, MouseInput, RedrawRequested}, dpi::PhysicalSize, keyboard::NamedKey};
-use crate::{windowed_device::WindowedDevice, math, constants::NUMBER_OF_FRAMES, raw::Raw};
+use std::path::{Path, PathBuf};
 
-pub struct RendererRunner {
-    wd: WindowedDevice,
-    projection_bind_group: BindGroup,
-    projection_buffer: Buffer,
-    projection_bind_group_layout: BindGroupLayout,
-    renderers: Vec<Box<dyn RenderBase>>,
-}
+use log::{error, info};
+use wgpu::{BindGroup, Buffer, util::DeviceExt, BindGroupLayout};
+use winit::{event_loop::EventLoop, window::Window, event::{Event, ElementState, MouseButton, MouseScrollDelta}, event::WindowEvent::{Resized, CloseRequested, KeyboardInput, MouseInput, CursorMoved, MouseWheel, RedrawRequested}, dpi::{PhysicalPosition, PhysicalSize}, keyboard::NamedKey};
+use crate::{windowed_device::WindowedDevice, render_target::RenderTarget, math, constants::NUMBER_OF_FRAMES, raw::Raw, camera::Camera2D};
 
-impl RendererRunner {
-    pub async fn new(renderers: Vec<Box<dyn RenderBase>>, event_loop: &mut EventLoop<()>) -> Self {
-        let window = Window::new(&event_loop).unwrap();
-        let mut wd = WindowedDevice::new(window).await;
+// Zoom factor for one line of the mouse wheel.
+const WHEEL_ZOOM_STEP: f32 = 1.1;
+// Touchpads scroll in pixels; about this many pixels make one wheel line.
+const PIXELS_PER_WHEEL_LINE: f32 = 40.0;
 
-        let (projection_buffer, projection_bind_group_layout, projection_bind_group) = Self::create_projection(&mut wd);
-        Self {wd, projection_bind_group, projection_buffer, projection_bind_group_layout, renderers}
-    }
+// View of the camera and the orthographic projection over the pixels of the
+// render target. It is shared by all the renderers as the bind group 0.
+pub struct Projection {
+    pub buffer: Buffer,
+    pub bind_group_layout: BindGroupLayout,
+    pub bind_group: BindGroup,
+    // Camera of the last update; the renderers follow it where the projection
+    // isn't enough, like for the scissor rectangles.
+    camera: Camera2D,
+}
 
-    fn create_projection(wd: &mut WindowedDevice) -> (Buffer, BindGroupLayout, BindGroup){
-        let size = wd.window.inner_size();
-        let perspective_matrix: math::Matrix4x4<f32> =
-            math::ortho(0.0, size.width as f32, 0.0, size.height as f32, 0.0, 1.0);
+impl Projection {
+    pub fn new(target: &dyn RenderTarget) -> Self {
+        let perspective_matrix: math::Matrix4x4<f32> = Camera2D::default().matrix(target.size());
 
-        let projection_buffer = wd
-            .device
+        let buffer = target
+            .device()
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("Projection Buffer"),
                 contents: perspective_matrix.get_raw(),
@@ -34,8 +34,8 @@ impl RendererRunner {
                 // TODO: Check if the COPY_DST is needed.
             });
 
-        let projection_bind_group_layout =
-            wd.device
+        let bind_group_layout =
+            target.device()
                 .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                     label: Some("Projection Bind Group Descriptor"),
                     entries: &[wgpu::BindGroupLayoutEntry {
@@ -50,23 +50,93 @@ impl RendererRunner {
                     }],
                 });
 
-        let projection_bind_group = wd.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &projection_bind_group_layout,
+        let bind_group = target.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
             entries: &[wgpu::BindGroupEntry {
                 binding: 0,
-                resource: projection_buffer.as_entire_binding(),
+                resource: buffer.as_entire_binding(),
             }],
             label: Some("Projection Bind Group"),
         });
 
-        (projection_buffer, projection_bind_group_layout, projection_bind_group)
+        Self {buffer, bind_group_layout, bind_group, camera: Camera2D::default()}
+    }
+
+    pub fn camera(&self) -> &Camera2D {
+        &self.camera
+    }
+
+    pub fn update(&mut self, target: &dyn RenderTarget, new_size: PhysicalSize<u32>, camera: &Camera2D) {
+        self.camera = *camera;
+        let projection_matrix: math::Matrix4x4<f32> = camera.matrix(new_size);
+        target.queue().write_buffer(
+            &self.buffer,
+            0,
+            projection_matrix.get_raw(),
+        );
+    }
+}
+
+pub struct RendererRunner {
+    wd: WindowedDevice,
+    projection: Projection,
+    renderers: Vec<Box<dyn RenderBase>>,
+    camera: Camera2D,
+    // Last cursor position in pixels with the origin at the bottom left
+    // corner like the projection.
+    cursor: math::Vector2<f32>,
+    // Whether the left button is down and the cursor pans the camera.
+    dragging: bool,
+    // Frame number and the path the frame is saved to as PNG.
+    frame_dump: Option<(u32, PathBuf)>,
+}
+
+impl RendererRunner {
+    pub async fn new(renderers: Vec<Box<dyn RenderBase>>, event_loop: &mut EventLoop<()>) -> Self {
+        let window = Window::new(event_loop).unwrap();
+        let wd = WindowedDevice::new(window).await;
+
+        let projection = Projection::new(&wd);
+        Self {
+            wd,
+            projection,
+            renderers,
+            camera: Camera2D::default(),
+            cursor: math::Vector2 { x: 0.0, y: 0.0 },
+            dragging: false,
+            frame_dump: None,
+        }
+    }
+
+    // Camera the frames start with; it is moved by the mouse afterwards.
+    pub fn set_camera(&mut self, camera: Camera2D) {
+        self.camera = camera;
+        self.projection.update(&self.wd, self.wd.size(), &self.camera);
+    }
+
+    // Converts the window position to the pixels with the y axis going up.
+    fn cursor_pixel(&self, position: PhysicalPosition<f64>) -> math::Vector2<f32> {
+        math::Vector2 {
+            x: position.x as f32,
+            y: self.wd.size().height as f32 - position.y as f32,
+        }
+    }
+
+    fn update_camera(&mut self) {
+        self.projection.update(&self.wd, self.wd.size(), &self.camera);
+        self.wd.window.request_redraw();
+    }
+
+    // Saves the `frame`-th rendered frame (counted from 1) as PNG.
+    pub fn dump_frame<P: Into<PathBuf>>(&mut self, frame: u32, path: P) {
+        self.frame_dump = Some((frame, path.into()));
     }
 
     pub fn run(&mut self, event_loop: EventLoop<()>) {
         let mut render_count: u32 = 0;
         let current_renderer_base : Box<dyn RenderBase> = self.renderers.pop().expect("Renderer runner needs to be initialized with not enpty list of renderes!");
         info!("preparing the renderer instance");
-        let mut current_renderer: Box<dyn PreparedRenderBase> = current_renderer_base.prepare(&mut self.wd, &self.projection_bind_group_layout);
+        let mut current_renderer: Box<dyn PreparedRenderBase> = current_renderer_base.prepare(&mut self.wd, &self.projection.bind_group_layout);
         info!("preparation of the  the renderer instance is done");
 
 
@@ -75,7 +145,8 @@ impl RendererRunner {
                 match event {
                     Resized(new_size) => {
                         info!("updating the projection matric after resize");
-                        self.update_projection(new_size);
+                        self.wd.resize(new_size);
+                        self.projection.update(&self.wd, new_size, &self.camera);
                     },
                     CloseRequested => elwt.exit(),
                     KeyboardInput { device_id: _, event, is_synthetic: _ } => {
@@ -84,11 +155,38 @@ impl RendererRunner {
                             elwt.exit()
                         }
                     },
-                    MouseInput { device_id: _, state: _, button: _ } => (),
+                    MouseInput { device_id: _, state, button: MouseButton::Left } => {
+                        self.dragging = state == ElementState::Pressed;
+                    },
+                    MouseInput { .. } => (),
+                    CursorMoved { device_id: _, position } => {
+                        let cursor = self.cursor_pixel(position);
+                        if self.dragging {
+                            self.camera.pan(cursor.x - self.cursor.x, cursor.y - self.cursor.y);
+                            self.update_camera();
+                        }
+                        self.cursor = cursor;
+                    },
+                    MouseWheel { device_id: _, delta, phase: _ } => {
+                        let lines = match delta {
+                            MouseScrollDelta::LineDelta(_, lines) => lines,
+                            MouseScrollDelta::PixelDelta(pixels) => pixels.y as f32 / PIXELS_PER_WHEEL_LINE,
+                        };
+                        self.camera.zoom_around(WHEEL_ZOOM_STEP.powf(lines), self.cursor);
+                        self.update_camera();
+                    },
                     RedrawRequested => {
                         info!("rendering as per the RedrawRequested was received");
-                        current_renderer.render(&mut self.wd, &self.projection_bind_group);
                         render_count += 1;
+                        match &self.frame_dump {
+                            Some((frame, path)) if *frame == render_count => {
+                                info!("saving frame {} to {}", frame, path.display());
+                                if let Err(err) = current_renderer.render_to_png(&mut self.wd, &self.projection, path) {
+                                    error!("saving frame {} failed: {:?}", frame, err);
+                                }
+                            },
+                            _ => current_renderer.render(&mut self.wd, &self.projection),
+                        }
                         if render_count > NUMBER_OF_FRAMES {
                             render_count = 0;
                             // TODO:
@@ -105,22 +203,54 @@ impl RendererRunner {
     }
 }
 
-impl RendererRunner {
-    fn update_projection(&mut self, new_size: PhysicalSize<u32>) {
-        let projection_matrix: math::Matrix4x4<f32> =
-            math::ortho(0.0, new_size.width as f32, 0.0, new_size.height as f32, 0.0, 1.0);
-        self.wd.queue.write_buffer(
-            &self.projection_buffer,
-            0,
-            projection_matrix.get_raw(),
-        );
-    }
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AntiAliasing {
+    // Hard edges.
+    None,
+    // Coverage of the edge pixels is computed in the fragment shaders from the
+    // signed distance of the shapes and used as alpha.
+    #[default]
+    Analytic,
+    // Multisampling with 4 samples. The triangle edges are multisampled, the
+    // coverage from the shaders is blended like `Analytic`. Other counts
+    // depend on the adapter and the format, 4 is the one wgpu guarantees for
+    // all the formats without the adapter specific format features.
+    Msaa,
 }
 
+// Samples of every pixel with `AntiAliasing::Msaa`.
+const MSAA_SAMPLE_COUNT: u32 = 4;
+
+impl AntiAliasing {
+    pub fn sample_count(self) -> u32 {
+        match self {
+            AntiAliasing::Msaa => MSAA_SAMPLE_COUNT,
+            _ => 1,
+        }
+    }
+
+    // Whether the shaders compute the coverage of the edges.
+    pub fn coverage(self) -> bool {
+        self != AntiAliasing::None
+    }
+}
 
 pub trait RenderBase {
-    fn prepare(&self, windowed_device: &mut WindowedDevice, projection_bind_group_layout: &BindGroupLayout) -> Box<dyn PreparedRenderBase>;
+    fn prepare(&self, target: &mut dyn RenderTarget, projection_bind_group_layout: &BindGroupLayout) -> Box<dyn PreparedRenderBase>;
 }
 pub trait PreparedRenderBase {
-    fn render(&mut self, windowed_device: &mut WindowedDevice, perspective_bind_group: &BindGroup);
+    fn render(&mut self, target: &mut dyn RenderTarget, projection: &Projection);
+
+    // Renders the frame as usual and additionally saves it as PNG.
+    fn render_to_png(&mut self, target: &mut dyn RenderTarget, projection: &Projection, path: &Path) -> anyhow::Result<()> {
+        target.capture_next_frame();
+        self.render(target, projection);
+        let frame = target.take_captured_frame()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        frame.save_with_format(path, image::ImageFormat::Png)?;
+        Ok(())
+    }
 }
@@ -0,0 +1,39 @@
+use anyhow::Result;
+use image::RgbaImage;
+use winit::dpi::PhysicalSize;
+
+// Something renderers can draw into. It is either the window surface
+// (`WindowedDevice`) or an offscreen texture (`HeadlessDevice`), so the same
+// `RenderBase` implementation can be used for both.
+pub trait RenderTarget {
+    fn device(&self) -> &wgpu::Device;
+    fn queue(&self) -> &wgpu::Queue;
+    // Format of the texture the frames are rendered into. Pipelines need to be
+    // created with the same format.
+    fn format(&self) -> wgpu::TextureFormat;
+    fn size(&self) -> PhysicalSize<u32>;
+    fn prepare_encoder(
+        &self,
+    ) -> Result<(wgpu::CommandEncoder, wgpu::TextureView, FrameOutput), wgpu::SurfaceError>;
+    // Has to be called after the encoded commands were submitted.
+    fn present(&mut self, output: FrameOutput);
+    // The next presented frame is copied back to CPU and kept until it is
+    // taken by `take_captured_frame`.
+    fn capture_next_frame(&mut self);
+    fn take_captured_frame(&mut self) -> Result<RgbaImage>;
+}
+
+// Texture the current frame is rendered into. Surface textures need to be
+// presented, offscreen textures stay in the target and can be read back.
+pub enum FrameOutput {
+    Surface(wgpu::SurfaceTexture),
+    Offscreen,
+}
+
+impl FrameOutput {
+    pub(crate) fn present(self) {
+        if let FrameOutput::Surface(output) = self {
+            output.present();
+        }
+    }
+}
@@ -1,24 +1,254 @@
+use crate::color::Color;
 use crate::math;
 use crate::raw::{Gpu, Raw};
-use crate::render_common::{RenderBase, PreparedRenderBase};
+use crate::render_common::{AntiAliasing, RenderBase, PreparedRenderBase, Projection};
 use crate::statistics;
-use crate::windowed_device::WindowedDevice;
-use wgpu::{include_wgsl, BindGroupLayout, BindGroup, StoreOp};
+use crate::render_target::RenderTarget;
+use wgpu::{BindGroupLayout, BindGroup, StoreOp};
+use std::collections::BTreeMap;
 use std::time::Instant;
 use std::vec::Vec;
+use ab_glyph::FontArc;
+use image::{DynamicImage, GenericImageView};
+use std::borrow::Cow;
+use std::ops::Range;
+use std::sync::Arc;
 use std::{iter, mem};
 use wgpu::util::DeviceExt;
 
+mod clip;
+mod dash;
+mod ellipse;
+mod gradient;
+mod group;
+mod line;
+mod path;
+mod polygon;
+mod scene;
+mod sprite;
+mod table;
+mod text;
+
+use crate::atlas::Atlas;
+use crate::dash::Dash;
+use crate::gradient::Gradient;
+use crate::path::{FillRule, Path};
+use clip::{Clip, ClipShape, PreparedClips};
+use dash::Dashes;
+use ellipse::{Ellipse, ARC_KIND, ELLIPSE_INDICES, ELLIPSE_VERTICES, PIE_KIND};
+use gradient::Gradients;
+use group::{group_states, Group, GroupState, Grouped};
+pub use group::{GroupBuilder, GroupHandle};
+use line::{LineSegment, Outline, Polyline, LINE_INDICES, LINE_VERTICES};
+pub use line::{LineBuilder, LineCap, LineJoin};
+use path::{PathShape, PreparedPaths};
+use polygon::{PolygonShape, PolygonVertex};
+pub use path::PathBuilder;
+use scene::{Batch, DirtyMark, DrawKey, ShapeKind, Shapes};
+pub use scene::ShapeHandle;
+use sprite::{PreparedSprites, Sprite};
+pub use sprite::{AtlasImage, ImageBuilder, TextureHandle};
+use table::PreparedTables;
+use text::{GlyphAtlas, TextShape};
+pub use text::{FontHandle, TextAlign, TextBuilder};
+
+// Format of the stencil attachment used for filling the paths.
+const STENCIL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Stencil8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FillMode {
+    Stroke,
+    Fill,
+    FillAndStroke,
+}
+
+// Has to be kept in sync with the flags in the shaders.
+const STROKE_FLAG: u32 = 1;
+const FILL_FLAG: u32 = 2;
+
+impl FillMode {
+    fn flags(self) -> u32 {
+        match self {
+            FillMode::Stroke => STROKE_FLAG,
+            FillMode::Fill => FILL_FLAG,
+            FillMode::FillAndStroke => STROKE_FLAG | FILL_FLAG,
+        }
+    }
+}
+
+// How a layer is composed over the shapes below it. The modes are blended
+// by the fixed function blending of the pipelines, which can't read the
+// shapes below in the shader. As the frame is opaque, `Multiply` and `Screen`
+// are exact for translucent layers too. `Darken` and `Lighten` are exact only
+// for the opaque parts of the layer: the translucent parts are compared by
+// their premultiplied colors, so they darken too much and lighten too little.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    // The layer is painted over the shapes below.
+    #[default]
+    Normal,
+    // Colors are added, e.g. for heatmaps.
+    Additive,
+    // Colors are multiplied, the result is darker.
+    Multiply,
+    // Inverted colors are multiplied, the result is lighter.
+    Screen,
+    // The darker of the colors in every channel.
+    Darken,
+    // The lighter of the colors in every channel.
+    Lighten,
+}
+
+const BLEND_MODES: [BlendMode; 6] = [
+    BlendMode::Normal,
+    BlendMode::Additive,
+    BlendMode::Multiply,
+    BlendMode::Screen,
+    BlendMode::Darken,
+    BlendMode::Lighten,
+];
+
+impl BlendMode {
+    // Has to be kept in sync with the modes in renderer_1_layer.wgsl.
+    fn index(self) -> u32 {
+        match self {
+            BlendMode::Normal => 0,
+            BlendMode::Additive => 1,
+            BlendMode::Multiply => 2,
+            BlendMode::Screen => 3,
+            BlendMode::Darken => 4,
+            BlendMode::Lighten => 5,
+        }
+    }
+
+    // Blending of the premultiplied colors; the alpha is always composed as
+    // by `Normal`.
+    fn blend_state(self) -> wgpu::BlendState {
+        let color = |src_factor, dst_factor, operation| wgpu::BlendComponent {
+            src_factor,
+            dst_factor,
+            operation,
+        };
+        use wgpu::{BlendFactor, BlendOperation};
+        let color = match self {
+            BlendMode::Normal => return wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+            BlendMode::Additive => color(BlendFactor::One, BlendFactor::One, BlendOperation::Add),
+            BlendMode::Multiply => color(BlendFactor::Dst, BlendFactor::OneMinusSrcAlpha, BlendOperation::Add),
+            BlendMode::Screen => color(BlendFactor::OneMinusDst, BlendFactor::One, BlendOperation::Add),
+            BlendMode::Darken => color(BlendFactor::One, BlendFactor::One, BlendOperation::Min),
+            BlendMode::Lighten => color(BlendFactor::One, BlendFactor::One, BlendOperation::Max),
+        };
+        wgpu::BlendState {
+            color,
+            alpha: wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING.alpha,
+        }
+    }
+}
+
+// Shapes of one z-index composed over the shapes below as a whole.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Layer {
+    opacity: f32,
+    blend_mode: BlendMode,
+}
+
+impl Default for Layer {
+    fn default() -> Self {
+        Layer {
+            opacity: 1.0,
+            blend_mode: BlendMode::Normal,
+        }
+    }
+}
+
+// Instance of the quad composing a layer over the frame.
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+struct LayerInstance {
+    opacity: f32,
+    // `BlendMode::index`.
+    blend_mode: u32,
+}
+
+impl Gpu for LayerInstance {}
+
+impl LayerInstance {
+    fn buffer_description<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<LayerInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: mem::offset_of!(LayerInstance, opacity) as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::offset_of!(LayerInstance, blend_mode) as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+            ],
+        }
+    }
+}
+
+// Part of every shape instance describing how the shape is painted.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[repr(C, packed)]
+struct Style {
+    stroke_color: Color,
+    fill_color: Color,
+    // `FillMode` flags.
+    fill_mode: u32,
+    // Fades the whole shape, the stroke and the fill composed together.
+    opacity: f32,
+    // Gradients painted instead of the colors, see `Gradients::add`; 0 for
+    // the colors.
+    stroke_gradient: u32,
+    fill_gradient: u32,
+    // Dash pattern of the stroke, see `Dashes::add`; 0 for a solid stroke.
+    dash: u32,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            stroke_color: Color::YELLOW,
+            fill_color: Color::YELLOW,
+            fill_mode: FillMode::Stroke.flags(),
+            opacity: 1.0,
+            stroke_gradient: 0,
+            fill_gradient: 0,
+            dash: 0,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 #[repr(C, packed)]
 struct Circle {
     pos: math::Vector2<f32>, // Center position
     radius: f32,
     brush_size: f32,
+    style: Style,
+    transform: math::Affine2,
 }
 
 impl Gpu for Circle {}
 
+impl Grouped for Circle {
+    fn in_group(&self, group: &GroupState) -> Self {
+        let mut style = self.style;
+        style.opacity *= group.opacity;
+        Circle {
+            style,
+            transform: group.transform * self.transform,
+            ..self.clone()
+        }
+    }
+}
+
 impl Circle {
     fn buffer_description<'a>() -> wgpu::VertexBufferLayout<'a> {
         wgpu::VertexBufferLayout {
@@ -41,6 +271,65 @@ impl Circle {
                     shader_location: 4,
                     format: wgpu::VertexFormat::Float32,
                 },
+                wgpu::VertexAttribute {
+                    offset: (mem::size_of::<math::Vector2<f32>>() + 2 * mem::size_of::<f32>())
+                        as wgpu::BufferAddress,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::size_of::<math::Vector2<f32>>()
+                        + 2 * mem::size_of::<f32>()
+                        + mem::size_of::<Color>())
+                        as wgpu::BufferAddress,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::size_of::<math::Vector2<f32>>()
+                        + 2 * mem::size_of::<f32>()
+                        + 2 * mem::size_of::<Color>())
+                        as wgpu::BufferAddress,
+                    shader_location: 7,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(Circle, transform) + mem::offset_of!(math::Affine2, x_axis))
+                        as wgpu::BufferAddress,
+                    shader_location: 8,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(Circle, transform) + mem::offset_of!(math::Affine2, y_axis))
+                        as wgpu::BufferAddress,
+                    shader_location: 9,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(Circle, transform) + mem::offset_of!(math::Affine2, translation))
+                        as wgpu::BufferAddress,
+                    shader_location: 10,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(Circle, style) + mem::offset_of!(Style, opacity))
+                        as wgpu::BufferAddress,
+                    shader_location: 11,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                // The stroke and the fill gradient.
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(Circle, style) + mem::offset_of!(Style, stroke_gradient))
+                        as wgpu::BufferAddress,
+                    shader_location: 12,
+                    format: wgpu::VertexFormat::Uint32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(Circle, style) + mem::offset_of!(Style, dash))
+                        as wgpu::BufferAddress,
+                    shader_location: 13,
+                    format: wgpu::VertexFormat::Uint32,
+                },
             ],
         }
     }
@@ -95,7 +384,7 @@ const CIRCLE_VERTICES: &[Vertex] = &[
     },
 ];
 
-const CIRCLE_INDICES: &[u16] = &[0, 1, 3, 3, 2, 0];
+const CIRCLE_INDICES: &[u16] = &[0, 2, 3, 3, 1, 0];
 
 #[derive(Clone, Debug)]
 #[repr(C, packed)]
@@ -108,12 +397,17 @@ struct Rectangle {
     h: f32,
     #[allow(unused)]
     brush_size: f32,
+    style: Style,
+    // Top left, top right, bottom right and bottom left.
+    #[allow(unused)]
+    corner_radii: [f32; 4],
+    transform: math::Affine2,
 }
 
 impl Rectangle {
     fn buffer_description<'a>() -> wgpu::VertexBufferLayout<'a> {
         wgpu::VertexBufferLayout {
-            array_stride: mem::size_of::<Circle>() as wgpu::BufferAddress,
+            array_stride: mem::size_of::<Rectangle>() as wgpu::BufferAddress,
             step_mode: wgpu::VertexStepMode::Instance,
             attributes: &[
                 wgpu::VertexAttribute {
@@ -133,6 +427,70 @@ impl Rectangle {
                     shader_location: 4,
                     format: wgpu::VertexFormat::Float32,
                 },
+                wgpu::VertexAttribute {
+                    offset: (2 * mem::size_of::<math::Vector2<f32>>() + mem::size_of::<f32>())
+                        as wgpu::BufferAddress,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: (2 * mem::size_of::<math::Vector2<f32>>()
+                        + mem::size_of::<f32>()
+                        + mem::size_of::<Color>())
+                        as wgpu::BufferAddress,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: (2 * mem::size_of::<math::Vector2<f32>>()
+                        + mem::size_of::<f32>()
+                        + 2 * mem::size_of::<Color>())
+                        as wgpu::BufferAddress,
+                    shader_location: 7,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::offset_of!(Rectangle, corner_radii) as wgpu::BufferAddress,
+                    shader_location: 8,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(Rectangle, transform) + mem::offset_of!(math::Affine2, x_axis))
+                        as wgpu::BufferAddress,
+                    shader_location: 9,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(Rectangle, transform) + mem::offset_of!(math::Affine2, y_axis))
+                        as wgpu::BufferAddress,
+                    shader_location: 10,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(Rectangle, transform) + mem::offset_of!(math::Affine2, translation))
+                        as wgpu::BufferAddress,
+                    shader_location: 11,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(Rectangle, style) + mem::offset_of!(Style, opacity))
+                        as wgpu::BufferAddress,
+                    shader_location: 12,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                // The stroke and the fill gradient.
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(Rectangle, style) + mem::offset_of!(Style, stroke_gradient))
+                        as wgpu::BufferAddress,
+                    shader_location: 13,
+                    format: wgpu::VertexFormat::Uint32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(Rectangle, style) + mem::offset_of!(Style, dash))
+                        as wgpu::BufferAddress,
+                    shader_location: 14,
+                    format: wgpu::VertexFormat::Uint32,
+                },
             ],
         }
     }
@@ -140,6 +498,18 @@ impl Rectangle {
 
 impl Gpu for Rectangle {}
 
+impl Grouped for Rectangle {
+    fn in_group(&self, group: &GroupState) -> Self {
+        let mut style = self.style;
+        style.opacity *= group.opacity;
+        Rectangle {
+            style,
+            transform: group.transform * self.transform,
+            ..self.clone()
+        }
+    }
+}
+
 const RECTANGLE_VERTICES: &[Vertex] = &[
     Vertex {
         pos: math::Vector2 { x: -1.0, y: 1.0 },
@@ -159,11 +529,12 @@ const RECTANGLE_VERTICES: &[Vertex] = &[
     },
 ];
 
-const RECTANGLE_INDICES: &[u16] = &[0, 1, 3, 3, 2, 0];
+const RECTANGLE_INDICES: &[u16] = &[0, 2, 3, 3, 1, 0];
 
-struct Renderer1Prepared {
-    circles: Vec<Circle>,
-    rectangles: Vec<Rectangle>,
+// Renderer with the buffers and pipelines on the GPU. The shapes can still be
+// changed through `scene`; only the changes are uploaded with the next frame.
+pub struct Renderer1Prepared {
+    scene: Renderer1,
     circle_vertex_buffer: wgpu::Buffer,
     circle_index_buffer: wgpu::Buffer,
     circle_pipeline: wgpu::RenderPipeline,
@@ -172,175 +543,814 @@ struct Renderer1Prepared {
     rectangle_pipeline: wgpu::RenderPipeline,
     circle_instances_buffer: wgpu::Buffer,
     rectangle_instances_buffer: wgpu::Buffer,
+    ellipse_vertex_buffer: wgpu::Buffer,
+    ellipse_index_buffer: wgpu::Buffer,
+    ellipse_pipeline: wgpu::RenderPipeline,
+    ellipse_instances_buffer: wgpu::Buffer,
+    polygon_vertices: Vec<PolygonVertex>,
+    // Fill vertices and stroke segments of every polygon.
+    polygon_ranges: Vec<(Range<u32>, Range<u32>)>,
+    polygon_pipeline: wgpu::RenderPipeline,
+    polygon_vertex_buffer: wgpu::Buffer,
+    line_segments: Vec<LineSegment>,
+    // Segments of every polyline.
+    polyline_ranges: Vec<Range<u32>>,
+    // Shapes of all the kinds in the painter's order.
+    batches: Vec<Batch>,
+    // Groups of the scene combined with their parents, see `group_states`.
+    group_states: Vec<GroupState>,
+    line_vertex_buffer: wgpu::Buffer,
+    line_index_buffer: wgpu::Buffer,
+    line_pipeline: wgpu::RenderPipeline,
+    line_instances_buffer: wgpu::Buffer,
+    paths: PreparedPaths,
+    sprites: PreparedSprites,
+    glyphs: GlyphAtlas,
+    glyph_sprites: Vec<Sprite>,
+    // Glyph sprites of every text.
+    text_ranges: Vec<Range<u32>>,
+    tables: PreparedTables,
+    clips: PreparedClips,
+    // Compose the layers over the frame; indexed by the blend mode.
+    layer_pipelines: Vec<wgpu::RenderPipeline>,
+    layer_bind_group_layout: wgpu::BindGroupLayout,
+    // Instance of every layer of the frame.
+    layer_instances_buffer: wgpu::Buffer,
+    anti_aliasing: AntiAliasing,
+    // Multisampled color attachment; the frame is resolved into the target.
+    msaa_texture: Option<wgpu::Texture>,
+    stencil_texture: Option<wgpu::Texture>,
+    // Layers are drawn into this texture, resolved from the multisampled
+    // one, before they are composed over the frame.
+    layer_texture: Option<wgpu::Texture>,
+    layer_msaa_texture: Option<wgpu::Texture>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Renderer1 {
-    circles: Vec<Circle>,
-    rectangles: Vec<Rectangle>,
+    circles: Shapes<Circle>,
+    rectangles: Shapes<Rectangle>,
+    ellipses: Shapes<Ellipse>,
+    polylines: Shapes<Polyline>,
+    polygons: Shapes<PolygonShape>,
+    paths: Shapes<PathShape>,
+    images: Shapes<Sprite>,
+    // Decoded images drawn by the image shapes, see `TextureHandle`.
+    textures: Vec<Arc<DynamicImage>>,
+    texts: Shapes<TextShape>,
+    // Fonts of the texts, see `FontHandle`.
+    fonts: Vec<FontArc>,
+    // Sequence number of the next shape; keeps the order the shapes were
+    // added in across the kinds.
+    sequence: u64,
+    // The z-indices composed as a whole.
+    layers: BTreeMap<i32, Layer>,
+    gradients: Gradients,
+    dashes: Dashes,
+    // Clips of the shapes, see `push_clip_rectangle`, with the paths of the
+    // path clips.
+    clips: Vec<Clip>,
+    clip_paths: Vec<PathShape>,
+    // Clips pushed and not popped yet; the shapes are added in the last one.
+    clip_stack: Vec<u32>,
+    groups: Vec<Group>,
+    // Groups pushed and not popped yet with the depth of the clip stack when
+    // they were pushed; the shapes are added in the last one.
+    group_stack: Vec<(u32, usize)>,
+    // Whether any group was added or changed since the last upload.
+    groups_changed: bool,
+    anti_aliasing: AntiAliasing,
+}
+
+// Returned by the shape functions of `Renderer1` to set up the optional
+// properties of the shape which was just added, or by `edit_shape` to change
+// them later.
+pub struct ShapeBuilder<'a> {
+    style: &'a mut Style,
+    transform: &'a mut math::Affine2,
+    z_index: &'a mut i32,
+    gradients: &'a mut Gradients,
+    dashes: &'a mut Dashes,
+    dirty: DirtyMark<'a>,
+    handle: ShapeHandle,
+}
+
+impl ShapeBuilder<'_> {
+    pub fn stroke(mut self, color: Color) -> Self {
+        self.dirty.update(self.style, |style| {
+            style.stroke_color = color;
+            style.stroke_gradient = 0;
+        });
+        self
+    }
+
+    // Sets the fill color and turns the filling on; the stroke is kept unless
+    // it is turned off by `fill_mode`.
+    pub fn fill(mut self, color: Color) -> Self {
+        self.dirty.update(self.style, |style| {
+            style.fill_color = color;
+            style.fill_gradient = 0;
+            style.fill_mode |= FILL_FLAG;
+        });
+        self
+    }
+
+    // Paints the stroke with the gradient in the coordinates of the shape
+    // before its transform.
+    pub fn stroke_gradient(mut self, gradient: &Gradient) -> Self {
+        let gradient = self.gradients.add(gradient);
+        self.dirty.update(self.style, |style| style.stroke_gradient = gradient);
+        self
+    }
+
+    // Same as `fill` with the gradient in the coordinates of the shape before
+    // its transform.
+    pub fn fill_gradient(mut self, gradient: &Gradient) -> Self {
+        let gradient = self.gradients.add(gradient);
+        self.dirty.update(self.style, |style| {
+            style.fill_gradient = gradient;
+            style.fill_mode |= FILL_FLAG;
+        });
+        self
+    }
+
+    // Dashes the stroke of circles and rectangles; the other shapes keep
+    // their strokes solid.
+    pub fn dash(mut self, dash: &Dash) -> Self {
+        let dash = self.dashes.add(dash);
+        self.dirty.update(self.style, |style| style.dash = dash);
+        self
+    }
+
+    pub fn fill_mode(mut self, fill_mode: FillMode) -> Self {
+        self.dirty.update(self.style, |style| style.fill_mode = fill_mode.flags());
+        self
+    }
+
+    // Fades the shape from 1.0, opaque, to 0.0, invisible; the shapes under
+    // it show through.
+    pub fn opacity(mut self, opacity: f32) -> Self {
+        self.dirty.update(self.style, |style| style.opacity = opacity.clamp(0.0, 1.0));
+        self
+    }
+
+    // Transforms the shape from its coordinates to the pixels; see
+    // `Affine2::around` for transforming around the shape's center.
+    pub fn transform(mut self, transform: math::Affine2) -> Self {
+        self.dirty.set(self.transform, transform);
+        self
+    }
+
+    // Shapes with a higher z-index are drawn over the lower ones whatever
+    // their kind is; shapes with the same z-index are drawn in the order they
+    // were added. The default is 0.
+    pub fn z_index(mut self, z_index: i32) -> Self {
+        self.dirty.set(self.z_index, z_index);
+        self
+    }
+
+    // Handle to change or remove the shape later.
+    pub fn handle(self) -> ShapeHandle {
+        self.handle
+    }
 }
 
 impl Renderer1 {
+    pub fn anti_aliasing(&mut self, anti_aliasing: AntiAliasing) {
+        self.anti_aliasing = anti_aliasing;
+    }
+
+    // Fades the shapes with the z-index as one layer: they are drawn over each
+    // other first and the result is blended over the shapes below, so the
+    // shapes of the layer don't show through each other. Every layer costs an
+    // extra render pass.
+    pub fn layer_opacity(&mut self, z_index: i32, opacity: f32) {
+        self.update_layer(z_index, |layer| layer.opacity = opacity.clamp(0.0, 1.0));
+    }
+
+    // Composes the shapes with the z-index as one layer, see `layer_opacity`,
+    // over the shapes below with the blend mode.
+    pub fn layer_blend_mode(&mut self, z_index: i32, blend_mode: BlendMode) {
+        self.update_layer(z_index, |layer| layer.blend_mode = blend_mode);
+    }
+
+    // Z-indices with the default opacity and blend mode are drawn straight to
+    // the frame.
+    fn update_layer(&mut self, z_index: i32, update: impl FnOnce(&mut Layer)) {
+        let layer = self.layers.entry(z_index).or_default();
+        update(layer);
+        if *layer == Layer::default() {
+            self.layers.remove(&z_index);
+        }
+    }
+
+    fn next_sequence(&mut self) -> u64 {
+        self.sequence += 1;
+        self.sequence
+    }
+
+    // Clip of the shapes added now.
+    fn clip(&self) -> u32 {
+        self.clip_stack.last().copied().unwrap_or(0)
+    }
+
+    fn push_clip(&mut self, shape: ClipShape) {
+        self.clips.push(Clip {
+            parent: self.clip(),
+            group: self.group(),
+            shape,
+        });
+        self.clip_stack.push(self.clips.len() as u32);
+    }
+
+    // Shapes added until the matching `pop_clip` are drawn only inside the
+    // rectangle, given like in `rectangle`; it is moved by the group and the
+    // camera like the shapes. Clips are nested, the shapes are also clipped by
+    // the clips pushed before.
+    pub fn push_clip_rectangle(&mut self, x: f32, y: f32, w: f32, h: f32) {
+        self.push_clip(ClipShape::Rectangle {
+            min: math::Vector2 { x: x - w, y: y - h },
+            max: math::Vector2 { x: x + w, y: y + h },
+        });
+    }
+
+    // Same as `push_clip_rectangle` for the inside of the path by the fill
+    // rule; the path is in the coordinates of the shapes.
+    pub fn push_clip_path(&mut self, path: &Path, fill_rule: FillRule) {
+        self.clip_paths.push(PathShape::clip(path, fill_rule));
+        self.push_clip(ClipShape::Path(self.clip_paths.len() - 1));
+    }
+
+    // Ends the last clip pushed in the current group; does nothing when there
+    // is none, the clips pushed before the group end only after it.
+    pub fn pop_clip(&mut self) {
+        let group_depth = self.group_stack.last().map_or(0, |&(_, clip_depth)| clip_depth);
+        if self.clip_stack.len() > group_depth {
+            self.clip_stack.pop();
+        }
+    }
+
+    // Group of the shapes added now.
+    fn group(&self) -> u32 {
+        self.group_stack.last().map_or(0, |&(group, _)| group)
+    }
+
+    // Shapes added until the matching `pop_group` are moved and faded by the
+    // group set up by the returned builder; groups are nested. The group is
+    // applied when the shapes are uploaded, so changing it through
+    // `edit_group` moves all its shapes. Clips pushed in the group end with
+    // it and are moved with it; clip rectangles are still not moved by the
+    // camera.
+    pub fn push_group(&mut self) -> GroupBuilder<'_> {
+        self.groups.push(Group::new(self.group()));
+        let group = self.groups.len() as u32;
+        self.group_stack.push((group, self.clip_stack.len()));
+        self.edit_group(GroupHandle(group)).unwrap()
+    }
+
+    // Ends the last pushed group with the clips pushed in it; does nothing
+    // when there is none.
+    pub fn pop_group(&mut self) {
+        if let Some((_, clip_depth)) = self.group_stack.pop() {
+            self.clip_stack.truncate(clip_depth);
+        }
+    }
+
+    // Builder changing the group of the handle.
+    pub fn edit_group(&mut self, handle: GroupHandle) -> Option<GroupBuilder<'_>> {
+        let group = self.groups.get_mut((handle.0 as usize).checked_sub(1)?)?;
+        self.groups_changed = true;
+        Some(GroupBuilder { group, handle })
+    }
+
     pub fn circles(&mut self, circles: &Vec<(f32, f32, f32, f32)>) {
         for &(x, y, radius, brush_size) in circles {
             self.circle(x, y, radius, brush_size);
         }
     }
-    pub fn circle(&mut self, x: f32, y: f32, radius: f32, brush_size: f32) {
-        self.circles.push(Circle {
+    // Yellow outline of a circle; the style can be changed by the returned
+    // builder.
+    pub fn circle(&mut self, x: f32, y: f32, radius: f32, brush_size: f32) -> ShapeBuilder<'_> {
+        let circle = Circle {
             pos: math::Vector2 { x, y },
             radius,
             brush_size,
-        });
+            style: Style::default(),
+            transform: math::Affine2::IDENTITY,
+        };
+        let sequence = self.next_sequence();
+        let (clip, group) = (self.clip(), self.group());
+        let handle = self.circles.push(ShapeKind::Circle, sequence, clip, group, circle);
+        self.edit_shape(handle).unwrap()
+    }
+    // Yellow outline of an ellipse with the radii along its axes, rotated
+    // counter clockwise by `rotation` radians; the style can be changed by the
+    // returned builder.
+    pub fn ellipse(&mut self, x: f32, y: f32, rx: f32, ry: f32, rotation: f32, brush_size: f32) -> ShapeBuilder<'_> {
+        self.add_ellipse(Ellipse::new(math::Vector2 { x, y }, math::Vector2 { x: rx, y: ry }, rotation, brush_size))
+    }
+    // Yellow part of a circle outline between the angles in radians, counter
+    // clockwise from the x axis. Arcs have only the stroke.
+    pub fn arc(&mut self, x: f32, y: f32, radius: f32, start_angle: f32, end_angle: f32, brush_size: f32) -> ShapeBuilder<'_> {
+        let circle = Ellipse::new(math::Vector2 { x, y }, math::Vector2 { x: radius, y: radius }, 0.0, brush_size);
+        self.add_ellipse(circle.with_angles(ARC_KIND, start_angle, end_angle))
+    }
+    // Yellow outline of a pie slice of a circle between the angles in radians,
+    // counter clockwise from the x axis; the style can be changed by the
+    // returned builder.
+    pub fn pie(&mut self, x: f32, y: f32, radius: f32, start_angle: f32, end_angle: f32, brush_size: f32) -> ShapeBuilder<'_> {
+        let circle = Ellipse::new(math::Vector2 { x, y }, math::Vector2 { x: radius, y: radius }, 0.0, brush_size);
+        self.add_ellipse(circle.with_angles(PIE_KIND, start_angle, end_angle))
+    }
+    fn add_ellipse(&mut self, ellipse: Ellipse) -> ShapeBuilder<'_> {
+        let sequence = self.next_sequence();
+        let (clip, group) = (self.clip(), self.group());
+        let handle = self.ellipses.push(ShapeKind::Ellipse, sequence, clip, group, ellipse);
+        self.edit_shape(handle).unwrap()
     }
     pub fn rectangles(&mut self, rectangles: &Vec<(f32, f32, f32, f32, f32)>) {
         for &(x, y, w, h, brush_size) in rectangles {
             self.rectangle(x, y ,w, h, brush_size);
         }
     }
-    pub fn rectangle(&mut self, x: f32, y: f32, w: f32, h: f32, brush_size: f32) {
-        self.rectangles.push(Rectangle {
+    // Yellow outline of a rectangle; the style can be changed by the returned
+    // builder.
+    pub fn rectangle(&mut self, x: f32, y: f32, w: f32, h: f32, brush_size: f32) -> ShapeBuilder<'_> {
+        self.rounded_rectangle(x, y, w, h, [0.0; 4], brush_size)
+    }
+    // Rectangle with the corners rounded by the radii in the order top left,
+    // top right, bottom right and bottom left. Radii bigger than the rectangle
+    // allows are reduced.
+    pub fn rounded_rectangle(
+        &mut self,
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        corner_radii: [f32; 4],
+        brush_size: f32,
+    ) -> ShapeBuilder<'_> {
+        let rectangle = Rectangle {
             pos: math::Vector2 { x, y },
             w,
             h,
             brush_size,
-        });
+            style: Style::default(),
+            corner_radii,
+            transform: math::Affine2::IDENTITY,
+        };
+        let sequence = self.next_sequence();
+        let (clip, group) = (self.clip(), self.group());
+        let handle = self.rectangles.push(ShapeKind::Rectangle, sequence, clip, group, rectangle);
+        self.edit_shape(handle).unwrap()
+    }
+    // Yellow line with butt caps; the style can be changed by the returned
+    // builder.
+    pub fn line(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, width: f32) -> LineBuilder<'_> {
+        self.polyline(&[(x0, y0), (x1, y1)], width)
+    }
+    // Yellow connected line segments with miter joins and butt caps; the style
+    // can be changed by the returned builder.
+    pub fn polyline(&mut self, points: &[(f32, f32)], width: f32) -> LineBuilder<'_> {
+        let points = points.iter().map(|&(x, y)| math::Vector2 { x, y }).collect();
+        self.add_polyline(Outline::Points(points), width)
+    }
+    // Yellow quadratic Bézier curve; it is drawn as line segments which are
+    // closer to the curve than the tolerance of the returned builder.
+    pub fn quadratic(
+        &mut self,
+        start: (f32, f32),
+        control: (f32, f32),
+        end: (f32, f32),
+        width: f32,
+    ) -> LineBuilder<'_> {
+        let [start, control, end] = [start, control, end].map(|(x, y)| math::Vector2 { x, y });
+        self.add_polyline(Outline::Quadratic([start, control, end]), width)
+    }
+    // Yellow cubic Bézier curve; it is drawn as line segments which are closer
+    // to the curve than the tolerance of the returned builder.
+    pub fn cubic(
+        &mut self,
+        start: (f32, f32),
+        control1: (f32, f32),
+        control2: (f32, f32),
+        end: (f32, f32),
+        width: f32,
+    ) -> LineBuilder<'_> {
+        let [start, control1, control2, end] =
+            [start, control1, control2, end].map(|(x, y)| math::Vector2 { x, y });
+        self.add_polyline(Outline::Cubic([start, control1, control2, end]), width)
+    }
+    // Yellow outline of a polygon, convex or concave, through the points; the
+    // style can be changed by the returned builder. The stroke is centered on
+    // the edges.
+    pub fn polygon(&mut self, points: &[(f32, f32)], brush_size: f32) -> ShapeBuilder<'_> {
+        let sequence = self.next_sequence();
+        let (clip, group) = (self.clip(), self.group());
+        let handle = self.polygons.push(ShapeKind::Polygon, sequence, clip, group, PolygonShape::new(points, brush_size));
+        self.edit_shape(handle).unwrap()
+    }
+    // Yellow filled path with the nonzero fill rule; the style can be changed
+    // by the returned builder.
+    pub fn path(&mut self, path: &Path) -> PathBuilder<'_> {
+        let sequence = self.next_sequence();
+        let (clip, group) = (self.clip(), self.group());
+        let handle = self.paths.push(ShapeKind::Path, sequence, clip, group, PathShape::new(path));
+        self.edit_path(handle).unwrap()
+    }
+    // Decodes the PNG or JPEG image so it can be drawn by `image`.
+    pub fn load_image(&mut self, bytes: &[u8]) -> anyhow::Result<TextureHandle> {
+        Ok(self.add_image(image::load_from_memory(bytes)?))
+    }
+    // Adds the decoded image so it can be drawn by `image`; it is uploaded to
+    // a texture with the next frame.
+    pub fn add_image(&mut self, image: DynamicImage) -> TextureHandle {
+        self.textures.push(Arc::new(image));
+        TextureHandle(self.textures.len() as u32 - 1)
+    }
+    // Image of the texture stretched over the rectangle, given like in
+    // `rectangle`; the source part, rotation and tint can be changed by the
+    // returned builder. Images of the same texture which follow each other in
+    // the painter's order are drawn at once. Panics when the texture was added
+    // to another renderer.
+    pub fn image(&mut self, texture: TextureHandle, x: f32, y: f32, w: f32, h: f32) -> ImageBuilder<'_> {
+        let image_size = self
+            .textures
+            .get(texture.0 as usize)
+            .unwrap_or_else(|| panic!("{texture:?} wasn't added to this renderer"))
+            .dimensions();
+        let sprite = Sprite::new(texture, image_size, math::Vector2 { x, y }, math::Vector2 { x: w, y: h });
+        let sequence = self.next_sequence();
+        let (clip, group) = (self.clip(), self.group());
+        let handle = self.images.push(ShapeKind::Image, sequence, clip, group, sprite);
+        self.edit_image(handle).unwrap()
+    }
+    // Adds the pages of the atlas as images; returns the images packed in it
+    // in the order of its regions. Images of one page which follow each other
+    // in the painter's order are drawn at once, see `image`.
+    pub fn add_atlas(&mut self, atlas: Atlas) -> Vec<AtlasImage> {
+        let textures: Vec<TextureHandle> =
+            atlas.pages.into_iter().map(|page| self.add_image(DynamicImage::ImageRgba8(page))).collect();
+        atlas
+            .regions
+            .iter()
+            .map(|region| AtlasImage {
+                texture: textures[region.page],
+                source: [region.x, region.y, region.width, region.height].map(|value| value as f32),
+            })
+            .collect()
+    }
+    // Image packed in an atlas drawn like by `image`; `source_rect` of the
+    // builder is in the pixels of the page.
+    pub fn atlas_image(&mut self, image: AtlasImage, x: f32, y: f32, w: f32, h: f32) -> ImageBuilder<'_> {
+        let [source_x, source_y, source_width, source_height] = image.source;
+        self.image(image.texture, x, y, w, h)
+            .source_rect(source_x, source_y, source_width, source_height)
+    }
+    // Parses the TrueType or OpenType font so texts can be drawn with it by
+    // `text`.
+    pub fn load_font(&mut self, bytes: &[u8]) -> anyhow::Result<FontHandle> {
+        self.fonts.push(FontArc::try_from_vec(bytes.to_vec())?);
+        Ok(FontHandle(self.fonts.len() as u32 - 1))
+    }
+    // Yellow text of the font, `size` pixels high, starting at the baseline
+    // point; lines are split at '\n'. The color and the alignment can be
+    // changed by the returned builder. The glyphs are rasterized to an atlas
+    // as they are drawn for the first time. Panics when the font was loaded to
+    // another renderer.
+    pub fn text(&mut self, font: FontHandle, text: &str, size: f32, x: f32, y: f32) -> TextBuilder<'_> {
+        assert!((font.0 as usize) < self.fonts.len(), "{font:?} wasn't loaded to this renderer");
+        let sequence = self.next_sequence();
+        let (clip, group) = (self.clip(), self.group());
+        let shape = TextShape::new(font, text, size, math::Vector2 { x, y });
+        let handle = self.texts.push(ShapeKind::Text, sequence, clip, group, shape);
+        self.edit_text(handle).unwrap()
     }
+    fn add_polyline(&mut self, outline: Outline, width: f32) -> LineBuilder<'_> {
+        let sequence = self.next_sequence();
+        let (clip, group) = (self.clip(), self.group());
+        let handle = self.polylines.push(ShapeKind::Line, sequence, clip, group, Polyline::new(outline, width));
+        self.edit_line(handle).unwrap()
+    }
+    // Builder changing the circle, rectangle, ellipse or polygon of the
+    // handle; `None` for other shapes and removed shapes.
+    pub fn edit_shape(&mut self, handle: ShapeHandle) -> Option<ShapeBuilder<'_>> {
+        let (style, transform, z_index, dirty) = match handle.kind {
+            ShapeKind::Circle => self
+                .circles
+                .get_mut(handle)
+                .map(|(shape, z_index, dirty)| (&mut shape.style, &mut shape.transform, z_index, dirty)),
+            ShapeKind::Rectangle => self
+                .rectangles
+                .get_mut(handle)
+                .map(|(shape, z_index, dirty)| (&mut shape.style, &mut shape.transform, z_index, dirty)),
+            ShapeKind::Ellipse => self
+                .ellipses
+                .get_mut(handle)
+                .map(|(shape, z_index, dirty)| (&mut shape.style, &mut shape.transform, z_index, dirty)),
+            ShapeKind::Polygon => self
+                .polygons
+                .get_mut(handle)
+                .map(|(shape, z_index, dirty)| (&mut shape.style, &mut shape.transform, z_index, dirty)),
+            ShapeKind::Line | ShapeKind::Path | ShapeKind::Image | ShapeKind::Text => None,
+        }?;
+        Some(ShapeBuilder {
+            style,
+            transform,
+            z_index,
+            gradients: &mut self.gradients,
+            dashes: &mut self.dashes,
+            dirty,
+            handle,
+        })
+    }
+    // Builder changing the line or curve of the handle.
+    pub fn edit_line(&mut self, handle: ShapeHandle) -> Option<LineBuilder<'_>> {
+        if handle.kind != ShapeKind::Line {
+            return None;
+        }
+        let (polyline, z_index, dirty) = self.polylines.get_mut(handle)?;
+        Some(LineBuilder {
+            polyline,
+            z_index,
+            gradients: &mut self.gradients,
+            dirty,
+            handle,
+        })
+    }
+    // Builder changing the path of the handle.
+    pub fn edit_path(&mut self, handle: ShapeHandle) -> Option<PathBuilder<'_>> {
+        if handle.kind != ShapeKind::Path {
+            return None;
+        }
+        let (shape, z_index, dirty) = self.paths.get_mut(handle)?;
+        Some(PathBuilder {
+            shape,
+            z_index,
+            gradients: &mut self.gradients,
+            dirty,
+            handle,
+        })
+    }
+    // Builder changing the image of the handle.
+    pub fn edit_image(&mut self, handle: ShapeHandle) -> Option<ImageBuilder<'_>> {
+        if handle.kind != ShapeKind::Image {
+            return None;
+        }
+        let (sprite, z_index, dirty) = self.images.get_mut(handle)?;
+        Some(ImageBuilder {
+            sprite,
+            z_index,
+            dirty,
+            handle,
+        })
+    }
+    // Builder changing the text of the handle.
+    pub fn edit_text(&mut self, handle: ShapeHandle) -> Option<TextBuilder<'_>> {
+        if handle.kind != ShapeKind::Text {
+            return None;
+        }
+        let (shape, z_index, dirty) = self.texts.get_mut(handle)?;
+        Some(TextBuilder {
+            shape,
+            z_index,
+            dirty,
+            handle,
+        })
+    }
+    // Removes the shape; returns false when it was already removed.
+    pub fn remove(&mut self, handle: ShapeHandle) -> bool {
+        match handle.kind {
+            ShapeKind::Circle => self.circles.remove(handle),
+            ShapeKind::Rectangle => self.rectangles.remove(handle),
+            ShapeKind::Ellipse => self.ellipses.remove(handle),
+            ShapeKind::Polygon => self.polygons.remove(handle),
+            ShapeKind::Line => self.polylines.remove(handle),
+            ShapeKind::Path => self.paths.remove(handle),
+            ShapeKind::Image => self.images.remove(handle),
+            ShapeKind::Text => self.texts.remove(handle),
+        }
+    }
+
+    // Frees the rows of the gradients and the dash patterns no shape refers
+    // to anymore, see `Rows`.
+    fn free_unused_tables(&mut self) {
+        let mut used_gradients = vec![false; self.gradients.len() + 1];
+        let mut used_dashes = vec![false; self.dashes.len() + 1];
+        let mut mark = |style: &Style| {
+            used_gradients[style.stroke_gradient as usize] = true;
+            used_gradients[style.fill_gradient as usize] = true;
+            used_dashes[style.dash as usize] = true;
+        };
+        self.circles.live_items().for_each(|circle| mark(&circle.style));
+        self.rectangles.live_items().for_each(|rectangle| mark(&rectangle.style));
+        self.ellipses.live_items().for_each(|ellipse| mark(&ellipse.style));
+        self.polygons.live_items().for_each(|polygon| mark(&polygon.style));
+        self.polylines.live_items().for_each(|polyline| mark(polyline.style()));
+        for path in self.paths.live_items() {
+            used_gradients[path.fill_gradient() as usize] = true;
+        }
+        self.gradients.free_unused(&used_gradients);
+        self.dashes.free_unused(&used_dashes);
+    }
+}
+
+// Shaders are compiled with the anti aliasing setting as a constant, the
+// gradient, dash and clip functions.
+fn create_shader(
+    target: &dyn RenderTarget,
+    label: &str,
+    source: &str,
+    anti_aliasing: AntiAliasing,
+) -> wgpu::ShaderModule {
+    target
+        .device()
+        .create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(
+                format!(
+                    "const ANTI_ALIASING: bool = {};\n{}\n{}\n{}\n{}",
+                    anti_aliasing.coverage(),
+                    include_str!("shaders/renderer_1_gradient.wgsl"),
+                    include_str!("shaders/renderer_1_dash.wgsl"),
+                    include_str!("shaders/renderer_1_clip.wgsl"),
+                    source
+                )
+                .into(),
+            ),
+        })
+}
+
+// The shaders output premultiplied colors; translucent shapes are blended
+// over the ones drawn before them in the painter's order.
+fn create_pipeline(
+    target: &dyn RenderTarget,
+    label: &str,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    buffers: &[wgpu::VertexBufferLayout],
+    anti_aliasing: AntiAliasing,
+    stencil: wgpu::StencilState,
+) -> wgpu::RenderPipeline {
+    create_blended_pipeline(target, label, layout, shader, buffers, anti_aliasing, stencil, BlendMode::Normal)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_blended_pipeline(
+    target: &dyn RenderTarget,
+    label: &str,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    buffers: &[wgpu::VertexBufferLayout],
+    anti_aliasing: AntiAliasing,
+    stencil: wgpu::StencilState,
+    blend_mode: BlendMode,
+) -> wgpu::RenderPipeline {
+    let blend = blend_mode.blend_state();
+    target
+        .device()
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                buffers,
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: target.format(),
+                    blend: Some(blend),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                // Setting this to anything other than Fill requires Features::POLYGON_MODE_LINE
+                // or Features::POLYGON_MODE_POINT
+                polygon_mode: wgpu::PolygonMode::Fill,
+                // Requires Features::DEPTH_CLIP_CONTROL
+                unclipped_depth: false,
+                // Requires Features::CONSERVATIVE_RASTERIZATION
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: STENCIL_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil,
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: anti_aliasing.sample_count(),
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            // If the pipeline will be used with a multiview render pass, this
+            // indicates how many array layers the attachments will have.
+            multiview: None,
+        })
 }
 
 impl RenderBase for Renderer1 {
-    fn prepare(&self, windowed_device: &mut WindowedDevice, projection_bind_group_layout: &BindGroupLayout) -> Box<dyn PreparedRenderBase> {
-        let circle_shader = windowed_device
-            .device
-            .create_shader_module(include_wgsl!("shaders/renderer_1_circle.wgsl"));
+    fn prepare(&self, target: &mut dyn RenderTarget, projection_bind_group_layout: &BindGroupLayout) -> Box<dyn PreparedRenderBase> {
+        Box::new(self.prepare_retained(target, projection_bind_group_layout))
+    }
+}
+
+impl Renderer1 {
+    // Same as `prepare`, but the shapes of the returned renderer can still be
+    // changed.
+    pub fn prepare_retained(&self, target: &dyn RenderTarget, projection_bind_group_layout: &BindGroupLayout) -> Renderer1Prepared {
+        let circle_shader = create_shader(
+            target,
+            "Circle Shader",
+            include_str!("shaders/renderer_1_circle.wgsl"),
+            self.anti_aliasing,
+        );
+
+        let rectangle_shader = create_shader(
+            target,
+            "Rectangle Shader",
+            include_str!("shaders/renderer_1_rectangle.wgsl"),
+            self.anti_aliasing,
+        );
+
+        let ellipse_shader = create_shader(
+            target,
+            "Ellipse Shader",
+            include_str!("shaders/renderer_1_ellipse.wgsl"),
+            self.anti_aliasing,
+        );
+
+        let polygon_shader = create_shader(
+            target,
+            "Polygon Shader",
+            include_str!("shaders/renderer_1_polygon.wgsl"),
+            self.anti_aliasing,
+        );
+
+        let line_shader = create_shader(
+            target,
+            "Line Shader",
+            include_str!("shaders/renderer_1_line.wgsl"),
+            self.anti_aliasing,
+        );
 
-        let rectangle_shader = windowed_device
-            .device
-            .create_shader_module(include_wgsl!("shaders/renderer_1_rectangle.wgsl"));
+        let table_bind_group_layout = PreparedTables::bind_group_layout(target);
+        let clip_bind_group_layout = PreparedClips::bind_group_layout(target);
 
-        let size = windowed_device.window.inner_size();
-            math::ortho(0.0, size.width as f32, 0.0, size.height as f32, 0.0, 1.0);
         let render_pipeline_layout =
-            windowed_device.device
+            target.device()
                 .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                     label: Some("Render Pipeline Layout"),
-                    bind_group_layouts: &[projection_bind_group_layout],
+                    bind_group_layouts: &[projection_bind_group_layout, &table_bind_group_layout, &clip_bind_group_layout],
                     push_constant_ranges: &[],
                 });
 
-        let circle_pipeline = windowed_device
-            .device
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("Circle Render Pipeline"),
-                layout: Some(&render_pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &circle_shader,
-                    entry_point: "vs_main",
-                    buffers: &[Vertex::buffer_description(), Circle::buffer_description()],
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &circle_shader,
-                    entry_point: "fs_main",
-                    targets: &[Some(wgpu::ColorTargetState {
-                        format: windowed_device.config.format,
-                        blend: Some(wgpu::BlendState {
-                            color: wgpu::BlendComponent::REPLACE,
-                            alpha: wgpu::BlendComponent::REPLACE,
-                        }),
-                        write_mask: wgpu::ColorWrites::ALL,
-                    })],
-                }),
-                primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::TriangleList,
-                    strip_index_format: None,
-                    front_face: wgpu::FrontFace::Ccw,
-                    cull_mode: Some(wgpu::Face::Back),
-                    // Setting this to anything other than Fill requires Features::POLYGON_MODE_LINE
-                    // or Features::POLYGON_MODE_POINT
-                    polygon_mode: wgpu::PolygonMode::Fill,
-                    // Requires Features::DEPTH_CLIP_CONTROL
-                    unclipped_depth: false,
-                    // Requires Features::CONSERVATIVE_RASTERIZATION
-                    conservative: false,
-                },
-                depth_stencil: None,
-                multisample: wgpu::MultisampleState {
-                    count: 1,
-                    mask: !0,
-                    alpha_to_coverage_enabled: false,
-                },
-                // If the pipeline will be used with a multiview render pass, this
-                // indicates how many array layers the attachments will have.
-                multiview: None,
-            });
+        let circle_pipeline = create_pipeline(
+            target,
+            "Circle Render Pipeline",
+            &render_pipeline_layout,
+            &circle_shader,
+            &[Vertex::buffer_description(), Circle::buffer_description()],
+            self.anti_aliasing,
+            wgpu::StencilState::default(),
+        );
 
         let circle_vertex_buffer =
-            windowed_device.device
+            target.device()
                 .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                     label: Some("Circle Vertex Buffer"),
                     contents: CIRCLE_VERTICES.get_raw(),
                     usage: wgpu::BufferUsages::VERTEX,
                 });
 
-        let circle_index_buffer = windowed_device
-            .device
+        let circle_index_buffer = target
+            .device()
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("Circle Index Buffer"),
                 contents: CIRCLE_INDICES.get_raw(),
                 usage: wgpu::BufferUsages::INDEX,
             });
 
-        let rectangle_pipeline =
-            windowed_device.device
-                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                    label: Some("Rectangle Render Pipeline"),
-                    layout: Some(&render_pipeline_layout),
-                    vertex: wgpu::VertexState {
-                        module: &rectangle_shader,
-                        entry_point: "vs_main",
-                        buffers: &[
-                            Vertex::buffer_description(),
-                            Rectangle::buffer_description(),
-                        ],
-                    },
-                    fragment: Some(wgpu::FragmentState {
-                        module: &rectangle_shader,
-                        entry_point: "fs_main",
-                        targets: &[Some(wgpu::ColorTargetState {
-                            format: windowed_device.config.format,
-                            blend: Some(wgpu::BlendState {
-                                color: wgpu::BlendComponent::REPLACE,
-                                alpha: wgpu::BlendComponent::REPLACE,
-                            }),
-                            write_mask: wgpu::ColorWrites::ALL,
-                        })],
-                    }),
-                    primitive: wgpu::PrimitiveState {
-                        topology: wgpu::PrimitiveTopology::TriangleList,
-                        strip_index_format: None,
-                        front_face: wgpu::FrontFace::Ccw,
-                        cull_mode: Some(wgpu::Face::Back),
-                        // Setting this to anything other than Fill requires Features::POLYGON_MODE_LINE
-                        // or Features::POLYGON_MODE_POINT
-                        polygon_mode: wgpu::PolygonMode::Fill,
-                        // Requires Features::DEPTH_CLIP_CONTROL
-                        unclipped_depth: false,
-                        // Requires Features::CONSERVATIVE_RASTERIZATION
-                        conservative: false,
-                    },
-                    depth_stencil: None,
-                    multisample: wgpu::MultisampleState {
-                        count: 1,
-                        mask: !0,
-                        alpha_to_coverage_enabled: false,
-                    },
-                    // If the pipeline will be used with a multiview render pass, this
-                    // indicates how many array layers the attachments will have.
-                    multiview: None,
-                });
+        let rectangle_pipeline = create_pipeline(
+            target,
+            "Rectangle Render Pipeline",
+            &render_pipeline_layout,
+            &rectangle_shader,
+            &[Vertex::buffer_description(), Rectangle::buffer_description()],
+            self.anti_aliasing,
+            wgpu::StencilState::default(),
+        );
 
         let rectangle_vertex_buffer =
-            windowed_device.device
+            target.device()
                 .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                     label: Some("rectangle Vertex Buffer"),
                     contents: RECTANGLE_VERTICES.get_raw(),
@@ -348,28 +1358,187 @@ impl RenderBase for Renderer1 {
                 });
 
         let rectangle_index_buffer =
-            windowed_device.device
+            target.device()
                 .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                     label: Some("rectangle Index Buffer"),
                     contents: RECTANGLE_INDICES.get_raw(),
                     usage: wgpu::BufferUsages::INDEX,
                 });
 
-        let circle_instances_buffer = windowed_device.device.create_buffer(&wgpu::BufferDescriptor {
+        let ellipse_pipeline = create_pipeline(
+            target,
+            "Ellipse Render Pipeline",
+            &render_pipeline_layout,
+            &ellipse_shader,
+            &[Vertex::buffer_description(), Ellipse::buffer_description()],
+            self.anti_aliasing,
+            wgpu::StencilState::default(),
+        );
+
+        let ellipse_vertex_buffer =
+            target.device()
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Ellipse Vertex Buffer"),
+                    contents: ELLIPSE_VERTICES.get_raw(),
+                    usage: wgpu::BufferUsages::VERTEX,
+                });
+
+        let ellipse_index_buffer =
+            target.device()
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Ellipse Index Buffer"),
+                    contents: ELLIPSE_INDICES.get_raw(),
+                    usage: wgpu::BufferUsages::INDEX,
+                });
+
+        let polygon_pipeline = create_pipeline(
+            target,
+            "Polygon Render Pipeline",
+            &render_pipeline_layout,
+            &polygon_shader,
+            &[PolygonVertex::buffer_description()],
+            self.anti_aliasing,
+            wgpu::StencilState::default(),
+        );
+
+        let line_pipeline = create_pipeline(
+            target,
+            "Line Render Pipeline",
+            &render_pipeline_layout,
+            &line_shader,
+            &[Vertex::buffer_description(), LineSegment::buffer_description()],
+            self.anti_aliasing,
+            wgpu::StencilState::default(),
+        );
+
+        let line_vertex_buffer =
+            target.device()
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Line Vertex Buffer"),
+                    contents: LINE_VERTICES.get_raw(),
+                    usage: wgpu::BufferUsages::VERTEX,
+                });
+
+        let line_index_buffer =
+            target.device()
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Line Index Buffer"),
+                    contents: LINE_INDICES.get_raw(),
+                    usage: wgpu::BufferUsages::INDEX,
+                });
+
+        let circle_instances_buffer = target.device().create_buffer(&wgpu::BufferDescriptor {
             label: Some("Circle Index Buffer"),
             size: 100000 * wgpu::COPY_BUFFER_ALIGNMENT,
             usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
 
-        let rectangle_instances_buffer = windowed_device.device.create_buffer(&wgpu::BufferDescriptor {
+        let rectangle_instances_buffer = target.device().create_buffer(&wgpu::BufferDescriptor {
             label: Some("Rectangle Index Buffer"),
             size: 100000 * wgpu::COPY_BUFFER_ALIGNMENT,
             usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
 
-        Box::new(Renderer1Prepared {
+        let ellipse_instances_buffer = target.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Ellipse Instances Buffer"),
+            size: 100000 * wgpu::COPY_BUFFER_ALIGNMENT,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let polygon_vertex_buffer = target.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Polygon Vertex Buffer"),
+            size: 100000 * wgpu::COPY_BUFFER_ALIGNMENT,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let line_instances_buffer = target.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Line Instances Buffer"),
+            size: 100000 * wgpu::COPY_BUFFER_ALIGNMENT,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let paths = PreparedPaths::new(target, &render_pipeline_layout, self.anti_aliasing);
+        let sprites = PreparedSprites::new(
+            target,
+            [projection_bind_group_layout, &table_bind_group_layout, &clip_bind_group_layout],
+            self.anti_aliasing,
+        );
+        let tables = PreparedTables::new(target, table_bind_group_layout);
+        let clips = PreparedClips::new(target, clip_bind_group_layout, &render_pipeline_layout, self.anti_aliasing);
+
+        let layer_shader = create_shader(
+            target,
+            "Layer Shader",
+            include_str!("shaders/renderer_1_layer.wgsl"),
+            self.anti_aliasing,
+        );
+
+        let layer_bind_group_layout =
+            target.device()
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("Layer Bind Group Layout"),
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    }],
+                });
+
+        let layer_pipeline_layout =
+            target.device()
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("Layer Pipeline Layout"),
+                    bind_group_layouts: &[&layer_bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+
+        let layer_pipelines = BLEND_MODES
+            .iter()
+            .map(|&blend_mode| {
+                create_blended_pipeline(
+                    target,
+                    "Layer Render Pipeline",
+                    &layer_pipeline_layout,
+                    &layer_shader,
+                    &[Vertex::buffer_description(), LayerInstance::buffer_description()],
+                    self.anti_aliasing,
+                    wgpu::StencilState::default(),
+                    blend_mode,
+                )
+            })
+            .collect();
+
+        let layer_instances_buffer = target.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Layer Instances Buffer"),
+            size: 256 * wgpu::COPY_BUFFER_ALIGNMENT,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        // The buffers are empty, everything is uploaded with the first frame.
+        let mut scene = self.clone();
+        scene.circles.mark_all_dirty();
+        scene.rectangles.mark_all_dirty();
+        scene.ellipses.mark_all_dirty();
+        scene.polygons.mark_all_dirty();
+        scene.polylines.mark_all_dirty();
+        scene.paths.mark_all_dirty();
+        scene.images.mark_all_dirty();
+        scene.texts.mark_all_dirty();
+        scene.groups_changed = true;
+
+        Renderer1Prepared {
+            scene,
             circle_pipeline,
             circle_vertex_buffer,
             circle_index_buffer,
@@ -378,118 +1547,418 @@ impl RenderBase for Renderer1 {
             rectangle_pipeline,
             circle_instances_buffer,
             rectangle_instances_buffer,
-            circles: self.circles.clone(),
-            rectangles: self.rectangles.clone(),
-        })
+            ellipse_vertex_buffer,
+            ellipse_index_buffer,
+            ellipse_pipeline,
+            ellipse_instances_buffer,
+            polygon_vertices: Vec::new(),
+            polygon_ranges: Vec::new(),
+            polygon_pipeline,
+            polygon_vertex_buffer,
+            line_segments: Vec::new(),
+            polyline_ranges: Vec::new(),
+            batches: Vec::new(),
+            group_states: Vec::new(),
+            line_vertex_buffer,
+            line_index_buffer,
+            line_pipeline,
+            line_instances_buffer,
+            paths,
+            sprites,
+            glyphs: GlyphAtlas::default(),
+            glyph_sprites: Vec::new(),
+            text_ranges: Vec::new(),
+            tables,
+            clips,
+            layer_pipelines,
+            layer_bind_group_layout,
+            layer_instances_buffer,
+            anti_aliasing: self.anti_aliasing,
+            msaa_texture: None,
+            stencil_texture: None,
+            layer_texture: None,
+            layer_msaa_texture: None,
+        }
+    }
+}
+
+// Creates the render pass attachment or recreates it when the size of the
+// target changed.
+fn update_attachment(
+    target: &dyn RenderTarget,
+    texture: &mut Option<wgpu::Texture>,
+    label: &str,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    usage: wgpu::TextureUsages,
+) {
+    let size = target.size();
+    let outdated = texture
+        .as_ref()
+        .is_none_or(|texture| texture.width() != size.width || texture.height() != size.height);
+    if outdated {
+        *texture = Some(target.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        }));
+    }
+}
+
+// Render pass drawing to the view, or to the multisampled view which is
+// resolved to it.
+fn begin_render_pass<'a>(
+    encoder: &'a mut wgpu::CommandEncoder,
+    label: &str,
+    view: &'a wgpu::TextureView,
+    msaa_view: Option<&'a wgpu::TextureView>,
+    stencil_view: &'a wgpu::TextureView,
+    load: wgpu::LoadOp<wgpu::Color>,
+) -> wgpu::RenderPass<'a> {
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: msaa_view.unwrap_or(view),
+            resolve_target: msaa_view.map(|_| view),
+            ops: wgpu::Operations {
+                load,
+                store: StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+            view: stencil_view,
+            depth_ops: None,
+            stencil_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(0),
+                store: StoreOp::Discard,
+            }),
+        }),
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    })
+}
+
+// Copies the instances to the buffer at the offset; the buffer is recreated
+// when they don't fit in it, the offset has to be 0 then.
+fn upload_instances(target: &dyn RenderTarget, buffer: &mut wgpu::Buffer, offset: usize, instances: &[u8], name: &str) {
+    let monotonic_time = Instant::now();
+    let start = monotonic_time.elapsed();
+    if offset + instances.len() > buffer.size() as usize {
+        debug_assert_eq!(offset, 0);
+        *buffer = target
+            .device()
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{name} Instances Buffer")),
+                contents: instances,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            });
+        let end = monotonic_time.elapsed();
+        statistics::report_value_with_name(&format!("bad_{name}_path"), (end - start).as_secs_f64());
+    } else {
+        target.queue().write_buffer(buffer, offset as wgpu::BufferAddress, instances);
+        let end = monotonic_time.elapsed();
+        statistics::report_value_with_name(&format!("good_{name}_path"), (end - start).as_secs_f64());
+    }
+    statistics::report_value_with_name(&format!("{name}_data_size"), instances.len() as f64);
+}
+
+// Shapes with the transforms and the opacities of their groups applied; the
+// keys are the keys of the shapes. Borrowed when there are no groups.
+fn flatten<'a, T: Grouped>(items: &'a [T], keys: &[DrawKey], groups: &[GroupState]) -> Cow<'a, [T]> {
+    if groups.len() <= 1 {
+        return Cow::Borrowed(items);
+    }
+    Cow::Owned(
+        items
+            .iter()
+            .zip(keys)
+            .map(|(item, key)| item.in_group(&groups[key.group as usize]))
+            .collect(),
+    )
+}
+
+// Uploads the shapes changed since the last upload flattened by their groups;
+// all of them when the buffer has to grow.
+fn upload_changed<T: Gpu + Grouped>(
+    target: &dyn RenderTarget,
+    buffer: &mut wgpu::Buffer,
+    shapes: &mut Shapes<T>,
+    groups: &[GroupState],
+    name: &str,
+) {
+    let Some(mut changed) = shapes.take_dirty() else {
+        return;
+    };
+    if shapes.items().get_raw().len() > buffer.size() as usize {
+        changed = 0..shapes.items().len();
+    }
+    let items = flatten(&shapes.items()[changed.clone()], &shapes.keys()[changed.clone()], groups);
+    upload_instances(target, buffer, changed.start * mem::size_of::<T>(), items.get_raw(), name);
+}
+
+impl Renderer1Prepared {
+    // Shapes drawn by the renderer. The anti aliasing was fixed when the
+    // renderer was prepared, changing it here has no effect.
+    pub fn scene(&mut self) -> &mut Renderer1 {
+        &mut self.scene
+    }
+
+    // The view of the camera places the scissor rectangles of the clips.
+    fn upload(&mut self, target: &dyn RenderTarget, view: math::Affine2) {
+        let scene = &mut self.scene;
+        scene.circles.sort();
+        scene.rectangles.sort();
+        scene.ellipses.sort();
+        scene.polygons.sort();
+        scene.polylines.sort();
+        scene.paths.sort();
+        scene.images.sort();
+        scene.texts.sort();
+        let reordered = scene.circles.is_changed()
+            || scene.rectangles.is_changed()
+            || scene.ellipses.is_changed()
+            || scene.polygons.is_changed()
+            || scene.polylines.is_changed()
+            || scene.paths.is_changed()
+            || scene.images.is_changed()
+            || scene.texts.is_changed();
+        // Gradients and dash patterns are only dropped by changing or removing
+        // shapes.
+        if reordered {
+            scene.free_unused_tables();
+        }
+
+        // The groups are flattened into the shapes as they are uploaded, so all
+        // of them are uploaded again when a group changes.
+        let regrouped = mem::take(&mut scene.groups_changed);
+        if regrouped {
+            self.group_states = group_states(&scene.groups);
+            scene.circles.mark_all_dirty();
+            scene.rectangles.mark_all_dirty();
+            scene.ellipses.mark_all_dirty();
+            scene.polygons.mark_all_dirty();
+            scene.polylines.mark_all_dirty();
+            scene.paths.mark_all_dirty();
+            scene.images.mark_all_dirty();
+            scene.texts.mark_all_dirty();
+        }
+
+        let groups = &self.group_states;
+        upload_changed(target, &mut self.circle_instances_buffer, &mut self.scene.circles, groups, "circle");
+        upload_changed(target, &mut self.rectangle_instances_buffer, &mut self.scene.rectangles, groups, "rectangle");
+        upload_changed(target, &mut self.ellipse_instances_buffer, &mut self.scene.ellipses, groups, "ellipse");
+
+        // Polygons and lines are split to a varying number of vertices and
+        // segments, so all of them are split and uploaded again after a change.
+        let polygons_changed = self.scene.polygons.take_changed();
+        if self.scene.polylines.take_changed() || polygons_changed {
+            self.polygon_vertices.clear();
+            self.polygon_ranges.clear();
+            self.line_segments.clear();
+            self.polyline_ranges.clear();
+            let polygons = &self.scene.polygons;
+            for polygon in flatten(polygons.items(), polygons.keys(), groups).iter() {
+                let fill_start = self.polygon_vertices.len() as u32;
+                polygon.fill_vertices(&mut self.polygon_vertices);
+                let stroke_start = self.line_segments.len() as u32;
+                if let Some(stroke) = polygon.stroke() {
+                    stroke.segments(&mut self.line_segments);
+                }
+                self.polygon_ranges.push((
+                    fill_start..self.polygon_vertices.len() as u32,
+                    stroke_start..self.line_segments.len() as u32,
+                ));
+            }
+            let polylines = &self.scene.polylines;
+            for polyline in flatten(polylines.items(), polylines.keys(), groups).iter() {
+                let start = self.line_segments.len() as u32;
+                polyline.segments(&mut self.line_segments);
+                self.polyline_ranges.push(start..self.line_segments.len() as u32);
+            }
+            upload_instances(target, &mut self.polygon_vertex_buffer, 0, self.polygon_vertices.get_raw(), "polygon");
+            upload_instances(target, &mut self.line_instances_buffer, 0, self.line_segments.get_raw(), "line");
+        }
+        if self.scene.paths.take_changed() {
+            let paths = &self.scene.paths;
+            self.paths.tessellate(&flatten(paths.items(), paths.keys(), groups));
+            self.paths.upload(target);
+        }
+        self.sprites.upload(target, &self.scene.textures, &mut self.scene.images, groups);
+        // Texts are laid out to glyphs like the polygons are split.
+        if self.scene.texts.take_changed() {
+            self.glyph_sprites.clear();
+            self.text_ranges.clear();
+            let texts = &self.scene.texts;
+            self.glyphs.sprites(
+                &flatten(texts.items(), texts.keys(), groups),
+                &self.scene.fonts,
+                &mut self.glyph_sprites,
+                &mut self.text_ranges,
+            );
+            self.sprites.upload_glyphs(target, self.glyphs.take_pages(), &self.glyph_sprites);
+        }
+        self.tables.upload(target, &mut self.scene.gradients, &mut self.scene.dashes);
+        self.clips.upload(target, &self.scene.clips, &self.scene.clip_paths, groups, view, regrouped);
+
+        if reordered {
+            let scene = &self.scene;
+            self.batches = scene::batches(&[
+                (ShapeKind::Circle, scene.circles.keys()),
+                (ShapeKind::Rectangle, scene.rectangles.keys()),
+                (ShapeKind::Ellipse, scene.ellipses.keys()),
+                (ShapeKind::Polygon, scene.polygons.keys()),
+                (ShapeKind::Line, scene.polylines.keys()),
+                (ShapeKind::Path, scene.paths.keys()),
+                (ShapeKind::Image, scene.images.keys()),
+                (ShapeKind::Text, scene.texts.keys()),
+            ]);
+        }
     }
 }
 
 impl PreparedRenderBase for Renderer1Prepared {
-    fn render(&mut self, windowed_device: &mut WindowedDevice, perspective_bind_group: &BindGroup) {
-        if self.circles.get_raw().len()
-            > self.circle_instances_buffer.size() as usize
-        {
-            let monotonic_time = Instant::now();
-            let start = monotonic_time.elapsed();
-            self.circle_instances_buffer =
-                windowed_device
-                    .device
-                    .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                        label: Some("Circle Index Buffer"),
-                        contents: self.circles.get_raw(),
-                        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-                    });
-            let end = monotonic_time.elapsed();
-            statistics::report_value_with_name("bad_circle_path", (end - start).as_secs_f64());
-        } else {
-            let monotonic_time = Instant::now();
-            let start = monotonic_time.elapsed();
-            windowed_device.queue.write_buffer(
-                &self.circle_instances_buffer,
-                0,
-                self.circles.get_raw(),
+    fn render(&mut self, target: &mut dyn RenderTarget, projection: &Projection) {
+        self.upload(target, projection.camera().view());
+        let perspective_bind_group = &projection.bind_group;
+
+        let sample_count = self.anti_aliasing.sample_count();
+        if sample_count > 1 {
+            update_attachment(
+                target,
+                &mut self.msaa_texture,
+                "MSAA Color Attachment",
+                target.format(),
+                sample_count,
+                wgpu::TextureUsages::RENDER_ATTACHMENT,
             );
-            let end = monotonic_time.elapsed();
-            statistics::report_value_with_name("good_circle_path", (end - start).as_secs_f64());
         }
-        statistics::report_value_with_name(
-            "circle_data_size",
-            self.circles.get_raw().len() as f64,
-        );
-        statistics::report_value_with_name(
-            "rectangle_data_size",
-            self.rectangles.get_raw().len() as f64,
+        update_attachment(
+            target,
+            &mut self.stencil_texture,
+            "Stencil Attachment",
+            STENCIL_FORMAT,
+            sample_count,
+            wgpu::TextureUsages::RENDER_ATTACHMENT,
         );
+        let msaa_view = self
+            .msaa_texture
+            .as_ref()
+            .map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()));
+        let stencil_view = self
+            .stencil_texture
+            .as_ref()
+            .unwrap()
+            .create_view(&wgpu::TextureViewDescriptor::default());
 
-        if self.rectangles.get_raw().len()
-            > self.rectangle_instances_buffer.size() as usize
-        {
-            let monotonic_time = Instant::now();
-            let start = monotonic_time.elapsed();
-            self.rectangle_instances_buffer =
-                windowed_device
-                    .device
-                    .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                        label: Some("rectangle Index Buffer"),
-                        contents: self.rectangles.get_raw(),
-                        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-                    });
-            let end = monotonic_time.elapsed();
-            statistics::report_value_with_name(
-                "bad_rectangle_path",
-                (end - start).as_secs_f64(),
-            );
-        } else {
-            let monotonic_time = Instant::now();
-            let start = monotonic_time.elapsed();
-            windowed_device.queue.write_buffer(
-                &self.rectangle_instances_buffer,
-                0,
-                self.rectangles.get_raw(),
-            );
-            let end = monotonic_time.elapsed();
-            statistics::report_value_with_name(
-                "good_rectangle_path",
-                (end - start).as_secs_f64(),
+        let layers = scene::layers(&self.batches, |z_index| self.scene.layers.get(&z_index).copied());
+        let layer_instances: Vec<LayerInstance> = layers
+            .iter()
+            .filter_map(|&(_, layer)| layer)
+            .map(|layer| LayerInstance {
+                opacity: layer.opacity,
+                blend_mode: layer.blend_mode.index(),
+            })
+            .collect();
+        if !layer_instances.is_empty() {
+            update_attachment(
+                target,
+                &mut self.layer_texture,
+                "Layer Attachment",
+                target.format(),
+                1,
+                wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
             );
+            if sample_count > 1 {
+                update_attachment(
+                    target,
+                    &mut self.layer_msaa_texture,
+                    "MSAA Layer Attachment",
+                    target.format(),
+                    sample_count,
+                    wgpu::TextureUsages::RENDER_ATTACHMENT,
+                );
+            }
+            upload_instances(target, &mut self.layer_instances_buffer, 0, layer_instances.get_raw(), "layer");
         }
+        let layer_view = self
+            .layer_texture
+            .as_ref()
+            .map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()));
+        let layer_msaa_view = self
+            .layer_msaa_texture
+            .as_ref()
+            .map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()));
+        let layer_bind_group = layer_view.as_ref().filter(|_| !layer_instances.is_empty()).map(|layer_view| {
+            target.device().create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &self.layer_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(layer_view),
+                }],
+                label: Some("Layer Bind Group"),
+            })
+        });
 
-        let (mut encoder, view, output) = windowed_device.prepare_encoder().unwrap();
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Rectangle Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.0,
-                            g: 0.0,
-                            b: 0.0,
-                            a: 1.0,
-                        }),
-                        store: StoreOp::Store,
-                    },
-                })], depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None, });
-
-            self.render_circles(&mut render_pass, &self.circle_instances_buffer, perspective_bind_group).unwrap();
-
-            self.render_rectangles(&mut render_pass, &self.rectangle_instances_buffer, perspective_bind_group).unwrap();
+        let (mut encoder, view, output) = target.prepare_encoder().unwrap();
+        self.clips.render_masks(
+            &mut encoder,
+            perspective_bind_group,
+            self.tables.bind_group(),
+            msaa_view.as_ref(),
+            &stencil_view,
+        );
+        // The frame is cleared by the first pass and kept by the others.
+        let mut load = wgpu::LoadOp::Clear(wgpu::Color::BLACK);
+        let mut layer_index = 0;
+        for (batches, layer) in &layers {
+            let batches = &self.batches[batches.clone()];
+            if let Some(layer) = layer {
+                {
+                    let mut render_pass = begin_render_pass(
+                        &mut encoder,
+                        "Layer Render Pass",
+                        layer_view.as_ref().unwrap(),
+                        layer_msaa_view.as_ref(),
+                        &stencil_view,
+                        wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    );
+                    self.render_batches(&mut render_pass, perspective_bind_group, batches);
+                }
+                let mut render_pass =
+                    begin_render_pass(&mut encoder, "Frame Render Pass", &view, msaa_view.as_ref(), &stencil_view, load);
+                self.render_layer(&mut render_pass, layer_bind_group.as_ref().unwrap(), layer.blend_mode, layer_index);
+                layer_index += 1;
+            } else {
+                let mut render_pass =
+                    begin_render_pass(&mut encoder, "Frame Render Pass", &view, msaa_view.as_ref(), &stencil_view, load);
+                self.render_batches(&mut render_pass, perspective_bind_group, batches);
+            }
+            load = wgpu::LoadOp::Load;
+        }
+        if layers.is_empty() {
+            begin_render_pass(&mut encoder, "Frame Render Pass", &view, msaa_view.as_ref(), &stencil_view, load);
         }
 
         {
             let monotonic_time = Instant::now();
             let start = monotonic_time.elapsed();
-            windowed_device.queue.on_submitted_work_done(move || {
+            target.queue().on_submitted_work_done(move || {
                 statistics::report_value_with_name(
                     "end_queue_submit_time",
                     monotonic_time.elapsed().as_secs_f64(),
                 )
             });
-            windowed_device
-                .queue
+            target
+                .queue()
                 .submit(iter::once(encoder.finish()));
             let end = monotonic_time.elapsed();
             statistics::report_value_with_name("queue_submit", (end - start).as_secs_f64());
@@ -499,7 +1968,7 @@ impl PreparedRenderBase for Renderer1Prepared {
         {
             let monotonic_time = Instant::now();
             let start = monotonic_time.elapsed();
-            output.present();
+            target.present(output);
             let end = monotonic_time.elapsed();
             statistics::report_value_with_name("output_present", (end - start).as_secs_f64());
         }
@@ -509,11 +1978,82 @@ impl PreparedRenderBase for Renderer1Prepared {
 
 
 impl Renderer1Prepared {
+    // Runs of shapes of the same kind are drawn by one draw call.
+    fn render_batches<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        perspective_bind_group: &'a BindGroup,
+        batches: &[Batch],
+    ) {
+        render_pass.set_bind_group(1, self.tables.bind_group(), &[]);
+        for batch in batches {
+            if !self.clips.set_clip(render_pass, batch.clip) {
+                continue;
+            }
+            let shapes = batch.shapes.start as u32..batch.shapes.end as u32;
+            match batch.kind {
+                ShapeKind::Circle => self
+                    .render_circles(render_pass, &self.circle_instances_buffer, perspective_bind_group, shapes)
+                    .unwrap(),
+                ShapeKind::Rectangle => self
+                    .render_rectangles(render_pass, &self.rectangle_instances_buffer, perspective_bind_group, shapes)
+                    .unwrap(),
+                ShapeKind::Ellipse => self
+                    .render_ellipses(render_pass, &self.ellipse_instances_buffer, perspective_bind_group, shapes)
+                    .unwrap(),
+                // The stroke of every polygon has to be drawn before the
+                // next polygon.
+                ShapeKind::Polygon => {
+                    for (fill, stroke) in &self.polygon_ranges[batch.shapes.clone()] {
+                        self.render_polygons(render_pass, perspective_bind_group, fill.clone());
+                        self.render_lines(render_pass, &self.line_instances_buffer, perspective_bind_group, stroke.clone())
+                            .unwrap();
+                    }
+                }
+                ShapeKind::Line => {
+                    let segments = self.polyline_ranges[batch.shapes.start].start
+                        ..self.polyline_ranges[batch.shapes.end - 1].end;
+                    self.render_lines(render_pass, &self.line_instances_buffer, perspective_bind_group, segments)
+                        .unwrap();
+                }
+                ShapeKind::Path => self.paths.render(render_pass, perspective_bind_group, batch.shapes.clone()),
+                ShapeKind::Image => self.sprites.render(
+                    render_pass,
+                    perspective_bind_group,
+                    self.scene.images.items(),
+                    batch.shapes.clone(),
+                ),
+                ShapeKind::Text => {
+                    let glyphs = self.text_ranges[batch.shapes.start].start as usize
+                        ..self.text_ranges[batch.shapes.end - 1].end as usize;
+                    self.sprites.render_glyphs(render_pass, perspective_bind_group, &self.glyph_sprites, glyphs);
+                }
+            }
+        }
+    }
+
+    // Composes the `layer`-th layer of the frame over it.
+    fn render_layer<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        layer_bind_group: &'a BindGroup,
+        blend_mode: BlendMode,
+        layer: u32,
+    ) {
+        render_pass.set_pipeline(&self.layer_pipelines[blend_mode.index() as usize]);
+        render_pass.set_bind_group(0, layer_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.rectangle_vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.layer_instances_buffer.slice(..));
+        render_pass.set_index_buffer(self.rectangle_index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..(RECTANGLE_INDICES.len() as u32), 0, layer..layer + 1);
+    }
+
     fn render_circles<'a, 'b, 'c, 'd>(
         &'c self,
         render_pass: &'a mut wgpu::RenderPass<'d>,
         circle_instances_buffer: &'b wgpu::Buffer,
         perspective_bind_group: &'d BindGroup,
+        instances: Range<u32>,
     ) -> Result<(), wgpu::SurfaceError>
     where
         'b: 'a,
@@ -532,7 +2072,7 @@ impl Renderer1Prepared {
         render_pass.draw_indexed(
             0..(CIRCLE_INDICES.len() as u32),
             0,
-            0..(self.circles.len() as u32),
+            instances,
         );
         Ok(())
     }
@@ -542,6 +2082,7 @@ impl Renderer1Prepared {
         render_pass: &'a mut wgpu::RenderPass<'d>,
         rectangle_instances_buffer: &'b wgpu::Buffer,
         perspective_bind_group: &'d BindGroup,
+        instances: Range<u32>,
     ) -> Result<(), wgpu::SurfaceError>
     where
         'b: 'a,
@@ -560,9 +2101,78 @@ impl Renderer1Prepared {
         render_pass.draw_indexed(
             0..(RECTANGLE_INDICES.len() as u32),
             0,
-            0..(self.rectangles.len() as u32),
+            instances,
+        );
+        Ok(())
+    }
+
+    fn render_ellipses<'a, 'b, 'c, 'd>(
+        &'c self,
+        render_pass: &'a mut wgpu::RenderPass<'d>,
+        ellipse_instances_buffer: &'b wgpu::Buffer,
+        perspective_bind_group: &'d BindGroup,
+        instances: Range<u32>,
+    ) -> Result<(), wgpu::SurfaceError>
+    where
+        'b: 'a,
+        'c: 'a,
+        'c: 'b,
+        'b: 'd,
+    {
+        render_pass.set_pipeline(&self.ellipse_pipeline);
+        render_pass.set_bind_group(0, perspective_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.ellipse_vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, ellipse_instances_buffer.slice(..));
+        render_pass.set_index_buffer(
+            self.ellipse_index_buffer.slice(..),
+            wgpu::IndexFormat::Uint16,
+        );
+        render_pass.draw_indexed(
+            0..(ELLIPSE_INDICES.len() as u32),
+            0,
+            instances,
         );
         Ok(())
     }
 
+    fn render_polygons<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        perspective_bind_group: &'a BindGroup,
+        vertices: Range<u32>,
+    ) {
+        render_pass.set_pipeline(&self.polygon_pipeline);
+        render_pass.set_bind_group(0, perspective_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.polygon_vertex_buffer.slice(..));
+        render_pass.draw(vertices, 0..1);
+    }
+
+    fn render_lines<'a, 'b, 'c, 'd>(
+        &'c self,
+        render_pass: &'a mut wgpu::RenderPass<'d>,
+        line_instances_buffer: &'b wgpu::Buffer,
+        perspective_bind_group: &'d BindGroup,
+        instances: Range<u32>,
+    ) -> Result<(), wgpu::SurfaceError>
+    where
+        'b: 'a,
+        'c: 'a,
+        'c: 'b,
+        'b: 'd,
+    {
+        render_pass.set_pipeline(&self.line_pipeline);
+        render_pass.set_bind_group(0, perspective_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.line_vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, line_instances_buffer.slice(..));
+        render_pass.set_index_buffer(
+            self.line_index_buffer.slice(..),
+            wgpu::IndexFormat::Uint16,
+        );
+        render_pass.draw_indexed(
+            0..(LINE_INDICES.len() as u32),
+            0,
+            instances,
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,444 @@
+use super::group::{GroupState, Grouped};
+use super::path::{PathShape, PreparedPaths};
+use super::{begin_render_pass, update_attachment};
+use crate::math::{Affine2, Vector2};
+use crate::path::{FillRule, Path};
+use crate::render_common::AntiAliasing;
+use crate::render_target::RenderTarget;
+use wgpu::util::DeviceExt;
+use winit::dpi::PhysicalSize;
+
+// Region the shapes are restricted to. The shapes refer to the clips by the
+// index plus one, 0 means no clip.
+#[derive(Debug, Clone)]
+pub(super) enum ClipShape {
+    // Axis aligned in the coordinates of the shapes before the transform of
+    // the group; applied by the scissor test, or drawn to a mask like the
+    // paths when the group or the camera rotates or skews it.
+    Rectangle { min: Vector2<f32>, max: Vector2<f32> },
+    // Index of the path in the clip paths; the path is drawn to a mask
+    // texture which the shapes are multiplied by.
+    Path(usize),
+}
+
+// Clips are nested, the shapes of a clip are also clipped by its parent.
+#[derive(Debug, Clone)]
+pub(super) struct Clip {
+    pub parent: u32,
+    // Group the clip was pushed in, see `Group`; it moves the clip paths.
+    pub group: u32,
+    pub shape: ClipShape,
+}
+
+impl Clip {
+    // Whether the clip is drawn to a mask instead of only setting the scissor
+    // rectangle; `view` is the transform of the camera.
+    fn is_masked(&self, groups: &[GroupState], view: Affine2) -> bool {
+        match self.shape {
+            ClipShape::Rectangle { .. } => !is_axis_aligned(&(view * groups[self.group as usize].transform)),
+            ClipShape::Path(_) => true,
+        }
+    }
+}
+
+// Whether the transform keeps the rectangles axis aligned; it only scales,
+// flips and moves them.
+fn is_axis_aligned(transform: &Affine2) -> bool {
+    transform.x_axis.y == 0.0 && transform.y_axis.x == 0.0
+}
+
+// Corners of the rectangle transformed, counter clockwise.
+fn rectangle_corners(min: Vector2<f32>, max: Vector2<f32>, transform: Affine2) -> [Vector2<f32>; 4] {
+    [min, Vector2 { x: max.x, y: min.y }, max, Vector2 { x: min.x, y: max.y }]
+        .map(|corner| transform.transform_point(corner))
+}
+
+// Clip reduced to the state of the draw calls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct ClipState {
+    // X, y, width and height in the pixels of the frame from its top left
+    // corner, as the scissor rectangle is set.
+    pub scissor: [u32; 4],
+    // Index of the mask of the innermost masked clip; the masks are indexed
+    // in the order of their clips.
+    pub mask: Option<usize>,
+}
+
+impl ClipState {
+    // Nothing can be drawn, the scissor rectangle is empty.
+    pub fn is_empty(&self) -> bool {
+        self.scissor[2] == 0 || self.scissor[3] == 0
+    }
+}
+
+// States of the clips in their groups seen through the view of the camera in
+// the frame of the size, with the state without a clip first so they are
+// indexed like the shapes refer to the clips. The parents are added before
+// their clips, so they are resolved first.
+pub(super) fn clip_states(
+    clips: &[Clip],
+    groups: &[GroupState],
+    view: Affine2,
+    size: PhysicalSize<u32>,
+) -> Vec<ClipState> {
+    let mut states = vec![ClipState {
+        scissor: [0, 0, size.width, size.height],
+        mask: None,
+    }];
+    let mut masks = 0;
+    for clip in clips {
+        let parent = states[clip.parent as usize];
+        let mask = if clip.is_masked(groups, view) {
+            masks += 1;
+            Some(masks - 1)
+        } else {
+            parent.mask
+        };
+        let state = match clip.shape {
+            ClipShape::Rectangle { min, max } => {
+                // The scissor rectangle bounds the masked rectangles.
+                let corners = rectangle_corners(min, max, view * groups[clip.group as usize].transform);
+                let min = corners.iter().fold(corners[0], |min, corner| Vector2 {
+                    x: min.x.min(corner.x),
+                    y: min.y.min(corner.y),
+                });
+                let max = corners.iter().fold(corners[0], |max, corner| Vector2 {
+                    x: max.x.max(corner.x),
+                    y: max.y.max(corner.y),
+                });
+                // The frame has the y axis going up, the scissor rectangle
+                // down.
+                let height = size.height as f32;
+                let left = min.x.floor().clamp(0.0, size.width as f32) as u32;
+                let right = max.x.ceil().clamp(0.0, size.width as f32) as u32;
+                let top = (height - max.y.ceil()).clamp(0.0, height) as u32;
+                let bottom = (height - min.y.floor()).clamp(0.0, height) as u32;
+                let [x, y, width, height] = parent.scissor;
+                let (left, right) = (left.max(x), right.min(x + width));
+                let (top, bottom) = (top.max(y), bottom.min(y + height));
+                ClipState {
+                    scissor: [left, top, right.saturating_sub(left), bottom.saturating_sub(top)],
+                    mask,
+                }
+            }
+            ClipShape::Path(_) => ClipState { mask, ..parent },
+        };
+        states.push(state);
+    }
+    states
+}
+
+// Mask of a path clip with the view it is drawn to and bound by.
+struct ClipMask {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    bind_group: wgpu::BindGroup,
+}
+
+impl ClipMask {
+    fn new(target: &dyn RenderTarget, bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+        let mut texture = None;
+        update_attachment(
+            target,
+            &mut texture,
+            "Clip Mask Attachment",
+            target.format(),
+            1,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        );
+        let texture = texture.unwrap();
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = create_bind_group(target, bind_group_layout, &view);
+        ClipMask {
+            texture,
+            view,
+            bind_group,
+        }
+    }
+}
+
+// Masked clip drawn within its parent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct MaskedClip {
+    index: usize,
+    parent: u32,
+}
+
+// Clips on the GPU: the masks of the masked clips bound as the bind group 2 of
+// the shape pipelines and the scissor rectangles. The masks are kept until the
+// size of the frame changes, but drawn again for every frame, before the
+// shapes, as the projection may have changed.
+pub(super) struct PreparedClips {
+    bind_group_layout: wgpu::BindGroupLayout,
+    // Lets everything through; bound for the shapes without a masked clip.
+    no_mask_bind_group: wgpu::BindGroup,
+    // Outlines of the masked clips in the coordinates of the shapes, drawn
+    // with their projection; indexed like the masks.
+    paths: PreparedPaths,
+    // The rectangles turn masked and back as the camera rotates.
+    masked: Vec<MaskedClip>,
+    masks: Vec<ClipMask>,
+    states: Vec<ClipState>,
+}
+
+impl PreparedClips {
+    pub(super) fn bind_group_layout(target: &dyn RenderTarget) -> wgpu::BindGroupLayout {
+        target
+            .device()
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Clip Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                }],
+            })
+    }
+
+    pub(super) fn new(
+        target: &dyn RenderTarget,
+        bind_group_layout: wgpu::BindGroupLayout,
+        pipeline_layout: &wgpu::PipelineLayout,
+        anti_aliasing: AntiAliasing,
+    ) -> Self {
+        let no_mask = target.device().create_texture_with_data(
+            target.queue(),
+            &wgpu::TextureDescriptor {
+                label: Some("No Clip Mask"),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            &[255; 4],
+        );
+        let no_mask_bind_group = create_bind_group(
+            target,
+            &bind_group_layout,
+            &no_mask.create_view(&wgpu::TextureViewDescriptor::default()),
+        );
+        PreparedClips {
+            bind_group_layout,
+            no_mask_bind_group,
+            paths: PreparedPaths::new(target, pipeline_layout, anti_aliasing),
+            masked: Vec::new(),
+            masks: Vec::new(),
+            states: Vec::new(),
+        }
+    }
+
+    // Resolves the clips for the view of the camera and the size of the frame
+    // and creates the masks of the new masked clips, or of all of them for a
+    // new size; `regrouped` when the groups changed since the last upload.
+    pub(super) fn upload(
+        &mut self,
+        target: &dyn RenderTarget,
+        clips: &[Clip],
+        paths: &[PathShape],
+        groups: &[GroupState],
+        view: Affine2,
+        regrouped: bool,
+    ) {
+        let masked: Vec<MaskedClip> = clips
+            .iter()
+            .enumerate()
+            .filter(|(_, clip)| clip.is_masked(groups, view))
+            .map(|(index, clip)| MaskedClip {
+                index,
+                parent: clip.parent,
+            })
+            .collect();
+        if masked != self.masked || regrouped {
+            let outlines: Vec<PathShape> = masked
+                .iter()
+                .map(|masked| {
+                    let clip = &clips[masked.index];
+                    let group = &groups[clip.group as usize];
+                    match clip.shape {
+                        ClipShape::Rectangle { min, max } => {
+                            let [a, b, c, d] = rectangle_corners(min, max, group.transform);
+                            let mut path = Path::new();
+                            path.move_to(a.x, a.y).line_to(b.x, b.y).line_to(c.x, c.y).line_to(d.x, d.y).close();
+                            PathShape::clip(&path, FillRule::NonZero)
+                        }
+                        ClipShape::Path(path) => paths[path].in_group(group),
+                    }
+                })
+                .collect();
+            self.paths.tessellate(&outlines);
+            self.paths.upload(target);
+            self.masked = masked;
+        }
+        let size = target.size();
+        self.states = clip_states(clips, groups, view, size);
+        let resized = self
+            .masks
+            .first()
+            .is_some_and(|mask| mask.texture.width() != size.width || mask.texture.height() != size.height);
+        if resized {
+            self.masks.clear();
+        }
+        while self.masks.len() < self.masked.len() {
+            self.masks.push(ClipMask::new(target, &self.bind_group_layout));
+        }
+    }
+
+    // Draws the coverage of every masked clip within its parent to its mask.
+    pub(super) fn render_masks(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        perspective_bind_group: &wgpu::BindGroup,
+        table_bind_group: &wgpu::BindGroup,
+        msaa_view: Option<&wgpu::TextureView>,
+        stencil_view: &wgpu::TextureView,
+    ) {
+        for (index, (clip, mask)) in self.masked.iter().zip(&self.masks).enumerate() {
+            let mut render_pass = begin_render_pass(
+                encoder,
+                "Clip Mask Render Pass",
+                &mask.view,
+                msaa_view,
+                stencil_view,
+                wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+            );
+            render_pass.set_bind_group(1, table_bind_group, &[]);
+            if self.set_clip(&mut render_pass, clip.parent) {
+                self.paths.render(&mut render_pass, perspective_bind_group, index..index + 1);
+            }
+        }
+    }
+
+    // Restricts the next draw calls to the clip; false when nothing of it can
+    // be seen.
+    pub(super) fn set_clip<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, clip: u32) -> bool {
+        let state = self.states[clip as usize];
+        if state.is_empty() {
+            return false;
+        }
+        let [x, y, width, height] = state.scissor;
+        render_pass.set_scissor_rect(x, y, width, height);
+        let bind_group = state.mask.map_or(&self.no_mask_bind_group, |mask| &self.masks[mask].bind_group);
+        render_pass.set_bind_group(2, bind_group, &[]);
+        true
+    }
+}
+
+fn create_bind_group(
+    target: &dyn RenderTarget,
+    layout: &wgpu::BindGroupLayout,
+    view: &wgpu::TextureView,
+) -> wgpu::BindGroup {
+    target.device().create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::TextureView(view),
+        }],
+        label: Some("Clip Bind Group"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: PhysicalSize<u32> = PhysicalSize::new(100, 50);
+
+    fn group(transform: Affine2) -> GroupState {
+        GroupState { transform, opacity: 1.0 }
+    }
+
+    fn rectangle(parent: u32, min: (f32, f32), max: (f32, f32)) -> Clip {
+        Clip {
+            parent,
+            group: 0,
+            shape: ClipShape::Rectangle {
+                min: Vector2 { x: min.0, y: min.1 },
+                max: Vector2 { x: max.0, y: max.1 },
+            },
+        }
+    }
+
+    #[test]
+    fn nested_rectangles_are_intersected() {
+        let clips = [
+            rectangle(0, (10.0, 10.0), (60.5, 40.0)),
+            rectangle(1, (50.0, -20.0), (200.0, 20.0)),
+            rectangle(0, (70.0, 0.0), (80.0, 10.0)),
+            rectangle(3, (0.0, 20.0), (10.0, 30.0)),
+        ];
+        let scissors: Vec<_> = clip_states(&clips, &[group(Affine2::IDENTITY)], Affine2::IDENTITY, SIZE).iter().map(|state| state.scissor).collect();
+        assert_eq!(
+            scissors,
+            vec![[0, 0, 100, 50], [10, 10, 51, 30], [50, 30, 11, 10], [70, 40, 10, 10], [70, 40, 0, 0]]
+        );
+        assert!(clip_states(&clips, &[group(Affine2::IDENTITY)], Affine2::IDENTITY, SIZE)[4].is_empty());
+    }
+
+    #[test]
+    fn paths_are_masked_within_the_parents() {
+        let clips = [
+            Clip {
+                parent: 0,
+                group: 0,
+                shape: ClipShape::Path(0),
+            },
+            rectangle(1, (0.0, 0.0), (10.0, 10.0)),
+            Clip {
+                parent: 2,
+                group: 0,
+                shape: ClipShape::Path(1),
+            },
+        ];
+        let states = clip_states(&clips, &[group(Affine2::IDENTITY)], Affine2::IDENTITY, SIZE);
+        assert_eq!(states[1].mask, Some(0));
+        assert_eq!(states[2], ClipState { scissor: [0, 40, 10, 10], mask: Some(0) });
+        assert_eq!(states[3], ClipState { scissor: [0, 40, 10, 10], mask: Some(1) });
+    }
+
+    #[test]
+    fn rectangles_are_transformed_by_their_groups() {
+        let groups = [
+            group(Affine2::IDENTITY),
+            group(Affine2::translation(20.0, 10.0) * Affine2::scale(2.0, -1.0)),
+            group(Affine2::rotation(std::f32::consts::FRAC_PI_4)),
+        ];
+        let in_group = |group, parent| Clip {
+            group,
+            ..rectangle(parent, (0.0, 0.0), (10.0, 10.0))
+        };
+        let clips = [in_group(1, 0), in_group(2, 0), in_group(0, 2)];
+        let states = clip_states(&clips, &groups, Affine2::IDENTITY, SIZE);
+        // Scaled and flipped to 20..40 and 0..10.
+        assert_eq!(states[1], ClipState { scissor: [20, 40, 20, 10], mask: None });
+        // Rotated, so masked within the bounds.
+        assert_eq!(states[2], ClipState { scissor: [0, 35, 8, 15], mask: Some(0) });
+        assert_eq!(states[3], ClipState { scissor: [0, 40, 8, 10], mask: Some(0) });
+    }
+
+    #[test]
+    fn rectangles_follow_the_camera() {
+        let clips = [rectangle(0, (0.0, 0.0), (10.0, 10.0))];
+        let groups = [group(Affine2::IDENTITY)];
+        // Zoomed twice around the world origin and panned by 5 pixels.
+        let view = Affine2::translation(5.0, 0.0) * Affine2::scale(2.0, 2.0);
+        let states = clip_states(&clips, &groups, view, SIZE);
+        assert_eq!(states[1], ClipState { scissor: [5, 30, 20, 20], mask: None });
+        // A rotated camera masks the rectangle within its bounds.
+        let view = Affine2::rotation(std::f32::consts::FRAC_PI_4);
+        assert!(clips[0].is_masked(&groups, view));
+        assert_eq!(clip_states(&clips, &groups, view, SIZE)[1].mask, Some(0));
+    }
+}
@@ -0,0 +1,68 @@
+use super::table::{table_texels, Rows, TableEntry};
+use super::LineCap;
+use crate::dash::{Dash, MAX_DASH_LENGTHS};
+
+// Every dash pattern is a row of the dash texture: the first texel is the
+// number of the lengths, the length of the whole pattern, the offset and the
+// cap; the rest are the lengths, four in a texel. The rows are wrapped like
+// the gradients. Has to be kept in sync with renderer_1_dash.wgsl.
+pub(super) const DASH_ROW_WIDTH: u32 = 1 + MAX_DASH_LENGTHS as u32 / 4;
+
+// Has to be kept in sync with the caps in renderer_1_dash.wgsl.
+fn cap_index(cap: LineCap) -> f32 {
+    match cap {
+        LineCap::Butt => 0.0,
+        LineCap::Round => 1.0,
+        LineCap::Square => 2.0,
+    }
+}
+
+impl TableEntry for Dash {
+    // Bits of the lengths, the offset and the cap; patterns with the same
+    // lengths share a row.
+    type Key = Vec<u32>;
+
+    fn key(&self) -> Vec<u32> {
+        let mut key = self.lengths();
+        key.extend_from_slice(&[self.offset, cap_index(self.cap)]);
+        key.into_iter().map(f32::to_bits).collect()
+    }
+}
+
+// Dash patterns used by the strokes, see `Rows`; 0 means a solid stroke.
+#[derive(Debug, Default, Clone)]
+pub(super) struct Dashes {
+    rows: Rows<Dash>,
+}
+
+impl Dashes {
+    pub(super) fn add(&mut self, dash: &Dash) -> u32 {
+        if dash.lengths().is_empty() {
+            return 0;
+        }
+        self.rows.add(dash, DASH_ROW_WIDTH, "dash pattern")
+    }
+
+    pub(super) fn free_unused(&mut self, used: &[bool]) {
+        self.rows.free_unused(used);
+    }
+
+    // Includes the freed rows.
+    pub(super) fn len(&self) -> usize {
+        self.rows.rows().len()
+    }
+
+    pub(super) fn take_changed(&mut self) -> bool {
+        self.rows.take_changed()
+    }
+
+    pub(super) fn texels(&self) -> Vec<f32> {
+        table_texels(self.rows.rows(), DASH_ROW_WIDTH, |dash, texels| {
+            let mut lengths = dash.lengths();
+            let period = lengths.iter().sum::<f32>();
+            texels.extend_from_slice(&[lengths.len() as f32, period, dash.offset, cap_index(dash.cap)]);
+            lengths.resize(MAX_DASH_LENGTHS, 0.0);
+            texels.extend_from_slice(&lengths);
+        })
+    }
+}
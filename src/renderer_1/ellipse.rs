@@ -0,0 +1,173 @@
+use super::group::{GroupState, Grouped};
+use super::{Style, Vertex};
+use crate::math::{self, Vector2};
+use crate::raw::Gpu;
+use std::f32::consts::TAU;
+use std::mem;
+
+// Has to be kept in sync with the kinds in renderer_1_ellipse.wgsl.
+pub(super) const ELLIPSE_KIND: u32 = 0;
+// Only the stroke along the outline between the angles.
+pub(super) const ARC_KIND: u32 = 1;
+// Part of the ellipse between the angles with the outline going through the
+// center.
+pub(super) const PIE_KIND: u32 = 2;
+
+#[derive(Debug, Clone)]
+#[repr(C, packed)]
+pub(super) struct Ellipse {
+    pos: Vector2<f32>, // Center position
+    radii: Vector2<f32>,
+    // Counter clockwise in radians.
+    rotation: f32,
+    brush_size: f32,
+    // Angles of the arcs and pies relative to the rotated ellipse, in radians
+    // counter clockwise from the x axis.
+    start_angle: f32,
+    sweep_angle: f32,
+    kind: u32,
+    pub(super) style: Style,
+    pub(super) transform: math::Affine2,
+}
+
+impl Gpu for Ellipse {}
+
+impl Grouped for Ellipse {
+    fn in_group(&self, group: &GroupState) -> Self {
+        let mut style = self.style;
+        style.opacity *= group.opacity;
+        Ellipse {
+            style,
+            transform: group.transform * self.transform,
+            ..self.clone()
+        }
+    }
+}
+
+impl Ellipse {
+    pub(super) fn new(pos: Vector2<f32>, radii: Vector2<f32>, rotation: f32, brush_size: f32) -> Self {
+        Ellipse {
+            pos,
+            radii,
+            rotation,
+            brush_size,
+            start_angle: 0.0,
+            sweep_angle: TAU,
+            kind: ELLIPSE_KIND,
+            style: Style::default(),
+            transform: math::Affine2::IDENTITY,
+        }
+    }
+
+    // Limits the ellipse to the angles; the angles can go either way.
+    pub(super) fn with_angles(self, kind: u32, start_angle: f32, end_angle: f32) -> Self {
+        Ellipse {
+            start_angle: start_angle.min(end_angle),
+            sweep_angle: (end_angle - start_angle).abs().min(TAU),
+            kind,
+            ..self
+        }
+    }
+
+    pub(super) fn buffer_description<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Ellipse>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: mem::offset_of!(Ellipse, pos) as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::offset_of!(Ellipse, radii) as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                // The rotation, brush size, start and sweep angle are
+                // contiguous and read as one attribute; the pipeline has only
+                // 16 attributes.
+                wgpu::VertexAttribute {
+                    offset: mem::offset_of!(Ellipse, rotation) as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::offset_of!(Ellipse, kind) as wgpu::BufferAddress,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(Ellipse, style) + mem::offset_of!(Style, stroke_color))
+                        as wgpu::BufferAddress,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(Ellipse, style) + mem::offset_of!(Style, fill_color))
+                        as wgpu::BufferAddress,
+                    shader_location: 7,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(Ellipse, style) + mem::offset_of!(Style, fill_mode))
+                        as wgpu::BufferAddress,
+                    shader_location: 8,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(Ellipse, transform) + mem::offset_of!(math::Affine2, x_axis))
+                        as wgpu::BufferAddress,
+                    shader_location: 9,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(Ellipse, transform) + mem::offset_of!(math::Affine2, y_axis))
+                        as wgpu::BufferAddress,
+                    shader_location: 10,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(Ellipse, transform) + mem::offset_of!(math::Affine2, translation))
+                        as wgpu::BufferAddress,
+                    shader_location: 11,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(Ellipse, style) + mem::offset_of!(Style, opacity))
+                        as wgpu::BufferAddress,
+                    shader_location: 12,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                // The stroke and the fill gradient.
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(Ellipse, style) + mem::offset_of!(Style, stroke_gradient))
+                        as wgpu::BufferAddress,
+                    shader_location: 13,
+                    format: wgpu::VertexFormat::Uint32x2,
+                },
+            ],
+        }
+    }
+}
+
+pub(super) const ELLIPSE_VERTICES: &[Vertex] = &[
+    Vertex {
+        pos: Vector2 { x: -1.0, y: 1.0 },
+        uv_coords: Vector2 { x: -1.0, y: 1.0 },
+    },
+    Vertex {
+        pos: Vector2 { x: 1.0, y: 1.0 },
+        uv_coords: Vector2 { x: 1.0, y: 1.0 },
+    },
+    Vertex {
+        pos: Vector2 { x: -1.0, y: -1.0 },
+        uv_coords: Vector2 { x: -1.0, y: -1.0 },
+    },
+    Vertex {
+        pos: Vector2 { x: 1.0, y: -1.0 },
+        uv_coords: Vector2 { x: 1.0, y: -1.0 },
+    },
+];
+
+pub(super) const ELLIPSE_INDICES: &[u16] = &[0, 2, 3, 3, 1, 0];
@@ -0,0 +1,72 @@
+use super::table::{table_texels, Rows, TableEntry};
+use crate::gradient::{Gradient, GradientKind};
+
+// Every gradient is a row of the gradient texture: the first texel is the
+// geometry, the second one the kind and the rest the colors of the stops
+// sampled at evenly spaced offsets. The rows are wrapped into the lines of the
+// texture, see `table_texels`. Has to be kept in sync with
+// renderer_1_gradient.wgsl.
+const RAMP_START: u32 = 2;
+const RAMP_WIDTH: u32 = 256;
+pub(super) const GRADIENT_ROW_WIDTH: u32 = RAMP_START + RAMP_WIDTH;
+
+// Has to be kept in sync with the kinds in renderer_1_gradient.wgsl.
+const LINEAR_KIND: f32 = 0.0;
+const RADIAL_KIND: f32 = 1.0;
+const CONIC_KIND: f32 = 2.0;
+
+// The first texel of the row and the kind.
+fn geometry(kind: GradientKind) -> ([f32; 4], f32) {
+    match kind {
+        GradientKind::Linear { start, end } => ([start.x, start.y, end.x, end.y], LINEAR_KIND),
+        GradientKind::Radial { center, radius } => ([center.x, center.y, radius, 0.0], RADIAL_KIND),
+        GradientKind::Conic { center, angle } => ([center.x, center.y, angle, 0.0], CONIC_KIND),
+    }
+}
+
+impl TableEntry for Gradient {
+    // Bits of the kind, the geometry and the stops.
+    type Key = Vec<u32>;
+
+    fn key(&self) -> Vec<u32> {
+        let (geometry, kind) = geometry(self.kind);
+        let stops = self.stops.iter().flat_map(|&(offset, color)| [offset, color.r, color.g, color.b, color.a]);
+        [kind].into_iter().chain(geometry).chain(stops).map(f32::to_bits).collect()
+    }
+}
+
+// Gradients used by the shapes, see `Rows`; 0 means the plain color.
+#[derive(Debug, Default, Clone)]
+pub(super) struct Gradients {
+    rows: Rows<Gradient>,
+}
+
+impl Gradients {
+    pub(super) fn add(&mut self, gradient: &Gradient) -> u32 {
+        self.rows.add(gradient, GRADIENT_ROW_WIDTH, "gradient")
+    }
+
+    pub(super) fn free_unused(&mut self, used: &[bool]) {
+        self.rows.free_unused(used);
+    }
+
+    // Includes the freed rows.
+    pub(super) fn len(&self) -> usize {
+        self.rows.rows().len()
+    }
+
+    pub(super) fn take_changed(&mut self) -> bool {
+        self.rows.take_changed()
+    }
+
+    pub(super) fn texels(&self) -> Vec<f32> {
+        table_texels(self.rows.rows(), GRADIENT_ROW_WIDTH, |gradient, texels| {
+            let (geometry, kind) = geometry(gradient.kind);
+            texels.extend_from_slice(&geometry);
+            texels.extend_from_slice(&[kind, 0.0, 0.0, 0.0]);
+            for color in gradient.ramp(RAMP_WIDTH as usize) {
+                texels.extend_from_slice(&[color.r, color.g, color.b, color.a]);
+            }
+        })
+    }
+}
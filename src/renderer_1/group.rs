@@ -0,0 +1,115 @@
+use crate::math::Affine2;
+
+// Node of the scene moving and fading the shapes added in it, see
+// `Renderer1::push_group`. The shapes refer to the groups by the index plus
+// one, 0 means the shape is in no group.
+#[derive(Debug, Clone)]
+pub(super) struct Group {
+    pub parent: u32,
+    pub transform: Affine2,
+    pub opacity: f32,
+}
+
+impl Group {
+    pub(super) fn new(parent: u32) -> Self {
+        Group {
+            parent,
+            transform: Affine2::IDENTITY,
+            opacity: 1.0,
+        }
+    }
+}
+
+// Group combined with all its parents.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct GroupState {
+    pub transform: Affine2,
+    pub opacity: f32,
+}
+
+// States of the groups with the state without a group first, so they are
+// indexed like the shapes refer to the groups. The parents are added before
+// their groups, so they are resolved first.
+pub(super) fn group_states(groups: &[Group]) -> Vec<GroupState> {
+    let mut states = vec![GroupState {
+        transform: Affine2::IDENTITY,
+        opacity: 1.0,
+    }];
+    for group in groups {
+        let parent = states[group.parent as usize];
+        states.push(GroupState {
+            transform: parent.transform * group.transform,
+            opacity: parent.opacity * group.opacity,
+        });
+    }
+    states
+}
+
+// Shapes which are flattened to the frame by their group before they are
+// uploaded.
+pub(super) trait Grouped: Clone {
+    // The shape with the transform and the opacity of the group applied.
+    fn in_group(&self, group: &GroupState) -> Self;
+}
+
+// Returned by `Renderer1` to set up the group which was just pushed, or by
+// `edit_group` to change it later.
+pub struct GroupBuilder<'a> {
+    pub(super) group: &'a mut Group,
+    pub(super) handle: GroupHandle,
+}
+
+impl GroupBuilder<'_> {
+    // Transforms the shapes of the group, after their own transforms, and the
+    // nested groups.
+    pub fn transform(self, transform: Affine2) -> Self {
+        self.group.transform = transform;
+        self
+    }
+
+    // Fades every shape of the group and the nested groups, see
+    // `ShapeBuilder::opacity`. The shapes are faded one by one, they show
+    // through each other unlike with `Renderer1::layer_opacity`.
+    pub fn opacity(self, opacity: f32) -> Self {
+        self.group.opacity = opacity.clamp(0.0, 1.0);
+        self
+    }
+
+    // Handle to change the group later.
+    pub fn handle(self) -> GroupHandle {
+        self.handle
+    }
+}
+
+// Refers to a group of a `Renderer1`; groups are never removed, so the handle
+// stays valid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GroupHandle(pub(super) u32);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_groups_are_combined() {
+        let groups = [
+            Group {
+                transform: Affine2::translation(10.0, 0.0),
+                opacity: 0.5,
+                ..Group::new(0)
+            },
+            Group {
+                transform: Affine2::scale(2.0, 2.0),
+                opacity: 0.5,
+                ..Group::new(1)
+            },
+            Group::new(0),
+        ];
+        let states = group_states(&groups);
+        assert_eq!(states.len(), 4);
+        assert_eq!(states[2].opacity, 0.25);
+        // The transform of the nested group is applied first.
+        assert_eq!(states[2].transform, Affine2::translation(10.0, 0.0) * Affine2::scale(2.0, 2.0));
+        assert_eq!(states[3], states[0]);
+    }
+}
@@ -0,0 +1,508 @@
+use super::gradient::Gradients;
+use super::group::{GroupState, Grouped};
+use super::scene::DirtyMark;
+use super::{ShapeHandle, Style, Vertex};
+use crate::color::Color;
+use crate::curve;
+use crate::gradient::Gradient;
+use crate::math::{Affine2, Vector2};
+use crate::raw::Gpu;
+use std::mem;
+
+// Shape of the open ends of a line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineCap {
+    // Ends exactly at the end point.
+    #[default]
+    Butt,
+    // Half circle around the end point.
+    Round,
+    // Extends past the end point by half of the line width.
+    Square,
+}
+
+// Shape of the corners between the segments of a polyline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineJoin {
+    // Sharp corner; falls back to `Bevel` when the corner is longer than the
+    // miter limit.
+    #[default]
+    Miter,
+    Round,
+    Bevel,
+}
+
+// Same default as in SVG; cuts off corners sharper than about 29 degrees.
+const DEFAULT_MITER_LIMIT: f32 = 4.0;
+
+// Offset of a half plane which contains everything.
+const NO_PLANE: f32 = 1.0e9;
+
+// Points of a polyline; curves are flattened with the tolerance of the
+// polyline when it is split to segments.
+#[derive(Debug, Clone)]
+pub(super) enum Outline {
+    Points(Vec<Vector2<f32>>),
+    Quadratic([Vector2<f32>; 3]),
+    Cubic([Vector2<f32>; 4]),
+}
+
+// Polyline as it was added to the renderer, it is split to segments when the
+// renderer is prepared.
+#[derive(Debug, Clone)]
+pub(super) struct Polyline {
+    outline: Outline,
+    tolerance: f32,
+    width: f32,
+    cap: LineCap,
+    join: LineJoin,
+    miter_limit: f32,
+    closed: bool,
+    style: Style,
+    transform: Affine2,
+    // Maps the transformed points to the coordinates of the gradients; the
+    // inverse of the transform when not set.
+    paint_transform: Option<Affine2>,
+}
+
+// The shape around one end point of a segment. The segment is the strip along
+// it cut by two half planes; every half plane is the normal and the offset from
+// the end point, points with `dot(p - end, normal) <= offset` are inside.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+struct LineEnd {
+    // Normal, offset and how far the end reaches past the end point.
+    cut: [f32; 4],
+    // Normal, offset and 1.0 when the area within half of the line width
+    // from the end point is added to the half plane.
+    bevel: [f32; 4],
+}
+
+impl LineEnd {
+    fn cap(cap: LineCap, outward: Vector2<f32>, half_width: f32) -> Self {
+        match cap {
+            LineCap::Butt => LineEnd {
+                cut: [outward.x, outward.y, 0.0, 0.0],
+                bevel: [0.0, 0.0, NO_PLANE, 0.0],
+            },
+            LineCap::Square => LineEnd {
+                cut: [outward.x, outward.y, half_width, half_width],
+                bevel: [0.0, 0.0, NO_PLANE, 0.0],
+            },
+            LineCap::Round => LineEnd {
+                cut: [0.0, 0.0, NO_PLANE, half_width],
+                bevel: [outward.x, outward.y, 0.0, 1.0],
+            },
+        }
+    }
+
+    // Ends of the segments before and after a corner where the direction
+    // changes from `incoming` to `outgoing`. The segments are split by the line
+    // halving the corner, so they don't overlap.
+    fn join(
+        join: LineJoin,
+        miter_limit: f32,
+        incoming: Vector2<f32>,
+        outgoing: Vector2<f32>,
+        half_width: f32,
+    ) -> (Self, Self) {
+        let tangent = incoming + outgoing;
+        if tangent.length() < 1.0e-4 {
+            // The line turns back, there is no corner to fill.
+            let cap = match join {
+                LineJoin::Round => LineCap::Round,
+                LineJoin::Miter | LineJoin::Bevel => LineCap::Butt,
+            };
+            return (
+                LineEnd::cap(cap, incoming, half_width),
+                LineEnd::cap(cap, -outgoing, half_width),
+            );
+        }
+        let tangent = tangent.normalized();
+
+        // Normals pointing to the outer side of the corner.
+        let (incoming_normal, outgoing_normal) = if incoming.cross(outgoing) > 0.0 {
+            (-incoming.perpendicular(), -outgoing.perpendicular())
+        } else {
+            (incoming.perpendicular(), outgoing.perpendicular())
+        };
+        let outer = (incoming_normal + outgoing_normal).normalized();
+        // Sine of half of the angle between the segments.
+        let sine = incoming_normal.dot(outer);
+        let bevel = [outer.x, outer.y, half_width * sine, 0.0];
+        // Distance from the corner to the tip of the miter along the segment.
+        let miter = -half_width * incoming_normal.dot(tangent) / incoming.dot(tangent);
+
+        let (bevel, extension) = match join {
+            LineJoin::Miter if sine * miter_limit >= 1.0 => ([0.0, 0.0, NO_PLANE, 0.0], miter),
+            LineJoin::Miter | LineJoin::Bevel => (bevel, half_width),
+            LineJoin::Round => ([bevel[0], bevel[1], bevel[2], 1.0], half_width),
+        };
+        (
+            LineEnd {
+                cut: [tangent.x, tangent.y, 0.0, extension],
+                bevel,
+            },
+            LineEnd {
+                cut: [-tangent.x, -tangent.y, 0.0, extension],
+                bevel,
+            },
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+#[repr(C, packed)]
+pub(super) struct LineSegment {
+    start: Vector2<f32>,
+    end: Vector2<f32>,
+    width: f32,
+    style: Style,
+    start_end: LineEnd,
+    end_end: LineEnd,
+    paint_transform: Affine2,
+}
+
+impl Gpu for LineSegment {}
+
+impl LineSegment {
+    pub(super) fn buffer_description<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<LineSegment>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: mem::offset_of!(LineSegment, start) as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::offset_of!(LineSegment, end) as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::offset_of!(LineSegment, width) as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(LineSegment, style) + mem::offset_of!(Style, stroke_color))
+                        as wgpu::BufferAddress,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(LineSegment, start_end) + mem::offset_of!(LineEnd, cut))
+                        as wgpu::BufferAddress,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(LineSegment, start_end) + mem::offset_of!(LineEnd, bevel))
+                        as wgpu::BufferAddress,
+                    shader_location: 7,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(LineSegment, end_end) + mem::offset_of!(LineEnd, cut))
+                        as wgpu::BufferAddress,
+                    shader_location: 8,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(LineSegment, end_end) + mem::offset_of!(LineEnd, bevel))
+                        as wgpu::BufferAddress,
+                    shader_location: 9,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(LineSegment, style) + mem::offset_of!(Style, opacity))
+                        as wgpu::BufferAddress,
+                    shader_location: 10,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(LineSegment, paint_transform) + mem::offset_of!(Affine2, x_axis))
+                        as wgpu::BufferAddress,
+                    shader_location: 11,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(LineSegment, paint_transform) + mem::offset_of!(Affine2, y_axis))
+                        as wgpu::BufferAddress,
+                    shader_location: 12,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(LineSegment, paint_transform) + mem::offset_of!(Affine2, translation))
+                        as wgpu::BufferAddress,
+                    shader_location: 13,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(LineSegment, style) + mem::offset_of!(Style, stroke_gradient))
+                        as wgpu::BufferAddress,
+                    shader_location: 14,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+            ],
+        }
+    }
+}
+
+pub(super) const LINE_VERTICES: &[Vertex] = &[
+    Vertex {
+        pos: Vector2 { x: -1.0, y: 1.0 },
+        uv_coords: Vector2 { x: -1.0, y: 1.0 },
+    },
+    Vertex {
+        pos: Vector2 { x: 1.0, y: 1.0 },
+        uv_coords: Vector2 { x: 1.0, y: 1.0 },
+    },
+    Vertex {
+        pos: Vector2 { x: -1.0, y: -1.0 },
+        uv_coords: Vector2 { x: -1.0, y: -1.0 },
+    },
+    Vertex {
+        pos: Vector2 { x: 1.0, y: -1.0 },
+        uv_coords: Vector2 { x: 1.0, y: -1.0 },
+    },
+];
+
+pub(super) const LINE_INDICES: &[u16] = &[0, 2, 3, 3, 1, 0];
+
+impl Grouped for Polyline {
+    fn in_group(&self, group: &GroupState) -> Self {
+        let mut polyline = self.clone();
+        polyline.style.opacity *= group.opacity;
+        polyline.transform = group.transform * self.transform;
+        // The points are transformed by the group before the paint transform
+        // maps them back.
+        let ungroup = group.transform.inverse().unwrap_or(Affine2::IDENTITY);
+        polyline.paint_transform = self.paint_transform.map(|paint_transform| paint_transform * ungroup);
+        polyline
+    }
+}
+
+impl Polyline {
+    pub(super) fn style(&self) -> &Style {
+        &self.style
+    }
+
+    pub(super) fn new(outline: Outline, width: f32) -> Self {
+        Polyline {
+            outline,
+            tolerance: curve::DEFAULT_TOLERANCE,
+            width,
+            cap: LineCap::default(),
+            join: LineJoin::default(),
+            miter_limit: DEFAULT_MITER_LIMIT,
+            closed: false,
+            style: Style::default(),
+            transform: Affine2::IDENTITY,
+            paint_transform: None,
+        }
+    }
+
+    // Closed line around a filled area painted with the stroke color or
+    // gradient and the opacity of the style. The points are already
+    // transformed, the paint transform maps them back to the coordinates of
+    // the gradient.
+    pub(super) fn outline(points: Vec<Vector2<f32>>, width: f32, style: Style, paint_transform: Affine2) -> Self {
+        Polyline {
+            closed: true,
+            style: Style {
+                stroke_color: style.stroke_color,
+                stroke_gradient: style.stroke_gradient,
+                opacity: style.opacity,
+                ..Style::default()
+            },
+            paint_transform: Some(paint_transform),
+            ..Polyline::new(Outline::Points(points), width)
+        }
+    }
+
+    // Outline without width; drawn only outside of the area, it adds the anti
+    // aliased edge to it.
+    pub(super) fn fringe(points: Vec<Vector2<f32>>, style: Style, paint_transform: Affine2) -> Self {
+        Polyline {
+            join: LineJoin::Round,
+            ..Polyline::outline(points, 0.0, style, paint_transform)
+        }
+    }
+
+    // Transformed points with the curves flattened and without repeated
+    // points. Curves are flattened after the transform so the tolerance stays
+    // in pixels.
+    fn points(&self) -> Vec<Vector2<f32>> {
+        let transform = |point| self.transform.transform_point(point);
+        let mut points = Vec::new();
+        match self.outline {
+            Outline::Points(ref outline) => points.extend(outline.iter().copied().map(transform)),
+            Outline::Quadratic(control_points) => {
+                let [start, control, end] = control_points.map(transform);
+                points.push(start);
+                curve::flatten_quadratic(start, control, end, self.tolerance, &mut points);
+            }
+            Outline::Cubic(control_points) => {
+                let [start, control1, control2, end] = control_points.map(transform);
+                points.push(start);
+                curve::flatten_cubic(start, control1, control2, end, self.tolerance, &mut points);
+            }
+        }
+        points.dedup();
+        points
+    }
+
+    // Appends the segments of the polyline with their ends already shaped.
+    pub(super) fn segments(&self, segments: &mut Vec<LineSegment>) {
+        let half_width = self.width * self.transform.average_scale() / 2.0;
+        let paint_transform = self
+            .paint_transform
+            .or_else(|| self.transform.inverse())
+            .unwrap_or(Affine2::IDENTITY);
+        let flattened = self.points();
+        let mut points = flattened.as_slice();
+        if points.is_empty() {
+            return;
+        }
+        if self.closed && points.len() > 2 && points.first() == points.last() {
+            points = &points[..points.len() - 1];
+        }
+
+        if points.len() == 1 {
+            // A dot; caps of a segment without direction.
+            if self.cap != LineCap::Butt {
+                let direction = Vector2 { x: 1.0, y: 0.0 };
+                segments.push(LineSegment {
+                    start: points[0],
+                    end: points[0],
+                    width: 2.0 * half_width,
+                    style: self.style,
+                    start_end: LineEnd::cap(self.cap, -direction, half_width),
+                    end_end: LineEnd::cap(self.cap, direction, half_width),
+                    paint_transform,
+                });
+            }
+            return;
+        }
+
+        let closed = self.closed && points.len() > 2;
+        let count = if closed { points.len() } else { points.len() - 1 };
+        let direction = |index: usize| {
+            (points[(index + 1) % points.len()] - points[index]).normalized()
+        };
+        let join = |incoming: Vector2<f32>, outgoing: Vector2<f32>| {
+            LineEnd::join(self.join, self.miter_limit, incoming, outgoing, half_width)
+        };
+
+        // Start of the first segment.
+        let mut start_end = if closed {
+            join(direction(count - 1), direction(0)).1
+        } else {
+            LineEnd::cap(self.cap, -direction(0), half_width)
+        };
+        for index in 0..count {
+            let (end_end, next_start_end) = if closed || index + 1 < count {
+                join(direction(index), direction((index + 1) % count))
+            } else {
+                let cap = LineEnd::cap(self.cap, direction(index), half_width);
+                (cap, cap)
+            };
+            segments.push(LineSegment {
+                start: points[index],
+                end: points[(index + 1) % points.len()],
+                width: 2.0 * half_width,
+                style: self.style,
+                start_end,
+                end_end,
+                paint_transform,
+            });
+            start_end = next_start_end;
+        }
+    }
+}
+
+// Returned by the line functions of `Renderer1` to set up the optional
+// properties of the line which was just added.
+pub struct LineBuilder<'a> {
+    pub(super) polyline: &'a mut Polyline,
+    pub(super) z_index: &'a mut i32,
+    pub(super) gradients: &'a mut Gradients,
+    pub(super) dirty: DirtyMark<'a>,
+    pub(super) handle: ShapeHandle,
+}
+
+impl LineBuilder<'_> {
+    pub fn stroke(mut self, color: Color) -> Self {
+        self.dirty.update(&mut self.polyline.style, |style| {
+            style.stroke_color = color;
+            style.stroke_gradient = 0;
+        });
+        self
+    }
+
+    // Paints the line with the gradient in the coordinates of the points
+    // before the transform.
+    pub fn stroke_gradient(mut self, gradient: &Gradient) -> Self {
+        let gradient = self.gradients.add(gradient);
+        self.dirty.update(&mut self.polyline.style, |style| style.stroke_gradient = gradient);
+        self
+    }
+
+    // Fades the line, see `ShapeBuilder::opacity`.
+    pub fn opacity(mut self, opacity: f32) -> Self {
+        self.dirty.update(&mut self.polyline.style, |style| style.opacity = opacity.clamp(0.0, 1.0));
+        self
+    }
+
+    pub fn cap(mut self, cap: LineCap) -> Self {
+        self.dirty.set(&mut self.polyline.cap, cap);
+        self
+    }
+
+    pub fn join(mut self, join: LineJoin) -> Self {
+        self.dirty.set(&mut self.polyline.join, join);
+        self
+    }
+
+    // Longest miter join as a multiple of the line width.
+    pub fn miter_limit(mut self, miter_limit: f32) -> Self {
+        self.dirty.set(&mut self.polyline.miter_limit, miter_limit);
+        self
+    }
+
+    // Largest distance in pixels between a curve and the line segments it is
+    // drawn with.
+    pub fn tolerance(mut self, tolerance: f32) -> Self {
+        self.dirty.set(&mut self.polyline.tolerance, tolerance);
+        self
+    }
+
+    // Transforms the points of the line; the width is scaled by the average
+    // scale of the transform.
+    pub fn transform(mut self, transform: Affine2) -> Self {
+        self.dirty.set(&mut self.polyline.transform, transform);
+        self
+    }
+
+    // Connects the last point with the first one; the line has joins instead
+    // of caps then.
+    pub fn close(mut self) -> Self {
+        self.dirty.set(&mut self.polyline.closed, true);
+        self
+    }
+
+    // Lines are ordered with the other shapes by the z-index, see
+    // `ShapeBuilder::z_index`.
+    pub fn z_index(mut self, z_index: i32) -> Self {
+        self.dirty.set(self.z_index, z_index);
+        self
+    }
+
+    // Handle to change or remove the line later.
+    pub fn handle(self) -> ShapeHandle {
+        self.handle
+    }
+}
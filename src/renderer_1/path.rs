@@ -0,0 +1,508 @@
+use super::gradient::Gradients;
+use super::group::{GroupState, Grouped};
+use super::scene::DirtyMark;
+use super::line::{LineSegment, Polyline, LINE_INDICES, LINE_VERTICES};
+use super::{create_pipeline, create_shader, upload_instances, ShapeHandle, Style, Vertex, STENCIL_FORMAT};
+use crate::color::Color;
+use crate::curve;
+use crate::gradient::Gradient;
+use crate::math::{Affine2, Vector2};
+use crate::path::{FillRule, Path};
+use crate::raw::{Gpu, Raw};
+use crate::render_common::AntiAliasing;
+use crate::render_target::RenderTarget;
+use std::mem;
+use std::ops::Range;
+use wgpu::util::DeviceExt;
+use wgpu::BindGroup;
+
+// Paths are filled by stencil and cover: a triangle fan from one point over
+// every edge counts the windings of the outline in the stencil buffer, then a
+// quad over the path paints the pixels the fill rule counts as inside and
+// resets the stencil for the next path.
+
+// Path as it was added to the renderer.
+#[derive(Debug, Clone)]
+pub(super) struct PathShape {
+    path: Path,
+    fill_color: Color,
+    // See `Style::fill_gradient`.
+    fill_gradient: u32,
+    fill_rule: FillRule,
+    tolerance: f32,
+    transform: Affine2,
+    opacity: f32,
+}
+
+impl PathShape {
+    pub(super) fn fill_gradient(&self) -> u32 {
+        self.fill_gradient
+    }
+
+    pub(super) fn new(path: &Path) -> Self {
+        PathShape {
+            path: path.clone(),
+            fill_color: Color::YELLOW,
+            fill_gradient: 0,
+            fill_rule: FillRule::default(),
+            tolerance: curve::DEFAULT_TOLERANCE,
+            transform: Affine2::IDENTITY,
+            opacity: 1.0,
+        }
+    }
+
+    // Path of a clip; it is drawn white to the clip mask.
+    pub(super) fn clip(path: &Path, fill_rule: FillRule) -> Self {
+        PathShape {
+            fill_color: Color::WHITE,
+            fill_rule,
+            ..PathShape::new(path)
+        }
+    }
+}
+
+impl Grouped for PathShape {
+    fn in_group(&self, group: &GroupState) -> Self {
+        PathShape {
+            transform: group.transform * self.transform,
+            opacity: self.opacity * group.opacity,
+            ..self.clone()
+        }
+    }
+}
+
+// Returned by `Renderer1::path` to set up the optional properties of the path
+// which was just added.
+pub struct PathBuilder<'a> {
+    pub(super) shape: &'a mut PathShape,
+    pub(super) z_index: &'a mut i32,
+    pub(super) gradients: &'a mut Gradients,
+    pub(super) dirty: DirtyMark<'a>,
+    pub(super) handle: ShapeHandle,
+}
+
+impl PathBuilder<'_> {
+    pub fn fill(mut self, color: Color) -> Self {
+        self.dirty.set(&mut self.shape.fill_color, color);
+        self.dirty.set(&mut self.shape.fill_gradient, 0);
+        self
+    }
+
+    // Fills the path with the gradient in the coordinates of the path before
+    // its transform.
+    pub fn fill_gradient(mut self, gradient: &Gradient) -> Self {
+        let gradient = self.gradients.add(gradient);
+        self.dirty.set(&mut self.shape.fill_gradient, gradient);
+        self
+    }
+
+    // Fades the path, see `ShapeBuilder::opacity`.
+    pub fn opacity(mut self, opacity: f32) -> Self {
+        self.dirty.set(&mut self.shape.opacity, opacity.clamp(0.0, 1.0));
+        self
+    }
+
+    pub fn fill_rule(mut self, fill_rule: FillRule) -> Self {
+        self.dirty.set(&mut self.shape.fill_rule, fill_rule);
+        self
+    }
+
+    // Largest distance in pixels between the curves of the path and the line
+    // segments they are filled with.
+    pub fn tolerance(mut self, tolerance: f32) -> Self {
+        self.dirty.set(&mut self.shape.tolerance, tolerance);
+        self
+    }
+
+    pub fn transform(mut self, transform: Affine2) -> Self {
+        self.dirty.set(&mut self.shape.transform, transform);
+        self
+    }
+
+    // Paths are ordered with the other shapes by the z-index, see
+    // `ShapeBuilder::z_index`.
+    pub fn z_index(mut self, z_index: i32) -> Self {
+        self.dirty.set(self.z_index, z_index);
+        self
+    }
+
+    // Handle to change or remove the path later.
+    pub fn handle(self) -> ShapeHandle {
+        self.handle
+    }
+}
+
+// Quad painting the inside of one path.
+#[derive(Debug, Clone)]
+#[repr(C, packed)]
+struct PathCover {
+    min: Vector2<f32>,
+    max: Vector2<f32>,
+    color: Color,
+    opacity: f32,
+    // Maps the pixels to the coordinates of the gradient.
+    paint_transform: Affine2,
+    gradient: u32,
+}
+
+impl Gpu for PathCover {}
+
+impl PathCover {
+    fn buffer_description<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<PathCover>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: mem::offset_of!(PathCover, min) as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::offset_of!(PathCover, max) as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::offset_of!(PathCover, color) as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::offset_of!(PathCover, opacity) as wgpu::BufferAddress,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(PathCover, paint_transform) + mem::offset_of!(Affine2, x_axis))
+                        as wgpu::BufferAddress,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(PathCover, paint_transform) + mem::offset_of!(Affine2, y_axis))
+                        as wgpu::BufferAddress,
+                    shader_location: 7,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(PathCover, paint_transform) + mem::offset_of!(Affine2, translation))
+                        as wgpu::BufferAddress,
+                    shader_location: 8,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::offset_of!(PathCover, gradient) as wgpu::BufferAddress,
+                    shader_location: 9,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+            ],
+        }
+    }
+}
+
+fn fan_buffer_description<'a>() -> wgpu::VertexBufferLayout<'a> {
+    wgpu::VertexBufferLayout {
+        array_stride: mem::size_of::<Vector2<f32>>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &[wgpu::VertexAttribute {
+            offset: 0,
+            shader_location: 0,
+            format: wgpu::VertexFormat::Float32x2,
+        }],
+    }
+}
+
+// Bits of the stencil value compared by the fill rule.
+fn read_mask(fill_rule: FillRule) -> u32 {
+    match fill_rule {
+        FillRule::NonZero => 0xff,
+        FillRule::EvenOdd => 0x01,
+    }
+}
+
+fn stencil_state(compare: wgpu::CompareFunction, pass_op: wgpu::StencilOperation, fill_rule: FillRule) -> wgpu::StencilState {
+    let face = wgpu::StencilFaceState {
+        compare,
+        fail_op: pass_op,
+        depth_fail_op: wgpu::StencilOperation::Keep,
+        pass_op,
+    };
+    wgpu::StencilState {
+        front: face,
+        back: face,
+        read_mask: read_mask(fill_rule),
+        write_mask: 0xff,
+    }
+}
+
+// Counts the windings; counter clockwise triangles increment, clockwise
+// triangles decrement the stencil.
+fn create_fan_pipeline(
+    target: &dyn RenderTarget,
+    layout: &wgpu::PipelineLayout,
+    anti_aliasing: AntiAliasing,
+) -> wgpu::RenderPipeline {
+    let shader = create_shader(
+        target,
+        "Path Stencil Shader",
+        include_str!("../shaders/renderer_1_path_stencil.wgsl"),
+        anti_aliasing,
+    );
+    let face = |pass_op| wgpu::StencilFaceState {
+        compare: wgpu::CompareFunction::Always,
+        fail_op: wgpu::StencilOperation::Keep,
+        depth_fail_op: wgpu::StencilOperation::Keep,
+        pass_op,
+    };
+    target
+        .device()
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Path Stencil Pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[fan_buffer_description()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: target.format(),
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::empty(),
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: STENCIL_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState {
+                    front: face(wgpu::StencilOperation::IncrementWrap),
+                    back: face(wgpu::StencilOperation::DecrementWrap),
+                    read_mask: 0xff,
+                    write_mask: 0xff,
+                },
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: anti_aliasing.sample_count(),
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        })
+}
+
+// Draw calls of one path.
+struct PathDraw {
+    fill_rule: FillRule,
+    fan: Range<u32>,
+    fringe: Range<u32>,
+}
+
+pub(super) struct PreparedPaths {
+    draws: Vec<PathDraw>,
+    fan_vertices: Vec<Vector2<f32>>,
+    covers: Vec<PathCover>,
+    fringe_segments: Vec<LineSegment>,
+    fan_buffer: wgpu::Buffer,
+    cover_buffer: wgpu::Buffer,
+    fringe_buffer: wgpu::Buffer,
+    quad_vertex_buffer: wgpu::Buffer,
+    quad_index_buffer: wgpu::Buffer,
+    fan_pipeline: wgpu::RenderPipeline,
+    // Indexed by the fill rule.
+    cover_pipelines: [wgpu::RenderPipeline; 2],
+    // Anti aliased edge outside of the paths; there is none without the
+    // analytic anti aliasing.
+    fringe_pipelines: Option<[wgpu::RenderPipeline; 2]>,
+}
+
+const FILL_RULES: [FillRule; 2] = [FillRule::NonZero, FillRule::EvenOdd];
+
+fn rule_index(fill_rule: FillRule) -> usize {
+    match fill_rule {
+        FillRule::NonZero => 0,
+        FillRule::EvenOdd => 1,
+    }
+}
+
+impl PreparedPaths {
+    pub(super) fn new(
+        target: &dyn RenderTarget,
+        layout: &wgpu::PipelineLayout,
+        anti_aliasing: AntiAliasing,
+    ) -> Self {
+        let cover_shader = create_shader(
+            target,
+            "Path Cover Shader",
+            include_str!("../shaders/renderer_1_path_cover.wgsl"),
+            anti_aliasing,
+        );
+        let cover_pipelines = FILL_RULES.map(|fill_rule| {
+            create_pipeline(
+                target,
+                "Path Cover Pipeline",
+                layout,
+                &cover_shader,
+                &[Vertex::buffer_description(), PathCover::buffer_description()],
+                anti_aliasing,
+                stencil_state(wgpu::CompareFunction::NotEqual, wgpu::StencilOperation::Zero, fill_rule),
+            )
+        });
+
+        let fringe_pipelines = (anti_aliasing == AntiAliasing::Analytic).then(|| {
+            let line_shader = create_shader(
+                target,
+                "Path Fringe Shader",
+                include_str!("../shaders/renderer_1_line.wgsl"),
+                anti_aliasing,
+            );
+            FILL_RULES.map(|fill_rule| {
+                create_pipeline(
+                    target,
+                    "Path Fringe Pipeline",
+                    layout,
+                    &line_shader,
+                    &[Vertex::buffer_description(), LineSegment::buffer_description()],
+                    anti_aliasing,
+                    stencil_state(wgpu::CompareFunction::Equal, wgpu::StencilOperation::Keep, fill_rule),
+                )
+            })
+        });
+
+        let instances_buffer = |label| {
+            target.device().create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: 100000 * wgpu::COPY_BUFFER_ALIGNMENT,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+
+        PreparedPaths {
+            draws: Vec::new(),
+            fan_vertices: Vec::new(),
+            covers: Vec::new(),
+            fringe_segments: Vec::new(),
+            fan_buffer: instances_buffer("Path Fan Buffer"),
+            cover_buffer: instances_buffer("Path Cover Buffer"),
+            fringe_buffer: instances_buffer("Path Fringe Buffer"),
+            quad_vertex_buffer: target
+                .device()
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Path Vertex Buffer"),
+                    contents: LINE_VERTICES.get_raw(),
+                    usage: wgpu::BufferUsages::VERTEX,
+                }),
+            quad_index_buffer: target
+                .device()
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Path Index Buffer"),
+                    contents: LINE_INDICES.get_raw(),
+                    usage: wgpu::BufferUsages::INDEX,
+                }),
+            fan_pipeline: create_fan_pipeline(target, layout, anti_aliasing),
+            cover_pipelines,
+            fringe_pipelines,
+        }
+    }
+
+    // Splits the paths to the fans, covers and fringes drawing them.
+    pub(super) fn tessellate(&mut self, shapes: &[PathShape]) {
+        self.draws.clear();
+        self.fan_vertices.clear();
+        self.covers.clear();
+        self.fringe_segments.clear();
+        for shape in shapes {
+            // The fringe is painted like the fill.
+            let style = Style {
+                stroke_color: shape.fill_color,
+                stroke_gradient: shape.fill_gradient,
+                opacity: shape.opacity,
+                ..Style::default()
+            };
+            let paint_transform = shape.transform.inverse().unwrap_or(Affine2::IDENTITY);
+            let subpaths = shape.path.transformed(shape.transform).flatten(shape.tolerance);
+            // Empty paths keep their draw without any triangles so the draws
+            // stay in the order of the shapes.
+            let anchor = subpaths.first().map_or(Vector2 { x: 0.0, y: 0.0 }, |subpath| subpath.points[0]);
+            let fan_start = self.fan_vertices.len() as u32;
+            let fringe_start = self.fringe_segments.len() as u32;
+            let mut min = anchor;
+            let mut max = anchor;
+            for subpath in subpaths {
+                let points = &subpath.points;
+                for (index, &point) in points.iter().enumerate() {
+                    self.fan_vertices.extend_from_slice(&[anchor, point, points[(index + 1) % points.len()]]);
+                    min = Vector2 { x: min.x.min(point.x), y: min.y.min(point.y) };
+                    max = Vector2 { x: max.x.max(point.x), y: max.y.max(point.y) };
+                }
+                Polyline::fringe(subpath.points, style, paint_transform).segments(&mut self.fringe_segments);
+            }
+            self.covers.push(PathCover {
+                min,
+                max,
+                color: shape.fill_color,
+                opacity: shape.opacity,
+                paint_transform,
+                gradient: shape.fill_gradient,
+            });
+            self.draws.push(PathDraw {
+                fill_rule: shape.fill_rule,
+                fan: fan_start..self.fan_vertices.len() as u32,
+                fringe: fringe_start..self.fringe_segments.len() as u32,
+            });
+        }
+    }
+
+    pub(super) fn upload(&mut self, target: &dyn RenderTarget) {
+        upload_instances(target, &mut self.fan_buffer, 0, self.fan_vertices.get_raw(), "path_fan");
+        upload_instances(target, &mut self.cover_buffer, 0, self.covers.get_raw(), "path_cover");
+        upload_instances(target, &mut self.fringe_buffer, 0, self.fringe_segments.get_raw(), "path_fringe");
+    }
+
+    // Draws the paths with the indices in the range.
+    pub(super) fn render<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        perspective_bind_group: &'a BindGroup,
+        paths: Range<usize>,
+    ) {
+        render_pass.set_bind_group(0, perspective_bind_group, &[]);
+        render_pass.set_index_buffer(self.quad_index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        for (index, draw) in self.draws.iter().enumerate().skip(paths.start).take(paths.len()) {
+            if draw.fan.is_empty() {
+                continue;
+            }
+            let rule = rule_index(draw.fill_rule);
+
+            render_pass.set_pipeline(&self.fan_pipeline);
+            render_pass.set_vertex_buffer(0, self.fan_buffer.slice(..));
+            render_pass.draw(draw.fan.clone(), 0..1);
+
+            render_pass.set_vertex_buffer(0, self.quad_vertex_buffer.slice(..));
+            if let Some(fringe_pipelines) = &self.fringe_pipelines {
+                render_pass.set_pipeline(&fringe_pipelines[rule]);
+                render_pass.set_vertex_buffer(1, self.fringe_buffer.slice(..));
+                render_pass.draw_indexed(0..(LINE_INDICES.len() as u32), 0, draw.fringe.clone());
+            }
+
+            render_pass.set_pipeline(&self.cover_pipelines[rule]);
+            render_pass.set_vertex_buffer(1, self.cover_buffer.slice(..));
+            let cover = index as u32;
+            render_pass.draw_indexed(0..(LINE_INDICES.len() as u32), 0, cover..cover + 1);
+        }
+    }
+}
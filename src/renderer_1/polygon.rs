@@ -0,0 +1,150 @@
+use super::group::{GroupState, Grouped};
+use super::line::Polyline;
+use super::{Style, FILL_FLAG, STROKE_FLAG};
+use crate::color::Color;
+use crate::math::{Affine2, Vector2};
+use crate::raw::Gpu;
+use crate::triangulate::triangulate;
+use std::mem;
+
+// Distance to the edges which are not a part of the outline, so they are
+// never anti aliased.
+const INNER_EDGE: f32 = 1.0e6;
+
+// Polygon as it was added to the renderer; the fill is triangulated and the
+// stroke is turned to a closed line when the renderer is prepared.
+#[derive(Debug, Clone)]
+pub(super) struct PolygonShape {
+    points: Vec<Vector2<f32>>,
+    brush_size: f32,
+    pub(super) style: Style,
+    pub(super) transform: Affine2,
+}
+
+// Vertex of the triangulated fill.
+#[derive(Debug, Clone)]
+#[repr(C, packed)]
+pub(super) struct PolygonVertex {
+    pos: Vector2<f32>,
+    // Distances to the edges of the triangle; the interpolated minimum is the
+    // distance to the outline of the polygon.
+    edge_distances: [f32; 3],
+    color: Color,
+    opacity: f32,
+    // Position before the transform, where the gradient is.
+    paint_position: Vector2<f32>,
+    gradient: u32,
+}
+
+impl Gpu for PolygonVertex {}
+
+impl PolygonVertex {
+    pub(super) fn buffer_description<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<PolygonVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: mem::offset_of!(PolygonVertex, pos) as wgpu::BufferAddress,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::offset_of!(PolygonVertex, edge_distances) as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::offset_of!(PolygonVertex, color) as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::offset_of!(PolygonVertex, opacity) as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::offset_of!(PolygonVertex, paint_position) as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::offset_of!(PolygonVertex, gradient) as wgpu::BufferAddress,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+            ],
+        }
+    }
+}
+
+impl Grouped for PolygonShape {
+    fn in_group(&self, group: &GroupState) -> Self {
+        let mut shape = self.clone();
+        shape.style.opacity *= group.opacity;
+        shape.transform = group.transform * self.transform;
+        shape
+    }
+}
+
+impl PolygonShape {
+    pub(super) fn new(points: &[(f32, f32)], brush_size: f32) -> Self {
+        PolygonShape {
+            points: points.iter().map(|&(x, y)| Vector2 { x, y }).collect(),
+            brush_size,
+            style: Style::default(),
+            transform: Affine2::IDENTITY,
+        }
+    }
+
+    fn transformed_points(&self) -> Vec<Vector2<f32>> {
+        self.points.iter().map(|&point| self.transform.transform_point(point)).collect()
+    }
+
+    // Appends the triangles of the fill.
+    pub(super) fn fill_vertices(&self, vertices: &mut Vec<PolygonVertex>) {
+        if self.style.fill_mode & FILL_FLAG == 0 {
+            return;
+        }
+        let points = self.transformed_points();
+        for triangle in triangulate(&points) {
+            let corners = triangle.indices.map(|index| points[index]);
+            let area = (corners[1] - corners[0]).cross(corners[2] - corners[0]);
+            for corner in 0..3 {
+                // The edge opposite to the corner starts at the next corner.
+                let mut edge_distances = [0.0; 3];
+                let opposite = (corner + 1) % 3;
+                let edge_length = (corners[(opposite + 1) % 3] - corners[opposite]).length();
+                edge_distances[opposite] = area / edge_length;
+                for (edge, distance) in edge_distances.iter_mut().enumerate() {
+                    if !triangle.outline[edge] {
+                        *distance = INNER_EDGE;
+                    }
+                }
+                vertices.push(PolygonVertex {
+                    pos: corners[corner],
+                    edge_distances,
+                    color: self.style.fill_color,
+                    opacity: self.style.opacity,
+                    paint_position: self.points[triangle.indices[corner]],
+                    gradient: self.style.fill_gradient,
+                });
+            }
+        }
+    }
+
+    // Line along the outline; the stroke is centered on the edges. The fill and
+    // the stroke are drawn separately, so both are faded by the opacity.
+    pub(super) fn stroke(&self) -> Option<Polyline> {
+        (self.style.fill_mode & STROKE_FLAG != 0)
+            .then(|| {
+                Polyline::outline(
+                    self.transformed_points(),
+                    self.brush_size * self.transform.average_scale(),
+                    self.style,
+                    self.transform.inverse().unwrap_or(Affine2::IDENTITY),
+                )
+            })
+    }
+}
@@ -0,0 +1,550 @@
+use std::mem;
+use std::ops::Range;
+
+// Kinds of shapes which are stored separately, every kind is drawn from its
+// own buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(super) enum ShapeKind {
+    Circle,
+    Rectangle,
+    Ellipse,
+    Polygon,
+    Line,
+    Path,
+    Image,
+    Text,
+}
+
+// Returned by the builders of the shapes to change or remove the shape later,
+// also after the renderer is prepared. The handle stays valid while other
+// shapes are added and removed; it is ignored once its shape is removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ShapeHandle {
+    pub(super) kind: ShapeKind,
+    slot: u32,
+    generation: u32,
+}
+
+// Place of a shape in the painter's order across all kinds: shapes with a
+// higher z-index are drawn over the lower ones, shapes with the same z-index
+// in the order they were added. The clip and the group don't change the order,
+// the sequences are unique.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(super) struct DrawKey {
+    pub z_index: i32,
+    pub sequence: u64,
+    // Clip the shape was added in, see `Clip`.
+    pub clip: u32,
+    // Group the shape was added in, see `Group`; it only moves and fades the
+    // shape.
+    pub group: u32,
+    // The shape was removed and its place is left empty until the shapes are
+    // compacted; it isn't drawn.
+    pub removed: bool,
+}
+
+// Run of shapes of one kind, z-index and clip which follow each other in the
+// painter's order; the shapes are the indices in the shapes of the kind. The
+// places of removed shapes split the runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Batch {
+    pub kind: ShapeKind,
+    pub z_index: i32,
+    pub clip: u32,
+    pub shapes: Range<usize>,
+}
+
+// Merges the keys of all the kinds, every one sorted, to the batches in the
+// painter's order.
+pub(super) fn batches(kinds: &[(ShapeKind, &[DrawKey])]) -> Vec<Batch> {
+    let mut next = vec![0; kinds.len()];
+    let mut batches: Vec<Batch> = Vec::new();
+    while let Some(kind) = (0..kinds.len())
+        .filter(|&kind| next[kind] < kinds[kind].1.len())
+        .min_by_key(|&kind| kinds[kind].1[next[kind]])
+    {
+        let index = next[kind];
+        let DrawKey { z_index, clip, removed, .. } = kinds[kind].1[index];
+        next[kind] += 1;
+        if removed {
+            continue;
+        }
+        match batches.last_mut() {
+            Some(batch)
+                if batch.kind == kinds[kind].0
+                    && batch.z_index == z_index
+                    && batch.clip == clip
+                    && batch.shapes.end == index =>
+            {
+                batch.shapes.end = index + 1
+            }
+            _ => batches.push(Batch {
+                kind: kinds[kind].0,
+                z_index,
+                clip,
+                shapes: index..index + 1,
+            }),
+        }
+    }
+    batches
+}
+
+// Splits the batches to the runs drawn straight to the frame and the layers
+// composed as a whole; a layer is made of the shapes of one z-index which
+// `layer` returns a layer for. The layer is `None` for the other runs.
+pub(super) fn layers<L: Copy>(batches: &[Batch], layer: impl Fn(i32) -> Option<L>) -> Vec<(Range<usize>, Option<L>)> {
+    let mut layers: Vec<(Range<usize>, Option<L>)> = Vec::new();
+    for (index, batch) in batches.iter().enumerate() {
+        let batch_layer = layer(batch.z_index);
+        match layers.last_mut() {
+            Some((run, None)) if batch_layer.is_none() => run.end = index + 1,
+            Some((run, Some(_))) if batches[run.start].z_index == batch.z_index => run.end = index + 1,
+            _ => layers.push((index..index + 1, batch_layer)),
+        }
+    }
+    layers
+}
+
+// Slot of the places of the removed shapes.
+const NO_SLOT: u32 = u32::MAX;
+
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    // Index of the shape in the packed shapes; `None` for a free slot.
+    index: Option<u32>,
+    // Incremented when the slot is freed so the old handles don't match.
+    generation: u32,
+}
+
+// Changes of the shapes of one kind since the last upload.
+#[derive(Debug, Clone, Default)]
+struct Changes {
+    // Shapes to be uploaded again as one range.
+    dirty: Option<Range<usize>>,
+    // Whether any shape was added, changed or removed; removed shapes only
+    // change the draw calls.
+    changed: bool,
+}
+
+impl Changes {
+    fn mark_dirty(&mut self, range: Range<usize>) {
+        self.dirty = Some(match self.dirty.take() {
+            Some(dirty) => dirty.start.min(range.start)..dirty.end.max(range.end),
+            None => range,
+        });
+        self.changed = true;
+    }
+}
+
+// Given to the builders with the shape they edit; the setters mark the shape
+// to be uploaded again only when they change it.
+pub(super) struct DirtyMark<'a> {
+    index: usize,
+    changes: &'a mut Changes,
+}
+
+impl DirtyMark<'_> {
+    pub(super) fn set<V: PartialEq>(&mut self, field: &mut V, value: V) {
+        if *field != value {
+            *field = value;
+            self.changes.mark_dirty(self.index..self.index + 1);
+        }
+    }
+
+    // Changes a copy of the value, e.g. a packed style whose fields can't be
+    // borrowed, and stores it when it differs.
+    pub(super) fn update<V: Clone + PartialEq>(&mut self, value: &mut V, change: impl FnOnce(&mut V)) {
+        let mut changed = value.clone();
+        change(&mut changed);
+        self.set(value, changed);
+    }
+}
+
+// Shapes of one kind packed in the drawing order as they are uploaded, with
+// the slots the handles point to. Changes since the last upload are tracked
+// as one range of the shapes. Removed shapes leave their places empty, so the
+// shapes after them don't move, until more than half of the places are empty.
+#[derive(Debug, Clone)]
+pub(super) struct Shapes<T> {
+    items: Vec<T>,
+    // Draw key and slot of every shape; `NO_SLOT` for the removed ones.
+    keys: Vec<DrawKey>,
+    item_slots: Vec<u32>,
+    slots: Vec<Slot>,
+    free_slots: Vec<u32>,
+    // Number of the places of removed shapes.
+    removed: usize,
+    changes: Changes,
+}
+
+impl<T> Default for Shapes<T> {
+    fn default() -> Self {
+        Shapes {
+            items: Vec::new(),
+            keys: Vec::new(),
+            item_slots: Vec::new(),
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            removed: 0,
+            changes: Changes::default(),
+        }
+    }
+}
+
+impl<T> Shapes<T> {
+    // Includes the places of the removed shapes, see `DrawKey::removed`.
+    pub(super) fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    pub(super) fn keys(&self) -> &[DrawKey] {
+        &self.keys
+    }
+
+    // The shapes without the places of the removed ones.
+    pub(super) fn live_items(&self) -> impl Iterator<Item = &T> {
+        self.items.iter().zip(&self.keys).filter(|(_, key)| !key.removed).map(|(item, _)| item)
+    }
+
+    // Adds the shape with the z-index 0; `sequence` has to be bigger than the
+    // sequences of all the shapes added before.
+    pub(super) fn push(&mut self, kind: ShapeKind, sequence: u64, clip: u32, group: u32, item: T) -> ShapeHandle {
+        let key = DrawKey {
+            z_index: 0,
+            sequence,
+            clip,
+            group,
+            removed: false,
+        };
+        let index = self.keys.partition_point(|other| *other < key);
+        let slot = match self.free_slots.pop() {
+            Some(slot) => slot,
+            None => {
+                self.slots.push(Slot {
+                    index: None,
+                    generation: 0,
+                });
+                self.slots.len() as u32 - 1
+            }
+        };
+        self.items.insert(index, item);
+        self.keys.insert(index, key);
+        self.item_slots.insert(index, slot);
+        self.update_slots(index);
+        self.mark_dirty(index..self.items.len());
+        ShapeHandle {
+            kind,
+            slot,
+            generation: self.slots[slot as usize].generation,
+        }
+    }
+
+    fn index(&self, handle: ShapeHandle) -> Option<usize> {
+        let slot = self.slots.get(handle.slot as usize)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        slot.index.map(|index| index as usize)
+    }
+
+    // The shape and its z-index to be changed through the mark, which makes
+    // the shape uploaded again with the next frame when they change.
+    pub(super) fn get_mut(&mut self, handle: ShapeHandle) -> Option<(&mut T, &mut i32, DirtyMark<'_>)> {
+        let index = self.index(handle)?;
+        Some((
+            &mut self.items[index],
+            &mut self.keys[index].z_index,
+            DirtyMark {
+                index,
+                changes: &mut self.changes,
+            },
+        ))
+    }
+
+    fn update_slots(&mut self, from: usize) {
+        for (index, &slot) in self.item_slots.iter().enumerate().skip(from) {
+            if slot != NO_SLOT {
+                self.slots[slot as usize].index = Some(index as u32);
+            }
+        }
+    }
+
+    // Removes the shape from the draw calls; its place is kept, so nothing
+    // has to be uploaded. The places are dropped when there are more of them
+    // than of the shapes, or when the shapes are at the end. Returns false
+    // when the shape was already removed.
+    pub(super) fn remove(&mut self, handle: ShapeHandle) -> bool {
+        let Some(index) = self.index(handle) else {
+            return false;
+        };
+        let slot = &mut self.slots[handle.slot as usize];
+        slot.index = None;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_slots.push(handle.slot);
+
+        self.item_slots[index] = NO_SLOT;
+        self.keys[index].removed = true;
+        self.removed += 1;
+        self.changes.changed = true;
+        while self.keys.last().is_some_and(|key| key.removed) {
+            self.items.pop();
+            self.keys.pop();
+            self.item_slots.pop();
+            self.removed -= 1;
+        }
+        if self.removed * 2 > self.items.len() {
+            self.compact();
+        }
+        true
+    }
+
+    // Drops the places of the removed shapes; the shapes after the first one
+    // move down and are uploaded again.
+    fn compact(&mut self) {
+        let Some(first) = self.keys.iter().position(|key| key.removed) else {
+            return;
+        };
+        let mut index = 0;
+        let keys = &self.keys;
+        self.items.retain(|_| {
+            index += 1;
+            !keys[index - 1].removed
+        });
+        self.keys.retain(|key| !key.removed);
+        self.item_slots.retain(|&slot| slot != NO_SLOT);
+        self.removed = 0;
+        self.update_slots(first);
+        self.mark_dirty(first..self.items.len());
+    }
+
+    // Moves the shapes whose z-index changed to their place in the order;
+    // the moved shapes are uploaded again.
+    pub(super) fn sort(&mut self) {
+        if self.keys.is_sorted() {
+            return;
+        }
+        let mut order: Vec<usize> = (0..self.items.len()).collect();
+        order.sort_by_key(|&index| self.keys[index]);
+        let first = order.iter().enumerate().position(|(index, &from)| index != from).unwrap();
+        let last = order.iter().enumerate().rposition(|(index, &from)| index != from).unwrap();
+
+        let mut items: Vec<Option<T>> = self.items.drain(..).map(Some).collect();
+        self.items = order.iter().map(|&from| items[from].take().unwrap()).collect();
+        self.keys = order.iter().map(|&from| self.keys[from]).collect();
+        self.item_slots = order.iter().map(|&from| self.item_slots[from]).collect();
+        self.update_slots(first);
+        self.mark_dirty(first..last + 1);
+    }
+
+    // Whether any shape was added, changed or removed since the last upload.
+    pub(super) fn is_changed(&self) -> bool {
+        self.changes.changed
+    }
+
+    fn mark_dirty(&mut self, range: Range<usize>) {
+        self.changes.mark_dirty(range);
+    }
+
+    // Everything has to be uploaded, e.g. to new buffers.
+    pub(super) fn mark_all_dirty(&mut self) {
+        self.mark_dirty(0..self.items.len());
+    }
+
+    // Whether any shape was added, changed or removed since the last call.
+    // All the shapes are uploaded again then, so the places of the removed
+    // ones are dropped first.
+    pub(super) fn take_changed(&mut self) -> bool {
+        if self.changes.changed {
+            self.compact();
+        }
+        mem::take(&mut self.changes).changed
+    }
+
+    // Range of the shapes changed since the last call; `None` when there is
+    // nothing to upload. Removed shapes are only dropped from the draw calls
+    // and need no upload.
+    pub(super) fn take_dirty(&mut self) -> Option<Range<usize>> {
+        let dirty = mem::take(&mut self.changes).dirty?;
+        let range = dirty.start..dirty.end.min(self.items.len());
+        (!range.is_empty()).then_some(range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handles_survive_removing_other_shapes() {
+        let mut shapes = Shapes::default();
+        let first = shapes.push(ShapeKind::Circle, 0, 0, 0, 'a');
+        let second = shapes.push(ShapeKind::Circle, 1, 0, 0, 'b');
+        let third = shapes.push(ShapeKind::Circle, 2, 0, 0, 'c');
+        assert_eq!(shapes.take_dirty(), Some(0..3));
+
+        // The place of the removed shape is kept, so nothing moves.
+        assert!(shapes.remove(first));
+        assert!(shapes.is_changed());
+        assert_eq!(shapes.items(), &['a', 'b', 'c']);
+        assert!(shapes.keys()[0].removed);
+        assert_eq!(shapes.take_dirty(), None);
+        let (item, _, mut mark) = shapes.get_mut(third).unwrap();
+        mark.set(item, 'C');
+        assert_eq!(shapes.items(), &['a', 'b', 'C']);
+        assert_eq!(shapes.take_dirty(), Some(2..3));
+        assert_eq!(shapes.get_mut(second).map(|(item, ..)| *item), Some('b'));
+    }
+
+    #[test]
+    fn unchanged_shapes_are_not_uploaded() {
+        let mut shapes = Shapes::default();
+        let handle = shapes.push(ShapeKind::Circle, 0, 0, 0, 'a');
+        shapes.take_dirty();
+        let (item, z_index, mut mark) = shapes.get_mut(handle).unwrap();
+        mark.set(item, 'a');
+        mark.set(z_index, 0);
+        assert!(!shapes.is_changed());
+        assert_eq!(shapes.take_dirty(), None);
+    }
+
+    #[test]
+    fn places_of_removed_shapes_are_dropped_when_most_are_empty() {
+        let mut shapes = Shapes::default();
+        let handles: Vec<_> = "abcd".chars().enumerate().map(|(index, item)| shapes.push(ShapeKind::Circle, index as u64, 0, 0, item)).collect();
+        shapes.take_dirty();
+        assert!(shapes.remove(handles[1]));
+        assert!(shapes.remove(handles[2]));
+        assert_eq!(shapes.items(), &['a', 'b', 'c', 'd']);
+        assert_eq!(shapes.take_dirty(), None);
+        assert!(shapes.remove(handles[0]));
+        assert_eq!(shapes.items(), &['d']);
+        assert_eq!(shapes.take_dirty(), Some(0..1));
+        assert_eq!(shapes.get_mut(handles[3]).map(|(item, ..)| *item), Some('d'));
+    }
+
+    #[test]
+    fn shapes_are_sorted_by_z_index() {
+        let mut shapes = Shapes::default();
+        let handles: Vec<_> = "abcd".chars().enumerate().map(|(index, item)| shapes.push(ShapeKind::Circle, index as u64, 0, 0, item)).collect();
+        *shapes.get_mut(handles[0]).unwrap().1 = 1;
+        shapes.take_dirty();
+        shapes.sort();
+        assert_eq!(shapes.items(), &['b', 'c', 'd', 'a']);
+        assert_eq!(shapes.take_dirty(), Some(0..4));
+
+        // Shapes added later go below the shapes with a higher z-index.
+        shapes.push(ShapeKind::Circle, 4, 0, 0, 'e');
+        assert_eq!(shapes.items(), &['b', 'c', 'd', 'e', 'a']);
+        *shapes.get_mut(handles[2]).unwrap().1 = -1;
+        shapes.sort();
+        assert_eq!(shapes.items(), &['c', 'b', 'd', 'e', 'a']);
+        assert_eq!(shapes.take_dirty(), Some(0..5));
+        assert!(shapes.remove(handles[0]));
+        assert!(shapes.remove(handles[2]));
+    }
+
+    #[test]
+    fn batches_follow_the_painters_order() {
+        let key = |z_index, sequence| DrawKey { z_index, sequence, clip: 0, group: 0, removed: false };
+        let circles = [key(0, 0), key(0, 1), key(0, 4), key(2, 5)];
+        let rectangles = [key(0, 2), key(0, 3), key(1, 6)];
+        let batch = |kind, z_index, shapes| Batch { kind, z_index, clip: 0, shapes };
+        assert_eq!(
+            batches(&[(ShapeKind::Circle, &circles), (ShapeKind::Rectangle, &rectangles), (ShapeKind::Path, &[])]),
+            vec![
+                batch(ShapeKind::Circle, 0, 0..2),
+                batch(ShapeKind::Rectangle, 0, 0..2),
+                batch(ShapeKind::Circle, 0, 2..3),
+                batch(ShapeKind::Rectangle, 1, 2..3),
+                batch(ShapeKind::Circle, 2, 3..4),
+            ]
+        );
+    }
+
+    #[test]
+    fn batches_are_split_by_z_index() {
+        let key = |z_index, sequence| DrawKey { z_index, sequence, clip: 0, group: 0, removed: false };
+        let circles = [key(0, 0), key(1, 1), key(1, 2)];
+        assert_eq!(
+            batches(&[(ShapeKind::Circle, &circles)]),
+            vec![
+                Batch { kind: ShapeKind::Circle, z_index: 0, clip: 0, shapes: 0..1 },
+                Batch { kind: ShapeKind::Circle, z_index: 1, clip: 0, shapes: 1..3 },
+            ]
+        );
+    }
+
+    #[test]
+    fn batches_are_split_by_clip() {
+        let key = |sequence, clip| DrawKey { z_index: 0, sequence, clip, group: 0, removed: false };
+        let circles = [key(0, 0), key(1, 1), key(2, 1), key(3, 0)];
+        assert_eq!(
+            batches(&[(ShapeKind::Circle, &circles)]),
+            vec![
+                Batch { kind: ShapeKind::Circle, z_index: 0, clip: 0, shapes: 0..1 },
+                Batch { kind: ShapeKind::Circle, z_index: 0, clip: 1, shapes: 1..3 },
+                Batch { kind: ShapeKind::Circle, z_index: 0, clip: 0, shapes: 3..4 },
+            ]
+        );
+    }
+
+    #[test]
+    fn removed_shapes_split_the_batches() {
+        let key = |sequence, removed| DrawKey { z_index: 0, sequence, clip: 0, group: 0, removed };
+        let circles = [key(0, false), key(1, true), key(2, false), key(3, true)];
+        assert_eq!(
+            batches(&[(ShapeKind::Circle, &circles)]),
+            vec![
+                Batch { kind: ShapeKind::Circle, z_index: 0, clip: 0, shapes: 0..1 },
+                Batch { kind: ShapeKind::Circle, z_index: 0, clip: 0, shapes: 2..3 },
+            ]
+        );
+    }
+
+    #[test]
+    fn z_indices_with_a_layer_are_drawn_apart() {
+        let batch = |kind, z_index| Batch { kind, z_index, clip: 0, shapes: 0..1 };
+        let batches = [
+            batch(ShapeKind::Circle, 0),
+            batch(ShapeKind::Rectangle, 1),
+            batch(ShapeKind::Circle, 1),
+            batch(ShapeKind::Circle, 2),
+            batch(ShapeKind::Line, 3),
+            batch(ShapeKind::Path, 4),
+        ];
+        let opacity = |z_index| match z_index {
+            1 => Some(0.5),
+            2 => Some(0.25),
+            _ => None,
+        };
+        assert_eq!(
+            layers(&batches, opacity),
+            vec![(0..1, None), (1..3, Some(0.5)), (3..4, Some(0.25)), (4..6, None)]
+        );
+        assert_eq!(layers(&batches, |_| None::<f32>), vec![(0..6, None)]);
+    }
+
+    #[test]
+    fn removed_handles_are_stale() {
+        let mut shapes = Shapes::default();
+        let removed = shapes.push(ShapeKind::Rectangle, 0, 0, 0, 1);
+        shapes.remove(removed);
+        // The new shape reuses the slot of the removed one.
+        let added = shapes.push(ShapeKind::Rectangle, 1, 0, 0, 2);
+        assert_ne!(removed, added);
+        assert!(shapes.get_mut(removed).is_none());
+        assert!(!shapes.remove(removed));
+        assert_eq!(shapes.items(), &[2]);
+    }
+
+    #[test]
+    fn removing_the_last_shape_uploads_nothing() {
+        let mut shapes = Shapes::default();
+        shapes.push(ShapeKind::Ellipse, 0, 0, 0, 1);
+        let last = shapes.push(ShapeKind::Ellipse, 1, 0, 0, 2);
+        shapes.take_dirty();
+        shapes.remove(last);
+        assert_eq!(shapes.take_dirty(), None);
+        assert_eq!(shapes.items(), &[1]);
+    }
+}
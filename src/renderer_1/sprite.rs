@@ -0,0 +1,425 @@
+use super::group::{GroupState, Grouped};
+use super::scene::{DirtyMark, Shapes};
+use super::{
+    create_pipeline, create_shader, upload_changed, upload_instances, ShapeHandle, Vertex, RECTANGLE_INDICES, RECTANGLE_VERTICES,
+};
+use crate::atlas::AtlasRegion;
+use crate::color::Color;
+use crate::math::{Affine2, Vector2};
+use crate::raw::{Gpu, Raw};
+use crate::render_common::AntiAliasing;
+use crate::render_target::RenderTarget;
+use crate::texture::Texture;
+use image::{DynamicImage, RgbaImage};
+use std::mem;
+use std::ops::Range;
+use std::sync::Arc;
+use wgpu::util::DeviceExt;
+use wgpu::BindGroup;
+
+// Refers to an image added to a `Renderer1` by `load_image` or `add_image`;
+// images are never removed, so the handle stays valid in that renderer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureHandle(pub(super) u32);
+
+// Image packed in an atlas, see `Renderer1::add_atlas`: the texture of its
+// page and the x, y, width and height of its region in the page.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtlasImage {
+    pub texture: TextureHandle,
+    pub source: [f32; 4],
+}
+
+// Instance of a textured quad.
+#[derive(Debug, Clone, PartialEq)]
+#[repr(C, packed)]
+pub(super) struct Sprite {
+    pos: Vector2<f32>, // Center position
+    // Half of the width and the height.
+    size: Vector2<f32>,
+    // Counter clockwise in radians.
+    rotation: f32,
+    // Multiplies the colors of the image.
+    tint: Color,
+    // X, y, width and height of the part of the image drawn, in its pixels
+    // from its top left corner.
+    source: [f32; 4],
+    opacity: f32,
+    transform: Affine2,
+    // Index of the texture; it isn't an attribute, the sprites are drawn with
+    // a draw call for every texture.
+    texture: u32,
+}
+
+impl Gpu for Sprite {}
+
+impl Grouped for Sprite {
+    fn in_group(&self, group: &GroupState) -> Self {
+        Sprite {
+            opacity: self.opacity * group.opacity,
+            transform: group.transform * self.transform,
+            ..self.clone()
+        }
+    }
+}
+
+impl Sprite {
+    // The whole image of the size given by the texture in the rectangle.
+    pub(super) fn new(texture: TextureHandle, image_size: (u32, u32), pos: Vector2<f32>, size: Vector2<f32>) -> Self {
+        Sprite {
+            pos,
+            size,
+            rotation: 0.0,
+            tint: Color::WHITE,
+            source: [0.0, 0.0, image_size.0 as f32, image_size.1 as f32],
+            opacity: 1.0,
+            transform: Affine2::IDENTITY,
+            texture: texture.0,
+        }
+    }
+
+    // Glyph of the text atlas in the region of its page; the top left corner
+    // is in the coordinates of the text, the y axis up.
+    pub(super) fn glyph(
+        region: AtlasRegion,
+        top_left: Vector2<f32>,
+        color: Color,
+        opacity: f32,
+        transform: Affine2,
+    ) -> Self {
+        let size = Vector2 {
+            x: region.width as f32 / 2.0,
+            y: region.height as f32 / 2.0,
+        };
+        Sprite {
+            pos: Vector2 {
+                x: top_left.x + size.x,
+                y: top_left.y - size.y,
+            },
+            size,
+            rotation: 0.0,
+            tint: color,
+            source: [region.x, region.y, region.width, region.height].map(|value| value as f32),
+            opacity,
+            transform,
+            texture: region.page as u32,
+        }
+    }
+
+    fn buffer_description<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Sprite>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: mem::offset_of!(Sprite, pos) as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::offset_of!(Sprite, size) as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::offset_of!(Sprite, rotation) as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::offset_of!(Sprite, tint) as wgpu::BufferAddress,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::offset_of!(Sprite, source) as wgpu::BufferAddress,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::offset_of!(Sprite, opacity) as wgpu::BufferAddress,
+                    shader_location: 7,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(Sprite, transform) + mem::offset_of!(Affine2, x_axis))
+                        as wgpu::BufferAddress,
+                    shader_location: 8,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(Sprite, transform) + mem::offset_of!(Affine2, y_axis))
+                        as wgpu::BufferAddress,
+                    shader_location: 9,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: (mem::offset_of!(Sprite, transform) + mem::offset_of!(Affine2, translation))
+                        as wgpu::BufferAddress,
+                    shader_location: 10,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+            ],
+        }
+    }
+}
+
+// Returned by `Renderer1::image` to set up the optional properties of the
+// image which was just added, or by `edit_image` to change them later.
+pub struct ImageBuilder<'a> {
+    pub(super) sprite: &'a mut Sprite,
+    pub(super) z_index: &'a mut i32,
+    pub(super) dirty: DirtyMark<'a>,
+    pub(super) handle: ShapeHandle,
+}
+
+impl ImageBuilder<'_> {
+    // Counter clockwise rotation around the center in radians.
+    pub fn rotation(mut self, rotation: f32) -> Self {
+        self.dirty.update(self.sprite, |sprite| sprite.rotation = rotation);
+        self
+    }
+
+    // Multiplies the colors of the image; white keeps them.
+    pub fn tint(mut self, color: Color) -> Self {
+        self.dirty.update(self.sprite, |sprite| sprite.tint = color);
+        self
+    }
+
+    // Draws only the part of the image, given in its pixels from its top left
+    // corner, stretched over the rectangle.
+    pub fn source_rect(mut self, x: f32, y: f32, width: f32, height: f32) -> Self {
+        self.dirty.update(self.sprite, |sprite| sprite.source = [x, y, width, height]);
+        self
+    }
+
+    // Fades the image, see `ShapeBuilder::opacity`.
+    pub fn opacity(mut self, opacity: f32) -> Self {
+        self.dirty.update(self.sprite, |sprite| sprite.opacity = opacity.clamp(0.0, 1.0));
+        self
+    }
+
+    // See `ShapeBuilder::transform`.
+    pub fn transform(mut self, transform: Affine2) -> Self {
+        self.dirty.update(self.sprite, |sprite| sprite.transform = transform);
+        self
+    }
+
+    // Images are ordered with the other shapes by the z-index, see
+    // `ShapeBuilder::z_index`.
+    pub fn z_index(mut self, z_index: i32) -> Self {
+        self.dirty.set(self.z_index, z_index);
+        self
+    }
+
+    // Handle to change or remove the image later.
+    pub fn handle(self) -> ShapeHandle {
+        self.handle
+    }
+}
+
+// Images on the GPU; every texture is bound as the bind group 3 of the sprite
+// pipeline for the sprites drawn from it. The glyphs of the texts are drawn by
+// the same pipeline from the pages of the glyph atlas.
+pub(super) struct PreparedSprites {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    // Indexed like the images of the scene; they are only added.
+    textures: Vec<(Texture, wgpu::BindGroup)>,
+    // Replaced when the glyph atlas is packed again.
+    glyph_pages: Vec<(Texture, wgpu::BindGroup)>,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    instances_buffer: wgpu::Buffer,
+    glyph_instances_buffer: wgpu::Buffer,
+}
+
+impl PreparedSprites {
+    // The layouts are the bind groups shared with the other shapes.
+    pub(super) fn new(
+        target: &dyn RenderTarget,
+        bind_group_layouts: [&wgpu::BindGroupLayout; 3],
+        anti_aliasing: AntiAliasing,
+    ) -> Self {
+        let bind_group_layout = target
+            .device()
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Image Bind Group Layout"),
+                entries: &[
+                    // The size of the texture is read by the vertex shader.
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
+        let [projection, table, clip] = bind_group_layouts;
+        let layout = target
+            .device()
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Image Pipeline Layout"),
+                bind_group_layouts: &[projection, table, clip, &bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let shader = create_shader(
+            target,
+            "Image Shader",
+            include_str!("../shaders/renderer_1_image.wgsl"),
+            anti_aliasing,
+        );
+        let pipeline = create_pipeline(
+            target,
+            "Image Render Pipeline",
+            &layout,
+            &shader,
+            &[Vertex::buffer_description(), Sprite::buffer_description()],
+            anti_aliasing,
+            wgpu::StencilState::default(),
+        );
+
+        PreparedSprites {
+            pipeline,
+            bind_group_layout,
+            textures: Vec::new(),
+            glyph_pages: Vec::new(),
+            vertex_buffer: target
+                .device()
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Image Vertex Buffer"),
+                    contents: RECTANGLE_VERTICES.get_raw(),
+                    usage: wgpu::BufferUsages::VERTEX,
+                }),
+            index_buffer: target
+                .device()
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Image Index Buffer"),
+                    contents: RECTANGLE_INDICES.get_raw(),
+                    usage: wgpu::BufferUsages::INDEX,
+                }),
+            instances_buffer: target.device().create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Image Instances Buffer"),
+                size: 100000 * wgpu::COPY_BUFFER_ALIGNMENT,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            glyph_instances_buffer: target.device().create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Glyph Instances Buffer"),
+                size: 100000 * wgpu::COPY_BUFFER_ALIGNMENT,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+        }
+    }
+
+    // Texture of the image with its bind group.
+    fn create_texture(&self, target: &dyn RenderTarget, image: &DynamicImage, label: &str) -> (Texture, BindGroup) {
+        let texture = Texture::from_image(target.device(), target.queue(), image, Some(label)).unwrap();
+        let bind_group = target.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+            label: Some("Image Bind Group"),
+        });
+        (texture, bind_group)
+    }
+
+    // Creates the textures of the images added since the last upload and
+    // uploads the changed sprites.
+    pub(super) fn upload(
+        &mut self,
+        target: &dyn RenderTarget,
+        images: &[Arc<DynamicImage>],
+        sprites: &mut Shapes<Sprite>,
+        groups: &[GroupState],
+    ) {
+        for image in &images[self.textures.len()..] {
+            let texture = self.create_texture(target, image, "Image Texture");
+            self.textures.push(texture);
+        }
+        upload_changed(target, &mut self.instances_buffer, sprites, groups, "image");
+    }
+
+    // Replaces the glyph pages when the atlas was packed again and uploads
+    // all the glyph sprites.
+    pub(super) fn upload_glyphs(&mut self, target: &dyn RenderTarget, pages: Option<Vec<RgbaImage>>, glyphs: &[Sprite]) {
+        if let Some(pages) = pages {
+            self.glyph_pages = pages
+                .into_iter()
+                .map(|page| self.create_texture(target, &DynamicImage::ImageRgba8(page), "Glyph Texture"))
+                .collect();
+        }
+        upload_instances(target, &mut self.glyph_instances_buffer, 0, glyphs.get_raw(), "glyph");
+    }
+
+    // Draws the image sprites with the indices in the range.
+    pub(super) fn render<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        perspective_bind_group: &'a BindGroup,
+        sprites: &[Sprite],
+        range: Range<usize>,
+    ) {
+        let textures = &self.textures;
+        self.draw(render_pass, perspective_bind_group, &self.instances_buffer, textures, sprites, range);
+    }
+
+    // Draws the glyph sprites with the indices in the range.
+    pub(super) fn render_glyphs<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        perspective_bind_group: &'a BindGroup,
+        glyphs: &[Sprite],
+        range: Range<usize>,
+    ) {
+        let pages = &self.glyph_pages;
+        self.draw(render_pass, perspective_bind_group, &self.glyph_instances_buffer, pages, glyphs, range);
+    }
+
+    // The sprites following each other with the same texture are drawn at
+    // once.
+    fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        perspective_bind_group: &'a BindGroup,
+        instances_buffer: &'a wgpu::Buffer,
+        textures: &'a [(Texture, BindGroup)],
+        sprites: &[Sprite],
+        range: Range<usize>,
+    ) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, perspective_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, instances_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        let mut start = range.start;
+        while start < range.end {
+            let texture = sprites[start].texture;
+            let end = (start..range.end)
+                .find(|&index| sprites[index].texture != texture)
+                .unwrap_or(range.end);
+            render_pass.set_bind_group(3, &textures[texture as usize].1, &[]);
+            render_pass.draw_indexed(0..(RECTANGLE_INDICES.len() as u32), 0, start as u32..end as u32);
+            start = end;
+        }
+    }
+}
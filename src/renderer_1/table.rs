@@ -0,0 +1,307 @@
+use super::dash::{Dashes, DASH_ROW_WIDTH};
+use super::gradient::{Gradients, GRADIENT_ROW_WIDTH};
+use crate::raw::Raw;
+use crate::render_target::RenderTarget;
+use log::warn;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+// Size of the table textures every device supports, see
+// `wgpu::Limits::downlevel_defaults`. The rows are wrapped into lines of the
+// texture this wide, as many lines as fit high.
+const MAX_TABLE_SIZE: u32 = 2048;
+
+// Entry of a table; entries with the same key share a row.
+pub(super) trait TableEntry: Clone {
+    type Key: Hash + Eq;
+
+    fn key(&self) -> Self::Key;
+}
+
+// Entries of a table in the rows of the texture. The shapes refer to them by
+// the index plus one, 0 means no entry. The same entry added again reuses its
+// row; rows no shape refers to are freed by `Renderer1Prepared::upload` after
+// the shapes changed, and taken by the next new entries.
+#[derive(Debug, Clone)]
+pub(super) struct Rows<T: TableEntry> {
+    rows: Vec<Option<T>>,
+    // Rows of the entries by their keys.
+    index: HashMap<T::Key, usize>,
+    free_rows: Vec<usize>,
+    // Whether any row was added or freed since the last upload.
+    changed: bool,
+}
+
+impl<T: TableEntry> Default for Rows<T> {
+    fn default() -> Self {
+        Rows {
+            rows: Vec::new(),
+            index: HashMap::new(),
+            free_rows: Vec::new(),
+            changed: false,
+        }
+    }
+}
+
+impl<T: TableEntry> Rows<T> {
+    // 0 when the table with rows of the width is full; `name` is the kind of
+    // the entries for the warning.
+    pub(super) fn add(&mut self, entry: &T, width: u32, name: &str) -> u32 {
+        let key = entry.key();
+        if let Some(&index) = self.index.get(&key) {
+            return index as u32 + 1;
+        }
+        let index = match self.free_rows.pop() {
+            Some(index) => index,
+            None if self.rows.len() < table_capacity(width) => {
+                self.rows.push(None);
+                self.rows.len() - 1
+            }
+            None => {
+                warn!("table of the {name}s is full; the {name} isn't drawn");
+                return 0;
+            }
+        };
+        self.rows[index] = Some(entry.clone());
+        self.index.insert(key, index);
+        self.changed = true;
+        index as u32 + 1
+    }
+
+    // Frees the rows not marked in `used`, which is indexed like the shapes
+    // refer to the rows.
+    pub(super) fn free_unused(&mut self, used: &[bool]) {
+        for (index, row) in self.rows.iter_mut().enumerate() {
+            if !used[index + 1] {
+                if let Some(entry) = row.take() {
+                    self.index.remove(&entry.key());
+                    self.free_rows.push(index);
+                    self.changed = true;
+                }
+            }
+        }
+    }
+
+    // Includes the freed rows.
+    pub(super) fn rows(&self) -> &[Option<T>] {
+        &self.rows
+    }
+
+    pub(super) fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+}
+
+// Number of the rows of the width which fit a table texture.
+fn table_capacity(width: u32) -> usize {
+    (rows_per_line(width) * MAX_TABLE_SIZE) as usize
+}
+
+fn rows_per_line(width: u32) -> u32 {
+    MAX_TABLE_SIZE / width
+}
+
+// Gradients and dash patterns on the GPU, bound as the bind group 1 of the
+// shape pipelines. Every table is a texture with a row per entry.
+pub(super) struct PreparedTables {
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+}
+
+impl PreparedTables {
+    pub(super) fn bind_group_layout(target: &dyn RenderTarget) -> wgpu::BindGroupLayout {
+        let entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        target
+            .device()
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Table Bind Group Layout"),
+                entries: &[entry(0), entry(1)],
+            })
+    }
+
+    pub(super) fn new(target: &dyn RenderTarget, bind_group_layout: wgpu::BindGroupLayout) -> Self {
+        let bind_group = create_bind_group(target, &bind_group_layout, &Gradients::default(), &Dashes::default());
+        PreparedTables {
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    pub(super) fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    // The textures are created again when any entry was added or freed.
+    pub(super) fn upload(&mut self, target: &dyn RenderTarget, gradients: &mut Gradients, dashes: &mut Dashes) {
+        // Both are taken so neither change is left for the next upload.
+        let gradients_changed = gradients.take_changed();
+        if dashes.take_changed() || gradients_changed {
+            self.bind_group = create_bind_group(target, &self.bind_group_layout, gradients, dashes);
+        }
+    }
+}
+
+// Texels of the rows of the width in the layout of the texture, the freed rows
+// and the rest of the last line empty; the row texels are given by `texels`.
+pub(super) fn table_texels<T>(rows: &[Option<T>], width: u32, mut texels: impl FnMut(&T, &mut Vec<f32>)) -> Vec<f32> {
+    let row_len = width as usize * 4;
+    let rows_per_line = rows_per_line(width) as usize;
+    let mut table = Vec::with_capacity(rows.len().div_ceil(rows_per_line) * rows_per_line * row_len);
+    for row in rows {
+        let start = table.len();
+        if let Some(row) = row {
+            texels(row, &mut table);
+        }
+        table.resize(start + row_len, 0.0);
+    }
+    table.resize(table.len().div_ceil(rows_per_line * row_len) * rows_per_line * row_len, 0.0);
+    table
+}
+
+// The rows are wrapped into lines of the texture, see `MAX_TABLE_SIZE`; the
+// texture has at least one line so it can be bound without entries.
+fn create_table(target: &dyn RenderTarget, label: &str, width: u32, rows: usize, texels: &[f32]) -> wgpu::TextureView {
+    let rows_per_line = rows_per_line(width);
+    let size = wgpu::Extent3d {
+        width: rows_per_line * width,
+        height: (rows as u32).div_ceil(rows_per_line).max(1),
+        depth_or_array_layers: 1,
+    };
+    let texture = target.device().create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba32Float,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    if rows != 0 {
+        target.queue().write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            texels.get_raw(),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(size.width * 16),
+                rows_per_image: Some(size.height),
+            },
+            size,
+        );
+    }
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+fn create_bind_group(
+    target: &dyn RenderTarget,
+    layout: &wgpu::BindGroupLayout,
+    gradients: &Gradients,
+    dashes: &Dashes,
+) -> wgpu::BindGroup {
+    let gradient_view = create_table(
+        target,
+        "Gradient Texture",
+        GRADIENT_ROW_WIDTH,
+        gradients.len(),
+        &gradients.texels(),
+    );
+    let dash_view = create_table(target, "Dash Texture", DASH_ROW_WIDTH, dashes.len(), &dashes.texels());
+    target.device().create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&gradient_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(&dash_view),
+            },
+        ],
+        label: Some("Table Bind Group"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl TableEntry for char {
+        type Key = char;
+
+        fn key(&self) -> char {
+            *self
+        }
+    }
+
+    impl TableEntry for u32 {
+        type Key = u32;
+
+        fn key(&self) -> u32 {
+            *self
+        }
+    }
+
+    #[test]
+    fn unused_rows_are_freed_and_reused() {
+        let mut rows = Rows::default();
+        assert_eq!(rows.add(&'a', 1, "letter"), 1);
+        assert_eq!(rows.add(&'b', 1, "letter"), 2);
+        assert_eq!(rows.add(&'a', 1, "letter"), 1);
+        assert!(rows.take_changed());
+
+        rows.free_unused(&[true, false, true]);
+        assert_eq!(rows.rows(), &[None, Some('b')]);
+        assert!(rows.take_changed());
+        assert_eq!(rows.add(&'c', 1, "letter"), 1);
+        assert_eq!(rows.add(&'d', 1, "letter"), 3);
+        assert_eq!(rows.rows(), &[Some('c'), Some('b'), Some('d')]);
+    }
+
+    #[test]
+    fn entries_added_again_change_nothing() {
+        let mut rows = Rows::default();
+        assert_eq!(rows.add(&'a', 1, "letter"), 1);
+        assert!(rows.take_changed());
+        assert_eq!(rows.add(&'a', 1, "letter"), 1);
+        assert!(!rows.take_changed());
+        assert_eq!(rows.rows(), &[Some('a')]);
+    }
+
+    #[test]
+    fn full_tables_add_no_rows() {
+        let mut rows = Rows::default();
+        let width = MAX_TABLE_SIZE / 2 + 1;
+        for row in 0..MAX_TABLE_SIZE {
+            assert_eq!(rows.add(&row, width, "number"), row + 1);
+        }
+        assert_eq!(rows.add(&MAX_TABLE_SIZE, width, "number"), 0);
+        let mut used = vec![true; MAX_TABLE_SIZE as usize + 1];
+        used[1] = false;
+        rows.free_unused(&used);
+        assert_eq!(rows.add(&MAX_TABLE_SIZE, width, "number"), 1);
+    }
+
+    #[test]
+    fn rows_are_wrapped_into_lines() {
+        let width = MAX_TABLE_SIZE / 2;
+        let texels = table_texels(&[Some(1.0), None, Some(3.0)], width, |&row, texels| texels.push(row));
+        let row = |index: usize| texels[index * width as usize * 4];
+        assert_eq!(texels.len(), 4 * width as usize * 4);
+        assert_eq!((row(0), row(1), row(2), row(3)), (1.0, 0.0, 3.0, 0.0));
+    }
+}
@@ -0,0 +1,328 @@
+use super::group::{GroupState, Grouped};
+use super::sprite::Sprite;
+use super::scene::DirtyMark;
+use super::ShapeHandle;
+use crate::atlas::{AtlasBuilder, AtlasRegion};
+use crate::color::Color;
+use crate::math::{Affine2, Vector2};
+use ab_glyph::{Font, FontArc, GlyphId, PxScale, ScaleFont};
+use image::{DynamicImage, RgbaImage};
+use std::collections::HashMap;
+use std::ops::Range;
+
+// Pages of the glyph atlas are at most this many pixels wide and high.
+const GLYPH_PAGE_SIZE: u32 = 1024;
+
+// Refers to a font loaded to a `Renderer1` by `load_font`; fonts are never
+// removed, so the handle stays valid in that renderer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FontHandle(pub(super) u32);
+
+// Where the lines of a text are placed relative to its position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextAlign {
+    // The lines start at the position.
+    #[default]
+    Left,
+    Center,
+    // The lines end at the position.
+    Right,
+}
+
+// Text as it was added to the renderer; it is laid out to the glyph sprites
+// when the renderer is prepared.
+#[derive(Debug, Clone)]
+pub(super) struct TextShape {
+    font: u32,
+    text: String,
+    // Height of the font in pixels; the glyphs are rasterized at the size
+    // rounded to whole pixels.
+    size: f32,
+    // Start of the baseline of the first line.
+    pos: Vector2<f32>,
+    color: Color,
+    align: TextAlign,
+    opacity: f32,
+    transform: Affine2,
+}
+
+impl TextShape {
+    pub(super) fn new(font: FontHandle, text: &str, size: f32, pos: Vector2<f32>) -> Self {
+        TextShape {
+            font: font.0,
+            text: text.to_string(),
+            size,
+            pos,
+            color: Color::YELLOW,
+            align: TextAlign::Left,
+            opacity: 1.0,
+            transform: Affine2::IDENTITY,
+        }
+    }
+}
+
+impl Grouped for TextShape {
+    fn in_group(&self, group: &GroupState) -> Self {
+        TextShape {
+            opacity: self.opacity * group.opacity,
+            transform: group.transform * self.transform,
+            ..self.clone()
+        }
+    }
+}
+
+// Returned by `Renderer1::text` to set up the optional properties of the text
+// which was just added, or by `edit_text` to change them later.
+pub struct TextBuilder<'a> {
+    pub(super) shape: &'a mut TextShape,
+    pub(super) z_index: &'a mut i32,
+    pub(super) dirty: DirtyMark<'a>,
+    pub(super) handle: ShapeHandle,
+}
+
+impl TextBuilder<'_> {
+    pub fn color(mut self, color: Color) -> Self {
+        self.dirty.set(&mut self.shape.color, color);
+        self
+    }
+
+    pub fn align(mut self, align: TextAlign) -> Self {
+        self.dirty.set(&mut self.shape.align, align);
+        self
+    }
+
+    // Replaces the string; the glyphs which weren't drawn yet are rasterized
+    // with the next frame.
+    pub fn text(mut self, text: &str) -> Self {
+        if self.shape.text != text {
+            self.dirty.set(&mut self.shape.text, text.to_string());
+        }
+        self
+    }
+
+    // Fades the text, see `ShapeBuilder::opacity`.
+    pub fn opacity(mut self, opacity: f32) -> Self {
+        self.dirty.set(&mut self.shape.opacity, opacity.clamp(0.0, 1.0));
+        self
+    }
+
+    // Transforms the text after it is laid out; the glyphs are rasterized at
+    // the size of the font, so they get blurry when the transform scales them
+    // up.
+    pub fn transform(mut self, transform: Affine2) -> Self {
+        self.dirty.set(&mut self.shape.transform, transform);
+        self
+    }
+
+    // Texts are ordered with the other shapes by the z-index, see
+    // `ShapeBuilder::z_index`.
+    pub fn z_index(mut self, z_index: i32) -> Self {
+        self.dirty.set(self.z_index, z_index);
+        self
+    }
+
+    // Handle to change or remove the text later.
+    pub fn handle(self) -> ShapeHandle {
+        self.handle
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct GlyphKey {
+    font: u32,
+    glyph: u16,
+    // In whole pixels.
+    size: u32,
+}
+
+// Coverage of a glyph as the alpha of white pixels.
+#[derive(Debug, Clone)]
+struct RasterizedGlyph {
+    image: DynamicImage,
+    // Top left corner of the image from the pen position on the baseline, in
+    // pixels with the y axis down.
+    offset: Vector2<f32>,
+}
+
+// Glyphs of all the texts rasterized to images packed in an atlas. Glyphs are
+// only added; the atlas is packed again with all of them when there are new
+// ones.
+#[derive(Debug, Default)]
+pub(super) struct GlyphAtlas {
+    // Index of the rasterized glyph; `None` for the glyphs without an
+    // outline, e.g. spaces.
+    keys: HashMap<GlyphKey, Option<usize>>,
+    glyphs: Vec<RasterizedGlyph>,
+    // Regions of the glyphs in the pages.
+    regions: Vec<AtlasRegion>,
+    // Pages packed since the last `take_pages`.
+    pages: Option<Vec<RgbaImage>>,
+}
+
+impl GlyphAtlas {
+    // Appends the sprites of the glyphs of every text with the range of its
+    // sprites; rasterizes the glyphs which weren't drawn before.
+    pub(super) fn sprites(
+        &mut self,
+        texts: &[TextShape],
+        fonts: &[FontArc],
+        sprites: &mut Vec<Sprite>,
+        ranges: &mut Vec<Range<u32>>,
+    ) {
+        let layouts: Vec<_> = texts.iter().map(|text| self.layout(text, &fonts[text.font as usize])).collect();
+        if self.regions.len() != self.glyphs.len() {
+            self.pack();
+        }
+        for (text, layout) in texts.iter().zip(layouts) {
+            let start = sprites.len() as u32;
+            for (glyph, top_left) in layout {
+                sprites.push(Sprite::glyph(
+                    self.regions[glyph],
+                    top_left,
+                    text.color,
+                    text.opacity,
+                    text.transform,
+                ));
+            }
+            ranges.push(start..sprites.len() as u32);
+        }
+    }
+
+    // Pages packed since the last call, to be uploaded; `None` when the
+    // atlas didn't change.
+    pub(super) fn take_pages(&mut self) -> Option<Vec<RgbaImage>> {
+        self.pages.take()
+    }
+
+    // Glyphs of the text with the top left corners of their images, in the
+    // coordinates of the text with the y axis up.
+    fn layout(&mut self, text: &TextShape, font: &FontArc) -> Vec<(usize, Vector2<f32>)> {
+        let size = text.size.round().max(1.0);
+        let scaled = font.as_scaled(PxScale::from(size));
+        let line_height = scaled.height() + scaled.line_gap();
+        let mut glyphs = Vec::new();
+        for (line_index, line) in text.text.lines().enumerate() {
+            // Pen positions of the glyphs along the line.
+            let mut pen = 0.0;
+            let mut previous = None;
+            let mut positions = Vec::new();
+            for c in line.chars() {
+                let id = scaled.glyph_id(c);
+                if let Some(previous) = previous {
+                    pen += scaled.kern(previous, id);
+                }
+                positions.push((id, pen));
+                pen += scaled.h_advance(id);
+                previous = Some(id);
+            }
+            let start = match text.align {
+                TextAlign::Left => 0.0,
+                TextAlign::Center => -pen / 2.0,
+                TextAlign::Right => -pen,
+            };
+            let baseline = text.pos.y - line_index as f32 * line_height;
+            for (id, pen) in positions {
+                let key = GlyphKey {
+                    font: text.font,
+                    glyph: id.0,
+                    size: size as u32,
+                };
+                let Some(glyph) = self.glyph(key, font) else {
+                    continue;
+                };
+                // The glyphs are rasterized on whole pixels.
+                let offset = self.glyphs[glyph].offset;
+                let x = (text.pos.x + start + pen).round() + offset.x;
+                let y = baseline.round() - offset.y;
+                glyphs.push((glyph, Vector2 { x, y }));
+            }
+        }
+        glyphs
+    }
+
+    // Index of the glyph, rasterized when it is drawn for the first time.
+    fn glyph(&mut self, key: GlyphKey, font: &FontArc) -> Option<usize> {
+        if let Some(&glyph) = self.keys.get(&key) {
+            return glyph;
+        }
+        let glyph = GlyphId(key.glyph).with_scale(PxScale::from(key.size as f32));
+        let rasterized = font.outline_glyph(glyph).map(|outlined| {
+            let bounds = outlined.px_bounds();
+            let mut image = RgbaImage::new(bounds.width() as u32, bounds.height() as u32);
+            outlined.draw(|x, y, coverage| {
+                let alpha = (coverage.clamp(0.0, 1.0) * 255.0).round() as u8;
+                image.put_pixel(x, y, image::Rgba([255, 255, 255, alpha]));
+            });
+            self.glyphs.push(RasterizedGlyph {
+                image: DynamicImage::ImageRgba8(image),
+                offset: Vector2 {
+                    x: bounds.min.x,
+                    y: bounds.min.y,
+                },
+            });
+            self.glyphs.len() - 1
+        });
+        self.keys.insert(key, rasterized);
+        rasterized
+    }
+
+    fn pack(&mut self) {
+        let mut builder = AtlasBuilder::new(GLYPH_PAGE_SIZE);
+        for glyph in &self.glyphs {
+            builder.add(&glyph.image);
+        }
+        let atlas = builder.build();
+        self.regions = atlas.regions;
+        self.pages = Some(atlas.pages);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn font() -> FontArc {
+        FontArc::try_from_slice(include_bytes!("../../tests/fonts/DejaVuSansMono.ttf")).unwrap()
+    }
+
+    // Left and right edges of the glyph images of the text.
+    fn extent(atlas: &mut GlyphAtlas, text: &TextShape, font: &FontArc) -> (f32, f32) {
+        let layout = atlas.layout(text, font);
+        let left = layout.iter().map(|(_, top_left)| top_left.x).fold(f32::MAX, f32::min);
+        let right = layout
+            .iter()
+            .map(|&(glyph, top_left)| top_left.x + atlas.glyphs[glyph].image.width() as f32)
+            .fold(f32::MIN, f32::max);
+        (left, right)
+    }
+
+    #[test]
+    fn glyphs_are_rasterized_once_and_aligned() {
+        let font = font();
+        let mut atlas = GlyphAtlas::default();
+        let mut text = TextShape::new(FontHandle(0), "o o", 20.0, Vector2 { x: 50.0, y: 10.0 });
+        let (left, right) = extent(&mut atlas, &text, &font);
+        // The space has no outline and the second 'o' is the first one.
+        assert_eq!(atlas.glyphs.len(), 1);
+        assert_eq!(atlas.layout(&text, &font).len(), 2);
+
+        text.align = TextAlign::Right;
+        let (right_left, right_right) = extent(&mut atlas, &text, &font);
+        assert!(right_right <= 50.0 && right_right > 45.0);
+        assert_eq!(right_right - right_left, right - left);
+        text.align = TextAlign::Center;
+        let (center_left, center_right) = extent(&mut atlas, &text, &font);
+        assert!((center_left - 50.0 + center_right - 50.0).abs() <= 2.0);
+        assert_eq!(atlas.glyphs.len(), 1);
+    }
+
+    #[test]
+    fn glyphs_are_placed_on_whole_pixels() {
+        let font = font();
+        let mut atlas = GlyphAtlas::default();
+        let text = TextShape::new(FontHandle(0), "ab\ncd", 13.0, Vector2 { x: 10.3, y: 40.6 });
+        for (_, top_left) in atlas.layout(&text, &font) {
+            assert_eq!(top_left, Vector2 { x: top_left.x.round(), y: top_left.y.round() });
+        }
+    }
+}
@@ -1,3 +1,16 @@
+// `ANTI_ALIASING` constant and the functions of renderer_1_gradient.wgsl,
+// renderer_1_dash.wgsl and renderer_1_clip.wgsl are prepended by renderer_1.rs.
+
+// Has to be kept in sync with the `FillMode` flags in renderer_1.rs.
+const STROKE_FLAG: u32 = 1u;
+const FILL_FLAG: u32 = 2u;
+
+// The quad is this many pixels bigger than the shape so there is space for
+// the anti aliased edge.
+const AA_MARGIN: f32 = 1.0;
+
+const TAU: f32 = 6.28318531;
+
 // Vertex shader
 
 @group(0) @binding(0)
@@ -11,12 +24,41 @@ struct InstanceInput {
     @location(2) position: vec2<f32>,
     @location(3) radius: f32,
     @location(4) brush_size: f32,
+    @location(5) stroke_color: vec4<f32>,
+    @location(6) fill_color: vec4<f32>,
+    @location(7) fill_mode: u32,
+    @location(8) transform_x_axis: vec2<f32>,
+    @location(9) transform_y_axis: vec2<f32>,
+    @location(10) transform_translation: vec2<f32>,
+    @location(11) opacity: f32,
+    @location(12) gradients: vec2<u32>,
+    @location(13) dash: u32,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
-    @location(0) uv_coords: vec2<f32>,
+    // Position relative to the circle center in pixels.
+    @location(0) local_position: vec2<f32>,
     @location(1) half_brush_size: f32,
+    @location(2) stroke_color: vec4<f32>,
+    @location(3) fill_color: vec4<f32>,
+    @location(4) @interpolate(flat) fill_mode: u32,
+    @location(5) radius: f32,
+    @location(6) @interpolate(flat) opacity: f32,
+    // Position in the coordinates of the shape before its transform, where
+    // the gradients are.
+    @location(7) shape_position: vec2<f32>,
+    @location(8) @interpolate(flat) gradients: vec2<u32>,
+    @location(9) @interpolate(flat) dash: u32,
+}
+
+// Smallest factor the transform scales a length by; the anti aliasing margin
+// is in pixels, so it grows in the local units when the shape is shrunk.
+fn min_scale(m: mat2x2<f32>) -> f32 {
+    let sum = dot(m[0], m[0]) + dot(m[1], m[1]);
+    let det = determinant(m);
+    let root = sqrt(max(sum * sum - 4.0 * det * det, 0.0));
+    return sqrt(max((sum - root) / 2.0, 0.00000001));
 }
 
 @vertex
@@ -26,30 +68,66 @@ fn vs_main(
 ) -> VertexOutput {
     var out: VertexOutput;
 
-    let model_matrix = mat4x4<f32>(
-        vec4(1.0, 0.0, 0.0, 0.0),
-        vec4(0.0, 1.0, 0.0, 0.0),
-        vec4(0.0, 0.0, 1.0, 0.0),
-        vec4(instance.position.x, instance.position.y, 0.0, 1.0)
-    );
-    let world_position = model_matrix * vec4<f32>(model.position.x * instance.radius, model.position.y * instance.radius, 0.5, 1.0);
+    let transform = mat2x2<f32>(instance.transform_x_axis, instance.transform_y_axis);
+    let extent = instance.radius + AA_MARGIN / min_scale(transform);
+    let local_position = model.uv_coords * extent;
+    let world_position = transform * (instance.position + local_position) + instance.transform_translation;
 
-    out.clip_position = perspective * world_position;
-    out.uv_coords = model.uv_coords;
-    out.half_brush_size = (instance.brush_size/instance.radius)/2.0;
+    out.clip_position = perspective * vec4<f32>(world_position, 0.5, 1.0);
+    out.local_position = local_position;
+    out.half_brush_size = instance.brush_size / 2.0;
+    out.stroke_color = instance.stroke_color;
+    out.fill_color = instance.fill_color;
+    out.fill_mode = instance.fill_mode;
+    out.opacity = instance.opacity;
+    out.radius = instance.radius;
+    out.shape_position = instance.position + local_position;
+    out.gradients = instance.gradients;
+    out.dash = instance.dash;
 
     return out;
 }
 
 // Fragment shader
 
-@fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let circle_sd: f32 = abs(length(in.uv_coords) - 1.0 + in.half_brush_size) - in.half_brush_size;
-    // let circle_sd: f32 = length(in.uv_coords) - 1.0;
+// Part of the pixel covered by the shape with the signed distance `sd`.
+fn coverage(sd: f32) -> f32 {
+    if ANTI_ALIASING {
+        let pixel_size = max(fwidth(sd), 0.0001);
+        return clamp(0.5 - sd / pixel_size, 0.0, 1.0);
+    }
+    return select(0.0, 1.0, sd <= 0.0);
+}
 
-    if circle_sd > 0.0 {
+// Stroke composed over the fill and faded by the opacity of the shape; the
+// result is premultiplied.
+fn paint(stroke_color: vec4<f32>, stroke_coverage: f32, fill_color: vec4<f32>, fill_coverage: f32, opacity: f32) -> vec4<f32> {
+    let stroke_alpha = stroke_color.a * stroke_coverage;
+    let fill_alpha = fill_color.a * fill_coverage * (1.0 - stroke_alpha);
+    let alpha = (stroke_alpha + fill_alpha) * opacity;
+    if alpha <= 0.0 {
         discard;
     }
-    return vec4<f32>(1.0, 1.0, 0.0, 1.0);
+    return vec4<f32>((stroke_color.rgb * stroke_alpha + fill_color.rgb * fill_alpha) * opacity, alpha);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let circle_sd: f32 = length(in.local_position) - in.radius;
+    let across = abs(circle_sd + in.half_brush_size);
+    // The dashes start on the right and go clockwise along the middle of the
+    // stroke.
+    var angle = atan2(-in.local_position.y, in.local_position.x);
+    angle = select(angle, angle + TAU, angle < 0.0);
+    let along = angle * max(in.radius - in.half_brush_size, 0.0);
+    let stroke_sd = dashed_stroke_sd(across - in.half_brush_size, across, along, in.half_brush_size, in.dash);
+
+    // Coverage is computed for every pixel; derivatives can't be used in
+    // non-uniform control flow.
+    let stroke_coverage = coverage(stroke_sd) * select(0.0, 1.0, (in.fill_mode & STROKE_FLAG) != 0u);
+    let fill_coverage = coverage(circle_sd) * select(0.0, 1.0, (in.fill_mode & FILL_FLAG) != 0u);
+
+    let stroke_color = paint_color(in.stroke_color, in.gradients.x, in.shape_position);
+    let fill_color = paint_color(in.fill_color, in.gradients.y, in.shape_position);
+    return paint(stroke_color, stroke_coverage, fill_color, fill_coverage, in.opacity) * clip_coverage(in.clip_position);
 }
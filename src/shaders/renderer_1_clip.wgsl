@@ -0,0 +1,12 @@
+// Clip mask of the shapes; prepended to the shaders by renderer_1.rs.
+
+// Coverage of the path clips in the alpha channel, the size of the frame. The
+// shapes without a path clip get a 1×1 opaque mask.
+@group(2) @binding(0)
+var clip_mask: texture_2d<f32>;
+
+// Part of the pixel at the fragment position inside the clip.
+fn clip_coverage(position: vec4<f32>) -> f32 {
+    let last = vec2<i32>(textureDimensions(clip_mask)) - 1;
+    return textureLoad(clip_mask, min(vec2<i32>(position.xy), last), 0).a;
+}
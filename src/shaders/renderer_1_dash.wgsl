@@ -0,0 +1,63 @@
+// Dash patterns of the strokes; prepended to the shaders by renderer_1.rs.
+
+// Rows of the dash patterns, see renderer_1/dash.rs.
+@group(1) @binding(1)
+var dashes: texture_2d<f32>;
+
+// Have to be kept in sync with renderer_1/dash.rs.
+const ROUND_DASH_CAP: u32 = 1u;
+const SQUARE_DASH_CAP: u32 = 2u;
+const DASH_ROW_WIDTH: i32 = 5;
+
+// Texel of the row; the rows are wrapped into the lines of the texture.
+fn dash_texel(row: i32, column: i32) -> vec4<f32> {
+    let rows_per_line = i32(textureDimensions(dashes).x) / DASH_ROW_WIDTH;
+    let x = row % rows_per_line * DASH_ROW_WIDTH + column;
+    return textureLoad(dashes, vec2<i32>(x, row / rows_per_line), 0);
+}
+
+fn dash_length(row: i32, index: i32) -> f32 {
+    let texel = dash_texel(row, 1 + index / 4);
+    return texel[index % 4];
+}
+
+// Signed distance from `t` to the range from `start` to `end`.
+fn range_sd(t: f32, start: f32, end: f32) -> f32 {
+    return max(start - t, t - end);
+}
+
+// Signed distance to the stroke with the dash pattern; `stroke_sd` is the
+// distance to the solid stroke, `across` the distance from its middle and
+// `along` the length along the middle. The pattern is `dash - 1`, the stroke
+// is solid when `dash` is 0.
+fn dashed_stroke_sd(stroke_sd: f32, across: f32, along: f32, half_width: f32, dash: u32) -> f32 {
+    if dash == 0u {
+        return stroke_sd;
+    }
+    let row = i32(dash) - 1;
+    let header = dash_texel(row, 0);
+    let count = i32(header.x);
+    let period = header.y;
+    let t = along + header.z - floor((along + header.z) / period) * period;
+
+    // Distance along the stroke to the nearest dash; the dashes next to the
+    // ends of the pattern are repeated around it.
+    var sd = 1.0e9;
+    var start = 0.0;
+    for (var index = 0; index < count; index += 2) {
+        let end = start + dash_length(row, index);
+        sd = min(sd, range_sd(t, start, end));
+        sd = min(sd, range_sd(t, start - period, end - period));
+        sd = min(sd, range_sd(t, start + period, end + period));
+        start = end + dash_length(row, index + 1);
+    }
+
+    let cap = u32(header.w);
+    if cap == ROUND_DASH_CAP {
+        return select(length(vec2<f32>(sd, across)) - half_width, stroke_sd, sd <= 0.0);
+    }
+    if cap == SQUARE_DASH_CAP {
+        return max(stroke_sd, sd - half_width);
+    }
+    return max(stroke_sd, sd);
+}
@@ -0,0 +1,197 @@
+// `ANTI_ALIASING` constant and the functions of renderer_1_gradient.wgsl,
+// renderer_1_dash.wgsl and renderer_1_clip.wgsl are prepended by renderer_1.rs.
+
+// Has to be kept in sync with the `FillMode` flags in renderer_1.rs.
+const STROKE_FLAG: u32 = 1u;
+const FILL_FLAG: u32 = 2u;
+
+// Has to be kept in sync with the kinds in renderer_1/ellipse.rs.
+const ARC_KIND: u32 = 1u;
+const PIE_KIND: u32 = 2u;
+
+// The quad is this many pixels bigger than the shape so there is space for
+// the anti aliased edge.
+const AA_MARGIN: f32 = 1.0;
+
+const PI: f32 = 3.14159265;
+
+// Vertex shader
+
+@group(0) @binding(0)
+var<uniform> perspective: mat4x4<f32>;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) uv_coords: vec2<f32>,
+}
+
+struct InstanceInput {
+    @location(2) position: vec2<f32>,
+    @location(3) radii: vec2<f32>,
+    // Rotation, brush size, start angle and sweep angle packed together; the
+    // pipeline has only 16 attributes.
+    @location(4) parameters: vec4<f32>,
+    @location(5) kind: u32,
+    @location(6) stroke_color: vec4<f32>,
+    @location(7) fill_color: vec4<f32>,
+    @location(8) fill_mode: u32,
+    @location(9) transform_x_axis: vec2<f32>,
+    @location(10) transform_y_axis: vec2<f32>,
+    @location(11) transform_translation: vec2<f32>,
+    @location(12) opacity: f32,
+    @location(13) gradients: vec2<u32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // Position relative to the center of the rotated ellipse in pixels.
+    @location(0) local_position: vec2<f32>,
+    @location(1) @interpolate(flat) radii: vec2<f32>,
+    @location(2) @interpolate(flat) half_brush_size: f32,
+    // Position rotated so the middle of the angles points up.
+    @location(3) wedge_position: vec2<f32>,
+    @location(4) @interpolate(flat) half_sweep_angle: f32,
+    @location(5) @interpolate(flat) kind: u32,
+    @location(6) stroke_color: vec4<f32>,
+    @location(7) fill_color: vec4<f32>,
+    @location(8) @interpolate(flat) fill_mode: u32,
+    @location(9) @interpolate(flat) opacity: f32,
+    // Position in the coordinates of the shape before its transform, where
+    // the gradients are.
+    @location(10) shape_position: vec2<f32>,
+    @location(11) @interpolate(flat) gradients: vec2<u32>,
+}
+
+fn rotate(v: vec2<f32>, angle: f32) -> vec2<f32> {
+    let c = cos(angle);
+    let s = sin(angle);
+    return vec2<f32>(c * v.x - s * v.y, s * v.x + c * v.y);
+}
+
+// Smallest factor the transform scales a length by; the anti aliasing margin
+// is in pixels, so it grows in the local units when the shape is shrunk.
+fn min_scale(m: mat2x2<f32>) -> f32 {
+    let sum = dot(m[0], m[0]) + dot(m[1], m[1]);
+    let det = determinant(m);
+    let root = sqrt(max(sum * sum - 4.0 * det * det, 0.0));
+    return sqrt(max((sum - root) / 2.0, 0.00000001));
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    let transform = mat2x2<f32>(instance.transform_x_axis, instance.transform_y_axis);
+    let extent = instance.radii + AA_MARGIN / min_scale(transform);
+    let local_position = model.uv_coords * extent;
+    let rotation = instance.parameters.x;
+    let shape_position = instance.position + rotate(local_position, rotation);
+    let world_position = transform * shape_position + instance.transform_translation;
+
+    out.clip_position = perspective * vec4<f32>(world_position, 0.5, 1.0);
+    out.local_position = local_position;
+    out.radii = instance.radii;
+    out.half_brush_size = instance.parameters.y / 2.0;
+    let start_angle = instance.parameters.z;
+    let half_sweep_angle = instance.parameters.w / 2.0;
+    out.wedge_position = rotate(local_position, PI / 2.0 - start_angle - half_sweep_angle);
+    out.half_sweep_angle = half_sweep_angle;
+    out.kind = instance.kind;
+    out.stroke_color = instance.stroke_color;
+    out.fill_color = instance.fill_color;
+    out.fill_mode = instance.fill_mode;
+    out.opacity = instance.opacity;
+    out.shape_position = shape_position;
+    out.gradients = instance.gradients;
+
+    return out;
+}
+
+// Fragment shader
+
+// Part of the pixel covered by the shape with the signed distance `sd`.
+fn coverage(sd: f32) -> f32 {
+    if ANTI_ALIASING {
+        let pixel_size = max(fwidth(sd), 0.0001);
+        return clamp(0.5 - sd / pixel_size, 0.0, 1.0);
+    }
+    return select(0.0, 1.0, sd <= 0.0);
+}
+
+// Stroke composed over the fill and faded by the opacity of the shape; the
+// result is premultiplied.
+fn paint(stroke_color: vec4<f32>, stroke_coverage: f32, fill_color: vec4<f32>, fill_coverage: f32, opacity: f32) -> vec4<f32> {
+    let stroke_alpha = stroke_color.a * stroke_coverage;
+    let fill_alpha = fill_color.a * fill_coverage * (1.0 - stroke_alpha);
+    let alpha = (stroke_alpha + fill_alpha) * opacity;
+    if alpha <= 0.0 {
+        discard;
+    }
+    return vec4<f32>((stroke_color.rgb * stroke_alpha + fill_color.rgb * fill_alpha) * opacity, alpha);
+}
+
+// Signed distance to an axis aligned ellipse. The closest point on the
+// ellipse is found by a few iterations approximating the ellipse by circles
+// around its evolute.
+fn ellipse_sd(p: vec2<f32>, radii: vec2<f32>) -> f32 {
+    let ab = max(radii, vec2<f32>(0.0001, 0.0001));
+    let q = abs(p);
+    var t = vec2<f32>(0.70710678, 0.70710678);
+    for (var i = 0; i < 3; i++) {
+        let point = ab * t;
+        // Center of the curvature at `point`.
+        let evolute = vec2<f32>(
+            (ab.x * ab.x - ab.y * ab.y) * t.x * t.x * t.x / ab.x,
+            (ab.y * ab.y - ab.x * ab.x) * t.y * t.y * t.y / ab.y,
+        );
+        let r = length(point - evolute);
+        let to_q = q - evolute;
+        let moved = to_q * r / max(length(to_q), 0.0001) + evolute;
+        t = normalize(clamp(moved / ab, vec2<f32>(0.0, 0.0), vec2<f32>(1.0, 1.0)));
+    }
+    let distance = length(q - ab * t);
+    let inside = dot(q / ab, q / ab) < 1.0;
+    return select(distance, -distance, inside);
+}
+
+// Signed distance to the area between two rays from the origin, symmetric
+// around the y axis.
+fn wedge_sd(p: vec2<f32>, half_angle: f32) -> f32 {
+    if half_angle >= PI - 0.0001 {
+        return -1.0e9;
+    }
+    let q = vec2<f32>(abs(p.x), p.y);
+    let edge = vec2<f32>(sin(half_angle), cos(half_angle));
+    let distance = length(q - edge * max(dot(q, edge), 0.0));
+    let inside = q.x * edge.y - q.y * edge.x < 0.0;
+    return select(distance, -distance, inside);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let outline_sd = ellipse_sd(in.local_position, in.radii);
+    let wedge_sd = wedge_sd(in.wedge_position, in.half_sweep_angle);
+
+    var shape_sd = outline_sd;
+    if in.kind == PIE_KIND {
+        shape_sd = max(outline_sd, wedge_sd);
+    }
+    var stroke_sd = abs(shape_sd + in.half_brush_size) - in.half_brush_size;
+    var fill_mode = in.fill_mode;
+    if in.kind == ARC_KIND {
+        stroke_sd = max(stroke_sd, wedge_sd);
+        fill_mode = fill_mode & STROKE_FLAG;
+    }
+
+    // Coverage is computed for every pixel; derivatives can't be used in
+    // non-uniform control flow.
+    let stroke_coverage = coverage(stroke_sd) * select(0.0, 1.0, (fill_mode & STROKE_FLAG) != 0u);
+    let fill_coverage = coverage(shape_sd) * select(0.0, 1.0, (fill_mode & FILL_FLAG) != 0u);
+
+    let stroke_color = paint_color(in.stroke_color, in.gradients.x, in.shape_position);
+    let fill_color = paint_color(in.fill_color, in.gradients.y, in.shape_position);
+    return paint(stroke_color, stroke_coverage, fill_color, fill_coverage, in.opacity) * clip_coverage(in.clip_position);
+}
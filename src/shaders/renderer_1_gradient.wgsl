@@ -0,0 +1,51 @@
+// Gradients of the shapes; prepended to the shaders by renderer_1.rs.
+
+// Rows of the gradients, see renderer_1/gradient.rs.
+@group(1) @binding(0)
+var gradients: texture_2d<f32>;
+
+// Have to be kept in sync with renderer_1/gradient.rs.
+const GRADIENT_RAMP_START: i32 = 2;
+const GRADIENT_RAMP_WIDTH: i32 = 256;
+const GRADIENT_ROW_WIDTH: i32 = 258;
+const LINEAR_GRADIENT: u32 = 0u;
+const RADIAL_GRADIENT: u32 = 1u;
+
+const GRADIENT_TAU: f32 = 6.28318531;
+
+// Texel of the row; the rows are wrapped into the lines of the texture.
+fn gradient_texel(row: i32, column: i32) -> vec4<f32> {
+    let rows_per_line = i32(textureDimensions(gradients).x) / GRADIENT_ROW_WIDTH;
+    let x = row % rows_per_line * GRADIENT_ROW_WIDTH + column;
+    return textureLoad(gradients, vec2<i32>(x, row / rows_per_line), 0);
+}
+
+// Color of the paint at the point in the coordinates of the shape; the paint is
+// the color when `gradient` is 0, otherwise the gradient with the index
+// `gradient - 1`.
+fn paint_color(color: vec4<f32>, gradient: u32, p: vec2<f32>) -> vec4<f32> {
+    if gradient == 0u {
+        return color;
+    }
+    let row = i32(gradient) - 1;
+    let geometry = gradient_texel(row, 0);
+    let kind = u32(gradient_texel(row, 1).x);
+
+    var offset: f32;
+    if kind == LINEAR_GRADIENT {
+        let axis = geometry.zw - geometry.xy;
+        offset = dot(p - geometry.xy, axis) / max(dot(axis, axis), 0.00000001);
+    } else if kind == RADIAL_GRADIENT {
+        offset = length(p - geometry.xy) / max(geometry.z, 0.00000001);
+    } else {
+        let d = p - geometry.xy;
+        offset = fract((atan2(d.y, d.x) - geometry.z) / GRADIENT_TAU);
+    }
+
+    // Linear interpolation between the two nearest colors of the ramp.
+    let x = clamp(offset, 0.0, 1.0) * f32(GRADIENT_RAMP_WIDTH - 1);
+    let index = min(i32(x), GRADIENT_RAMP_WIDTH - 2);
+    let lower = gradient_texel(row, GRADIENT_RAMP_START + index);
+    let upper = gradient_texel(row, GRADIENT_RAMP_START + index + 1);
+    return mix(lower, upper, x - f32(index));
+}
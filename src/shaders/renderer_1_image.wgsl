@@ -0,0 +1,109 @@
+// `ANTI_ALIASING` constant and the functions of renderer_1_gradient.wgsl,
+// renderer_1_dash.wgsl and renderer_1_clip.wgsl are prepended by renderer_1.rs.
+
+// The quad is this many pixels bigger than the image so there is space for
+// the anti aliased edge.
+const AA_MARGIN: f32 = 1.0;
+
+// Vertex shader
+
+@group(0) @binding(0)
+var<uniform> perspective: mat4x4<f32>;
+
+@group(3) @binding(0)
+var image_texture: texture_2d<f32>;
+@group(3) @binding(1)
+var image_sampler: sampler;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) uv_coords: vec2<f32>,
+}
+
+struct InstanceInput {
+    @location(2) position: vec2<f32>,
+    @location(3) size: vec2<f32>,
+    @location(4) rotation: f32,
+    @location(5) tint: vec4<f32>,
+    // X, y, width and height in the pixels of the image from its top left
+    // corner.
+    @location(6) source: vec4<f32>,
+    @location(7) opacity: f32,
+    @location(8) transform_x_axis: vec2<f32>,
+    @location(9) transform_y_axis: vec2<f32>,
+    @location(10) transform_translation: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // Position relative to the image center before the rotation in pixels.
+    @location(0) local_position: vec2<f32>,
+    @location(1) @interpolate(flat) half_size: vec2<f32>,
+    // Texture coordinates of the position.
+    @location(2) uv: vec2<f32>,
+    @location(3) @interpolate(flat) tint: vec4<f32>,
+    @location(4) @interpolate(flat) opacity: f32,
+}
+
+// Smallest factor the transform scales a length by; the anti aliasing margin
+// is in pixels, so it grows in the local units when the image is shrunk.
+fn min_scale(m: mat2x2<f32>) -> f32 {
+    let sum = dot(m[0], m[0]) + dot(m[1], m[1]);
+    let det = determinant(m);
+    let root = sqrt(max(sum * sum - 4.0 * det * det, 0.0));
+    return sqrt(max((sum - root) / 2.0, 0.00000001));
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    let transform = mat2x2<f32>(instance.transform_x_axis, instance.transform_y_axis);
+    let extent = instance.size + AA_MARGIN / min_scale(transform);
+    let local_position = model.uv_coords * extent;
+    let sine = sin(instance.rotation);
+    let cosine = cos(instance.rotation);
+    let rotation = mat2x2<f32>(cosine, sine, -sine, cosine);
+    let world_position = transform * (instance.position + rotation * local_position) + instance.transform_translation;
+
+    // The image goes down from its top left corner, the local y axis up.
+    let corner_offset = vec2<f32>(1.0 + local_position.x / instance.size.x, 1.0 - local_position.y / instance.size.y) / 2.0;
+    let texel = instance.source.xy + corner_offset * instance.source.zw;
+
+    out.clip_position = perspective * vec4<f32>(world_position, 0.5, 1.0);
+    out.local_position = local_position;
+    out.half_size = instance.size;
+    out.uv = texel / vec2<f32>(textureDimensions(image_texture));
+    out.tint = instance.tint;
+    out.opacity = instance.opacity;
+
+    return out;
+}
+
+// Fragment shader
+
+// Part of the pixel covered by the image with the signed distance `sd`.
+fn coverage(sd: f32) -> f32 {
+    if ANTI_ALIASING {
+        let pixel_size = max(fwidth(sd), 0.0001);
+        return clamp(0.5 - sd / pixel_size, 0.0, 1.0);
+    }
+    return select(0.0, 1.0, sd <= 0.0);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let v = abs(in.local_position) - in.half_size;
+    let sd = length(max(v, vec2<f32>(0.0, 0.0))) + min(max(v.x, v.y), 0.0);
+    // Sampled before the discard; it needs uniform control flow.
+    let color = textureSample(image_texture, image_sampler, in.uv) * in.tint;
+
+    let alpha = color.a * coverage(sd) * in.opacity;
+    if alpha <= 0.0 {
+        discard;
+    }
+    return vec4<f32>(color.rgb * alpha, alpha) * clip_coverage(in.clip_position);
+}
@@ -0,0 +1,54 @@
+// `ANTI_ALIASING` constant is prepended by renderer_1.rs.
+
+// Composes a layer rendered to a texture over the frame; the quad covers the
+// whole frame and the layer is faded by its opacity as a whole. The colors are
+// blended by the pipeline of the blend mode.
+
+// Has to be kept in sync with `BlendMode::index` in renderer_1.rs.
+const DARKEN: u32 = 4u;
+
+@group(0) @binding(0)
+var layer: texture_2d<f32>;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) uv_coords: vec2<f32>,
+}
+
+struct InstanceInput {
+    @location(2) opacity: f32,
+    @location(3) blend_mode: u32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) @interpolate(flat) opacity: f32,
+    @location(1) @interpolate(flat) blend_mode: u32,
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(model.position, 0.5, 1.0);
+    out.opacity = instance.opacity;
+    out.blend_mode = instance.blend_mode;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // The layer has the same size as the frame and is premultiplied.
+    let color = textureLoad(layer, vec2<i32>(in.clip_position.xy), 0) * in.opacity;
+    if color.a <= 0.0 {
+        discard;
+    }
+    // The minimum of the colors is blended; the layer is composed over white
+    // first so the frame is kept where the layer is transparent.
+    if in.blend_mode == DARKEN {
+        return vec4<f32>(color.rgb + (1.0 - color.a), color.a);
+    }
+    return color;
+}
@@ -0,0 +1,134 @@
+// `ANTI_ALIASING` constant and the functions of renderer_1_gradient.wgsl,
+// renderer_1_dash.wgsl and renderer_1_clip.wgsl are prepended by renderer_1.rs.
+
+// The quad is this many pixels bigger than the shape so there is space for
+// the anti aliased edge.
+const AA_MARGIN: f32 = 1.0;
+
+// Vertex shader
+
+@group(0) @binding(0)
+var<uniform> perspective: mat4x4<f32>;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) uv_coords: vec2<f32>,
+}
+
+// The ends are described by `LineEnd` in renderer_1/line.rs.
+struct InstanceInput {
+    @location(2) start: vec2<f32>,
+    @location(3) end: vec2<f32>,
+    @location(4) width: f32,
+    @location(5) stroke_color: vec4<f32>,
+    @location(6) start_cut: vec4<f32>,
+    @location(7) start_bevel: vec4<f32>,
+    @location(8) end_cut: vec4<f32>,
+    @location(9) end_bevel: vec4<f32>,
+    @location(10) opacity: f32,
+    // Maps the pixels to the coordinates of the gradient.
+    @location(11) paint_transform_x_axis: vec2<f32>,
+    @location(12) paint_transform_y_axis: vec2<f32>,
+    @location(13) paint_transform_translation: vec2<f32>,
+    @location(14) gradient: u32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // Position in pixels.
+    @location(0) world_position: vec2<f32>,
+    @location(1) @interpolate(flat) start: vec2<f32>,
+    @location(2) @interpolate(flat) end: vec2<f32>,
+    @location(3) @interpolate(flat) normal: vec2<f32>,
+    @location(4) @interpolate(flat) half_width: f32,
+    @location(5) stroke_color: vec4<f32>,
+    @location(6) @interpolate(flat) start_cut: vec4<f32>,
+    @location(7) @interpolate(flat) start_bevel: vec4<f32>,
+    @location(8) @interpolate(flat) end_cut: vec4<f32>,
+    @location(9) @interpolate(flat) end_bevel: vec4<f32>,
+    @location(10) @interpolate(flat) opacity: f32,
+    @location(11) paint_position: vec2<f32>,
+    @location(12) @interpolate(flat) gradient: u32,
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    let axis = instance.end - instance.start;
+    let length = length(axis);
+    let direction = select(vec2<f32>(1.0, 0.0), axis / length, length > 0.0);
+    let normal = vec2<f32>(-direction.y, direction.x);
+    let half_width = instance.width / 2.0;
+
+    // The quad covers the segment with the parts of the ends reaching past
+    // the end points.
+    let along = mix(
+        -instance.start_cut.w - AA_MARGIN,
+        length + instance.end_cut.w + AA_MARGIN,
+        (model.position.x + 1.0) / 2.0,
+    );
+    let across = model.position.y * (half_width + AA_MARGIN);
+    let world_position = instance.start + direction * along + normal * across;
+
+    out.clip_position = perspective * vec4<f32>(world_position, 0.5, 1.0);
+    out.world_position = world_position;
+    out.start = instance.start;
+    out.end = instance.end;
+    out.normal = normal;
+    out.half_width = half_width;
+    out.stroke_color = instance.stroke_color;
+    out.start_cut = instance.start_cut;
+    out.start_bevel = instance.start_bevel;
+    out.end_cut = instance.end_cut;
+    out.end_bevel = instance.end_bevel;
+    out.opacity = instance.opacity;
+    let paint_transform = mat2x2<f32>(instance.paint_transform_x_axis, instance.paint_transform_y_axis);
+    out.paint_position = paint_transform * world_position + instance.paint_transform_translation;
+    out.gradient = instance.gradient;
+
+    return out;
+}
+
+// Fragment shader
+
+// Part of the pixel covered by the shape with the signed distance `sd`.
+fn coverage(sd: f32) -> f32 {
+    if ANTI_ALIASING {
+        let pixel_size = max(fwidth(sd), 0.0001);
+        return clamp(0.5 - sd / pixel_size, 0.0, 1.0);
+    }
+    return select(0.0, 1.0, sd <= 0.0);
+}
+
+// Signed distance of the shape around an end point; `p` is relative to the
+// end point.
+fn end_sd(p: vec2<f32>, cut: vec4<f32>, bevel: vec4<f32>, half_width: f32) -> f32 {
+    let cut_sd = dot(p, cut.xy) - cut.z;
+    let bevel_sd = dot(p, bevel.xy) - bevel.z;
+    let round_sd = select(bevel_sd, min(bevel_sd, length(p) - half_width), bevel.w > 0.5);
+    return max(cut_sd, round_sd);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let strip_sd = abs(dot(in.world_position - in.start, in.normal)) - in.half_width;
+    let line_sd = max(
+        strip_sd,
+        max(
+            end_sd(in.world_position - in.start, in.start_cut, in.start_bevel, in.half_width),
+            end_sd(in.world_position - in.end, in.end_cut, in.end_bevel, in.half_width),
+        ),
+    );
+
+    // A line has one color, so the opacity only fades it.
+    let color = paint_color(in.stroke_color, in.gradient, in.paint_position);
+    let alpha = color.a * in.opacity * coverage(line_sd) * clip_coverage(in.clip_position);
+    if alpha <= 0.0 {
+        discard;
+    }
+    return vec4<f32>(color.rgb * alpha, alpha);
+}
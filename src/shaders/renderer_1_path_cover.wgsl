@@ -0,0 +1,56 @@
+// `ANTI_ALIASING` constant and the functions of renderer_1_gradient.wgsl,
+// renderer_1_dash.wgsl and renderer_1_clip.wgsl are prepended by renderer_1.rs.
+
+// The quad covers the bounding box of a path; the stencil test lets through
+// only the pixels inside the path.
+
+@group(0) @binding(0)
+var<uniform> perspective: mat4x4<f32>;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) uv_coords: vec2<f32>,
+}
+
+struct InstanceInput {
+    @location(2) min: vec2<f32>,
+    @location(3) max: vec2<f32>,
+    @location(4) color: vec4<f32>,
+    @location(5) opacity: f32,
+    // Maps the pixels to the coordinates of the gradient.
+    @location(6) paint_transform_x_axis: vec2<f32>,
+    @location(7) paint_transform_y_axis: vec2<f32>,
+    @location(8) paint_transform_translation: vec2<f32>,
+    @location(9) gradient: u32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) @interpolate(flat) opacity: f32,
+    @location(2) paint_position: vec2<f32>,
+    @location(3) @interpolate(flat) gradient: u32,
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    let world_position = mix(instance.min, instance.max, (model.position + 1.0) / 2.0);
+    out.clip_position = perspective * vec4<f32>(world_position, 0.5, 1.0);
+    out.color = instance.color;
+    out.opacity = instance.opacity;
+    let paint_transform = mat2x2<f32>(instance.paint_transform_x_axis, instance.paint_transform_y_axis);
+    out.paint_position = paint_transform * world_position + instance.paint_transform_translation;
+    out.gradient = instance.gradient;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = paint_color(in.color, in.gradient, in.paint_position);
+    let alpha = color.a * in.opacity * clip_coverage(in.clip_position);
+    return vec4<f32>(color.rgb * alpha, alpha);
+}
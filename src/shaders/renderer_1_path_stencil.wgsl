@@ -0,0 +1,17 @@
+// `ANTI_ALIASING` constant is prepended by renderer_1.rs.
+
+// Triangles of the fan only change the stencil; the color writes are masked
+// out by the pipeline.
+
+@group(0) @binding(0)
+var<uniform> perspective: mat4x4<f32>;
+
+@vertex
+fn vs_main(@location(0) position: vec2<f32>) -> @builtin(position) vec4<f32> {
+    return perspective * vec4<f32>(position, 0.5, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(0.0, 0.0, 0.0, 0.0);
+}
@@ -0,0 +1,61 @@
+// `ANTI_ALIASING` constant and the functions of renderer_1_gradient.wgsl,
+// renderer_1_dash.wgsl and renderer_1_clip.wgsl are prepended by renderer_1.rs.
+
+// Vertex shader
+
+@group(0) @binding(0)
+var<uniform> perspective: mat4x4<f32>;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) edge_distances: vec3<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) opacity: f32,
+    @location(4) paint_position: vec2<f32>,
+    @location(5) gradient: u32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) edge_distances: vec3<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) @interpolate(flat) opacity: f32,
+    @location(3) paint_position: vec2<f32>,
+    @location(4) @interpolate(flat) gradient: u32,
+}
+
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = perspective * vec4<f32>(model.position, 0.5, 1.0);
+    out.edge_distances = model.edge_distances;
+    out.color = model.color;
+    out.opacity = model.opacity;
+    out.paint_position = model.paint_position;
+    out.gradient = model.gradient;
+    return out;
+}
+
+// Fragment shader
+
+// Part of the pixel covered by the shape with the signed distance `sd`.
+fn coverage(sd: f32) -> f32 {
+    if ANTI_ALIASING {
+        let pixel_size = max(fwidth(sd), 0.0001);
+        return clamp(0.5 - sd / pixel_size, 0.0, 1.0);
+    }
+    return select(0.0, 1.0, sd <= 0.0);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // The triangles end at the outline, so only its inner half of the anti
+    // aliased edge is drawn.
+    let outline_sd = -min(in.edge_distances.x, min(in.edge_distances.y, in.edge_distances.z));
+    let color = paint_color(in.color, in.gradient, in.paint_position);
+    let alpha = color.a * in.opacity * coverage(outline_sd) * clip_coverage(in.clip_position);
+    if alpha <= 0.0 {
+        discard;
+    }
+    return vec4<f32>(color.rgb * alpha, alpha);
+}
@@ -1,3 +1,16 @@
+// `ANTI_ALIASING` constant and the functions of renderer_1_gradient.wgsl,
+// renderer_1_dash.wgsl and renderer_1_clip.wgsl are prepended by renderer_1.rs.
+
+// Has to be kept in sync with the `FillMode` flags in renderer_1.rs.
+const STROKE_FLAG: u32 = 1u;
+const FILL_FLAG: u32 = 2u;
+
+// The quad is this many pixels bigger than the shape so there is space for
+// the anti aliased edge.
+const AA_MARGIN: f32 = 1.0;
+
+const PI: f32 = 3.14159265;
+
 // Vertex shader
 
 @group(0) @binding(0)
@@ -12,13 +25,44 @@ struct InstanceInput {
     @location(2) position: vec2<f32>,
     @location(3) size: vec2<f32>,
     @location(4) brush_size: f32,
+    @location(5) stroke_color: vec4<f32>,
+    @location(6) fill_color: vec4<f32>,
+    @location(7) fill_mode: u32,
+    // Top left, top right, bottom right and bottom left.
+    @location(8) corner_radii: vec4<f32>,
+    @location(9) transform_x_axis: vec2<f32>,
+    @location(10) transform_y_axis: vec2<f32>,
+    @location(11) transform_translation: vec2<f32>,
+    @location(12) opacity: f32,
+    @location(13) gradients: vec2<u32>,
+    @location(14) dash: u32,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
-    @location(0) rect_pos: vec2<f32>,
-    @location(1) brush_size: f32,
+    // Position relative to the rectangle center in pixels.
+    @location(0) local_position: vec2<f32>,
+    @location(1) half_brush_size: f32,
     @location(2) rectangle_half_size: vec2<f32>,
+    @location(3) stroke_color: vec4<f32>,
+    @location(4) fill_color: vec4<f32>,
+    @location(5) @interpolate(flat) fill_mode: u32,
+    @location(6) @interpolate(flat) corner_radii: vec4<f32>,
+    @location(7) @interpolate(flat) opacity: f32,
+    // Position in the coordinates of the shape before its transform, where
+    // the gradients are.
+    @location(8) shape_position: vec2<f32>,
+    @location(9) @interpolate(flat) gradients: vec2<u32>,
+    @location(10) @interpolate(flat) dash: u32,
+}
+
+// Smallest factor the transform scales a length by; the anti aliasing margin
+// is in pixels, so it grows in the local units when the shape is shrunk.
+fn min_scale(m: mat2x2<f32>) -> f32 {
+    let sum = dot(m[0], m[0]) + dot(m[1], m[1]);
+    let det = determinant(m);
+    let root = sqrt(max(sum * sum - 4.0 * det * det, 0.0));
+    return sqrt(max((sum - root) / 2.0, 0.00000001));
 }
 
 @vertex
@@ -28,32 +72,123 @@ fn vs_main(
 ) -> VertexOutput {
     var out: VertexOutput;
 
-    let model_matrix = mat4x4<f32>(
-        vec4(1.0, 0.0, 0.0, 0.0),
-        vec4(0.0, 1.0, 0.0, 0.0),
-        vec4(0.0, 0.0, 1.0, 0.0),
-        vec4(instance.position.x, instance.position.y, 0.0, 1.0)
-    );
-    let world_position = model_matrix * vec4<f32>(model.position.x * instance.size.x, model.position.y * instance.size.y, 0.5, 1.0);
+    let transform = mat2x2<f32>(instance.transform_x_axis, instance.transform_y_axis);
+    let extent = instance.size + AA_MARGIN / min_scale(transform);
+    let local_position = model.uv_coords * extent;
+    let world_position = transform * (instance.position + local_position) + instance.transform_translation;
 
-    out.clip_position = perspective * world_position;
-    out.rect_pos = vec2<f32>((instance.size.x / 2.0) * model.uv_coords.x, (instance.size.y / 2.0) * model.uv_coords.y);
-    out.brush_size = instance.brush_size * 0.5;
-    out.rectangle_half_size = instance.size / 2.0;
+    out.clip_position = perspective * vec4<f32>(world_position, 0.5, 1.0);
+    out.local_position = local_position;
+    out.half_brush_size = instance.brush_size / 2.0;
+    out.rectangle_half_size = instance.size;
+    out.stroke_color = instance.stroke_color;
+    out.fill_color = instance.fill_color;
+    out.fill_mode = instance.fill_mode;
+    out.opacity = instance.opacity;
+    out.shape_position = instance.position + local_position;
+    out.gradients = instance.gradients;
+    out.dash = instance.dash;
+    out.corner_radii = clamp(
+        instance.corner_radii,
+        vec4<f32>(0.0),
+        vec4<f32>(min(instance.size.x, instance.size.y)),
+    );
 
     return out;
 }
 
 // Fragment shader
 
+// Part of the pixel covered by the shape with the signed distance `sd`.
+fn coverage(sd: f32) -> f32 {
+    if ANTI_ALIASING {
+        let pixel_size = max(fwidth(sd), 0.0001);
+        return clamp(0.5 - sd / pixel_size, 0.0, 1.0);
+    }
+    return select(0.0, 1.0, sd <= 0.0);
+}
+
+// Stroke composed over the fill and faded by the opacity of the shape; the
+// result is premultiplied.
+fn paint(stroke_color: vec4<f32>, stroke_coverage: f32, fill_color: vec4<f32>, fill_coverage: f32, opacity: f32) -> vec4<f32> {
+    let stroke_alpha = stroke_color.a * stroke_coverage;
+    let fill_alpha = fill_color.a * fill_coverage * (1.0 - stroke_alpha);
+    let alpha = (stroke_alpha + fill_alpha) * opacity;
+    if alpha <= 0.0 {
+        discard;
+    }
+    return vec4<f32>((stroke_color.rgb * stroke_alpha + fill_color.rgb * fill_alpha) * opacity, alpha);
+}
+
+// Signed distance to a box with rounded corners; the radius is picked by the
+// quadrant of `p`.
+fn rounded_box_sd(p: vec2<f32>, half_size: vec2<f32>, corner_radii: vec4<f32>) -> f32 {
+    let top = select(corner_radii.x, corner_radii.y, p.x > 0.0);
+    let bottom = select(corner_radii.w, corner_radii.z, p.x > 0.0);
+    let radius = select(bottom, top, p.y > 0.0);
+    let v = abs(p) - half_size + radius;
+    return length(max(v, vec2<f32>(0.0, 0.0))) + min(max(v.x, v.y), 0.0) - radius;
+}
+
+// Length along the outline of a box with rounded corners to the point nearest
+// to `p`; it starts on the left end of the top edge and goes clockwise.
+fn perimeter_position(p: vec2<f32>, half_size: vec2<f32>, corner_radii: vec4<f32>) -> f32 {
+    let quarter = PI / 2.0;
+    let top_right_start = 2.0 * half_size.x - corner_radii.x - corner_radii.y;
+    let right_start = top_right_start + quarter * corner_radii.y;
+    let bottom_right_start = right_start + 2.0 * half_size.y - corner_radii.y - corner_radii.z;
+    let bottom_start = bottom_right_start + quarter * corner_radii.z;
+    let bottom_left_start = bottom_start + 2.0 * half_size.x - corner_radii.z - corner_radii.w;
+    let left_start = bottom_left_start + quarter * corner_radii.w;
+    let top_left_start = left_start + 2.0 * half_size.y - corner_radii.w - corner_radii.x;
+
+    let top = select(corner_radii.x, corner_radii.y, p.x > 0.0);
+    let bottom = select(corner_radii.w, corner_radii.z, p.x > 0.0);
+    let radius = select(bottom, top, p.y > 0.0);
+    let q = abs(p);
+    let corner = half_size - radius;
+    if q.x > corner.x && q.y > corner.y {
+        // Angle from the edge along the x axis, in the mirrored quadrant.
+        let angle = atan2(q.y - corner.y, q.x - corner.x);
+        if p.x > 0.0 && p.y > 0.0 {
+            return top_right_start + (quarter - angle) * radius;
+        } else if p.x > 0.0 {
+            return bottom_right_start + angle * radius;
+        } else if p.y <= 0.0 {
+            return bottom_left_start + (quarter - angle) * radius;
+        }
+        return top_left_start + angle * radius;
+    }
+    if half_size.y - q.y < half_size.x - q.x {
+        if p.y > 0.0 {
+            return p.x + half_size.x - corner_radii.x;
+        }
+        return bottom_start + half_size.x - corner_radii.z - p.x;
+    }
+    if p.x > 0.0 {
+        return right_start + half_size.y - corner_radii.y - p.y;
+    }
+    return left_start + p.y + half_size.y - corner_radii.w;
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let rectangle_sd = rounded_box_sd(in.local_position, in.rectangle_half_size, in.corner_radii);
+    let across = abs(rectangle_sd + in.half_brush_size);
+    // The dashes are measured along the middle of the stroke.
+    let along = perimeter_position(
+        in.local_position,
+        in.rectangle_half_size - in.half_brush_size,
+        max(in.corner_radii - in.half_brush_size, vec4<f32>(0.0)),
+    );
+    let stroke_sd = dashed_stroke_sd(across - in.half_brush_size, across, along, in.half_brush_size, in.dash);
 
-    let v = abs(in.rect_pos) - in.rectangle_half_size;
-    let inner_sd = min(max(v.x, v.y), 0.0); 
+    // Coverage is computed for every pixel; derivatives can't be used in
+    // non-uniform control flow.
+    let stroke_coverage = coverage(stroke_sd) * select(0.0, 1.0, (in.fill_mode & STROKE_FLAG) != 0u);
+    let fill_coverage = coverage(rectangle_sd) * select(0.0, 1.0, (in.fill_mode & FILL_FLAG) != 0u);
 
-    if inner_sd < (-in.brush_size){
-        discard;
-    }
-    return vec4<f32>(1.0, 1.0, 0.0, 1.0);
+    let stroke_color = paint_color(in.stroke_color, in.gradients.x, in.shape_position);
+    let fill_color = paint_color(in.fill_color, in.gradients.y, in.shape_position);
+    return paint(stroke_color, stroke_coverage, fill_color, fill_coverage, in.opacity) * clip_coverage(in.clip_position);
 }
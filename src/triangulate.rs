@@ -0,0 +1,163 @@
+use crate::math::Vector2;
+
+// Twice the signed area; positive for counter clockwise polygons.
+fn signed_area(points: &[Vector2<f32>]) -> f32 {
+    (0..points.len())
+        .map(|index| points[index].cross(points[(index + 1) % points.len()]))
+        .sum()
+}
+
+fn contains(triangle: [Vector2<f32>; 3], point: Vector2<f32>) -> bool {
+    let [a, b, c] = triangle;
+    (b - a).cross(point - a) >= 0.0 && (c - b).cross(point - b) >= 0.0 && (a - c).cross(point - c) >= 0.0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Triangle {
+    // Counter clockwise indices of the points.
+    pub indices: [usize; 3],
+    // Whether the edge from the point to the next one is a part of the outline
+    // of the polygon.
+    pub outline: [bool; 3],
+}
+
+// Splits a simple polygon, convex or concave, to triangles by ear clipping.
+// Self intersecting polygons are triangulated too, but the triangles can
+// overlap.
+pub fn triangulate(points: &[Vector2<f32>]) -> Vec<Triangle> {
+    // Remaining points with the flag of the edge to the next remaining point.
+    let mut remaining: Vec<(usize, bool)> = (0..points.len()).map(|index| (index, true)).collect();
+    if signed_area(points) < 0.0 {
+        remaining.reverse();
+    }
+
+    let mut triangles = Vec::new();
+    let mut index = 0;
+    // Vertices checked since the last ear was clipped.
+    let mut checked = 0;
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        index %= count;
+        let previous_index = (index + count - 1) % count;
+        let (previous, previous_outline) = remaining[previous_index];
+        let (current, current_outline) = remaining[index];
+        let (next, _) = remaining[(index + 1) % count];
+        let triangle = [points[previous], points[current], points[next]];
+        let turn = (triangle[1] - triangle[0]).cross(triangle[2] - triangle[1]);
+
+        if turn == 0.0 {
+            // Straight or repeated vertex, there is no triangle to clip.
+            remaining[previous_index].1 = previous_outline && current_outline;
+            remaining.remove(index);
+            checked = 0;
+            continue;
+        }
+        let is_ear = turn > 0.0
+            && remaining
+                .iter()
+                .filter(|&&(other, _)| other != previous && other != current && other != next)
+                .all(|&(other, _)| !contains(triangle, points[other]));
+        // Without any ear the polygon crosses itself; the vertex is clipped
+        // anyway so the triangulation ends.
+        if is_ear || checked > count {
+            triangles.push(Triangle {
+                indices: [previous, current, next],
+                outline: [previous_outline, current_outline, false],
+            });
+            remaining[previous_index].1 = false;
+            remaining.remove(index);
+            checked = 0;
+        } else {
+            index += 1;
+            checked += 1;
+        }
+    }
+    if let [(a, a_outline), (b, b_outline), (c, c_outline)] = remaining[..] {
+        let turn = (points[b] - points[a]).cross(points[c] - points[b]);
+        if turn != 0.0 {
+            triangles.push(Triangle {
+                indices: [a, b, c],
+                outline: [a_outline, b_outline, c_outline],
+            });
+        }
+    }
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(coordinates: &[(f32, f32)]) -> Vec<Vector2<f32>> {
+        coordinates.iter().map(|&(x, y)| Vector2 { x, y }).collect()
+    }
+
+    fn area(points: &[Vector2<f32>], triangles: &[Triangle]) -> f32 {
+        triangles
+            .iter()
+            .map(|triangle| {
+                let [a, b, c] = triangle.indices;
+                (points[b] - points[a]).cross(points[c] - points[a]) / 2.0
+            })
+            .sum()
+    }
+
+    // Outline edges of the triangles as pairs of points in either direction.
+    fn outline_edges(triangles: &[Triangle]) -> Vec<(usize, usize)> {
+        let mut edges: Vec<_> = triangles
+            .iter()
+            .flat_map(|triangle| {
+                (0..3).filter(|&edge| triangle.outline[edge]).map(|edge| {
+                    let (a, b) = (triangle.indices[edge], triangle.indices[(edge + 1) % 3]);
+                    (a.min(b), a.max(b))
+                })
+            })
+            .collect();
+        edges.sort();
+        edges
+    }
+
+    #[test]
+    fn convex_polygon() {
+        let hexagon: Vec<_> = (0..6)
+            .map(|index| {
+                let angle = index as f32 * std::f32::consts::TAU / 6.0;
+                Vector2 { x: angle.cos(), y: angle.sin() }
+            })
+            .collect();
+        let triangles = triangulate(&hexagon);
+        assert_eq!(triangles.len(), 4);
+        assert!((area(&hexagon, &triangles) - signed_area(&hexagon) / 2.0).abs() < 1.0e-5);
+    }
+
+    #[test]
+    fn concave_clockwise_polygon() {
+        // Clockwise "L" shape.
+        let shape = points(&[(0.0, 0.0), (0.0, 2.0), (1.0, 2.0), (1.0, 1.0), (2.0, 1.0), (2.0, 0.0)]);
+        let triangles = triangulate(&shape);
+        assert_eq!(triangles.len(), 4);
+        // Every triangle is counter clockwise and they cover the area exactly.
+        for triangle in &triangles {
+            let [a, b, c] = triangle.indices;
+            assert!((shape[b] - shape[a]).cross(shape[c] - shape[a]) > 0.0);
+        }
+        assert_eq!(area(&shape, &triangles), 3.0);
+        assert_eq!(outline_edges(&triangles), vec![(0, 1), (0, 5), (1, 2), (2, 3), (3, 4), (4, 5)]);
+    }
+
+    #[test]
+    fn straight_vertices_are_skipped() {
+        let square = points(&[(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0), (0.0, 2.0)]);
+        let triangles = triangulate(&square);
+        assert_eq!(triangles.len(), 2);
+        assert_eq!(area(&square, &triangles), 4.0);
+        // The edges along the straight vertices are still outline edges.
+        assert_eq!(outline_edges(&triangles).len(), 4);
+    }
+
+    #[test]
+    fn degenerate_polygons_have_no_triangles() {
+        assert!(triangulate(&points(&[(0.0, 0.0), (1.0, 1.0)])).is_empty());
+        assert!(triangulate(&points(&[(0.0, 0.0), (1.0, 1.0), (2.0, 2.0)])).is_empty());
+    }
+}
@@ -1,6 +1,12 @@
-use log::info;
+use anyhow::Result;
+use image::RgbaImage;
+use log::{info, warn};
+use winit::dpi::PhysicalSize;
 use winit::window::Window;
 
+use crate::capture::{self, FrameCapture};
+use crate::render_target::{FrameOutput, RenderTarget};
+
 // TODO: Try to think of a better name.
 pub struct WindowedDevice {
     // Configuration of the droweable surface. It is here so we can reconfigure
@@ -15,6 +21,7 @@ pub struct WindowedDevice {
     pub surface: wgpu::Surface,
     // Represents the system window.
     pub window: Window,
+    capture: FrameCapture,
 }
 
 impl WindowedDevice {
@@ -70,8 +77,14 @@ impl WindowedDevice {
             .iter()
             .copied().find(|f| f.is_srgb())
             .unwrap_or(surface_caps.formats[0]);
+        // Frames can be copied back to CPU only if the surface allows it.
+        let usage = if surface_caps.usages.contains(wgpu::TextureUsages::COPY_SRC) {
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC
+        } else {
+            wgpu::TextureUsages::RENDER_ATTACHMENT
+        };
         let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            usage,
             format: surface_format,
             width: size.width,
             height: size.height,
@@ -89,23 +102,43 @@ impl WindowedDevice {
             queue,
             config,
             window,
+            capture: FrameCapture::default(),
+        }
+    }
+
+    pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
+        if new_size.width > 0 && new_size.height > 0 {
+            self.config.width = new_size.width;
+            self.config.height = new_size.height;
+            self.surface.configure(&self.device, &self.config);
         }
     }
+}
+
+impl RenderTarget for WindowedDevice {
+    fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+
+    fn queue(&self) -> &wgpu::Queue {
+        &self.queue
+    }
+
+    fn format(&self) -> wgpu::TextureFormat {
+        self.config.format
+    }
+
+    fn size(&self) -> PhysicalSize<u32> {
+        PhysicalSize::new(self.config.width, self.config.height)
+    }
 
     // TODO: Is it possible to do this only onece???
     // * Looks like encoder could be put into "global" state.
     // output and view seams to be part of the swapchain so I guess they need to be
     // recreated every time???
-    pub fn prepare_encoder(
+    fn prepare_encoder(
         &self,
-    ) -> Result<
-        (
-            wgpu::CommandEncoder,
-            wgpu::TextureView,
-            wgpu::SurfaceTexture,
-        ),
-        wgpu::SurfaceError,
-    > {
+    ) -> Result<(wgpu::CommandEncoder, wgpu::TextureView, FrameOutput), wgpu::SurfaceError> {
         info!("getting current surface texture");
         let output = self.surface.get_current_texture()?;
         info!("creating view from the texture");
@@ -119,6 +152,29 @@ impl WindowedDevice {
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
                 label: Some("GPU Encoder"),
             });
-        Ok((encoder, view, output))
+        Ok((encoder, view, FrameOutput::Surface(output)))
+    }
+
+    fn present(&mut self, output: FrameOutput) {
+        if self.capture.is_requested() {
+            if let FrameOutput::Surface(surface_texture) = &output {
+                let frame = if self.config.usage.contains(wgpu::TextureUsages::COPY_SRC) {
+                    capture::read_texture(&self.device, &self.queue, &surface_texture.texture)
+                } else {
+                    warn!("surface doesn't support copying; the frame can't be captured");
+                    Err(anyhow::anyhow!("Surface textures can't be copied on this platform"))
+                };
+                self.capture.store(frame);
+            }
+        }
+        output.present();
+    }
+
+    fn capture_next_frame(&mut self) {
+        self.capture.request();
+    }
+
+    fn take_captured_frame(&mut self) -> Result<RgbaImage> {
+        self.capture.take()
     }
 }
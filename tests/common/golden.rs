@@ -0,0 +1,193 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::*;
+use image::{Rgba, RgbaImage};
+
+// Setting this variable (to anything) overwrites the reference images with the
+// currently rendered ones instead of comparing them.
+pub const BLESS_VARIABLE: &str = "FROST_VECTOR_BLESS";
+
+#[derive(Debug, Clone, Copy)]
+pub struct Tolerance {
+    // Maximal difference of a single channel for pixels to be considered same.
+    pub channel: u8,
+    // Number of pixels which can differ more than `channel`. Different GPUs
+    // don't rasterize shape edges exactly the same.
+    pub mismatched_pixels: usize,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Tolerance {
+            channel: 2,
+            mismatched_pixels: 0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Comparison {
+    pub mismatched_pixels: usize,
+    pub max_channel_difference: u8,
+    // Mismatched pixels are red, the rest is a dimmed copy of the expected
+    // image.
+    pub diff: RgbaImage,
+}
+
+impl Comparison {
+    pub fn passed(&self, tolerance: Tolerance) -> bool {
+        self.mismatched_pixels <= tolerance.mismatched_pixels
+    }
+}
+
+pub fn compare(actual: &RgbaImage, expected: &RgbaImage, tolerance: Tolerance) -> Result<Comparison> {
+    if actual.dimensions() != expected.dimensions() {
+        bail!(
+            "Image size {:?} differs from the expected {:?}",
+            actual.dimensions(),
+            expected.dimensions()
+        );
+    }
+
+    let mut mismatched_pixels = 0;
+    let mut max_channel_difference = 0;
+    let mut diff = RgbaImage::new(actual.width(), actual.height());
+    for ((a, e), d) in actual.pixels().zip(expected.pixels()).zip(diff.pixels_mut()) {
+        let difference = a.0.iter().zip(e.0.iter()).map(|(a, e)| a.abs_diff(*e)).max().unwrap_or(0);
+        max_channel_difference = max_channel_difference.max(difference);
+        *d = if difference > tolerance.channel {
+            mismatched_pixels += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            let [r, g, b, _] = e.0;
+            let luma = ((r as u32 + g as u32 + b as u32) / 3 / 4) as u8;
+            Rgba([luma, luma, luma, 255])
+        };
+    }
+
+    Ok(Comparison {
+        mismatched_pixels,
+        max_channel_difference,
+        diff,
+    })
+}
+
+// Directory with reference images and the directory where the rendered and
+// diff images are written for failed comparisons.
+pub struct Golden {
+    references: PathBuf,
+    failures: PathBuf,
+}
+
+impl Golden {
+    pub fn new<P: Into<PathBuf>, Q: Into<PathBuf>>(references: P, failures: Q) -> Self {
+        Golden {
+            references: references.into(),
+            failures: failures.into(),
+        }
+    }
+
+    pub fn reference_path(&self, name: &str) -> PathBuf {
+        self.references.join(name).with_extension("png")
+    }
+
+    // Compares the image with the reference image called `name`. On failure
+    // `<name>.actual.png` and `<name>.diff.png` are written into the failures
+    // directory.
+    pub fn check(&self, name: &str, actual: &RgbaImage, tolerance: Tolerance) -> Result<()> {
+        let reference_path = self.reference_path(name);
+        if env::var_os(BLESS_VARIABLE).is_some() {
+            save(&reference_path, actual)?;
+            return Ok(());
+        }
+
+        let expected = image::open(&reference_path)
+            .with_context(|| {
+                format!(
+                    "Reference image {} can't be loaded; run with {}=1 to create it",
+                    reference_path.display(),
+                    BLESS_VARIABLE
+                )
+            })?
+            .to_rgba8();
+
+        let actual_path = self.failures.join(format!("{}.actual.png", name));
+        let comparison = compare(actual, &expected, tolerance);
+        if comparison.is_err() {
+            save(&actual_path, actual)?;
+        }
+        let comparison = comparison.with_context(|| format!("Golden image {} doesn't match", name))?;
+        if comparison.passed(tolerance) {
+            return Ok(());
+        }
+
+        let diff_path = self.failures.join(format!("{}.diff.png", name));
+        save(&actual_path, actual)?;
+        save(&diff_path, &comparison.diff)?;
+        bail!(
+            "Golden image {} doesn't match: {} pixels differ (allowed {}), max channel difference {}; see {} and {}",
+            name,
+            comparison.mismatched_pixels,
+            tolerance.mismatched_pixels,
+            comparison.max_channel_difference,
+            actual_path.display(),
+            diff_path.display()
+        )
+    }
+}
+
+fn save(path: &Path, image: &RgbaImage) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    image
+        .save_with_format(path, image::ImageFormat::Png)
+        .with_context(|| format!("Image {} can't be written", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(pixels: &[[u8; 4]]) -> RgbaImage {
+        RgbaImage::from_fn(pixels.len() as u32, 1, |x, _| Rgba(pixels[x as usize]))
+    }
+
+    #[test]
+    fn same_images_match() {
+        let a = image(&[[0, 0, 0, 255], [255, 255, 0, 255]]);
+        let comparison = compare(&a, &a, Tolerance::default()).unwrap();
+        assert_eq!(comparison.mismatched_pixels, 0);
+        assert_eq!(comparison.max_channel_difference, 0);
+    }
+
+    #[test]
+    fn differences_within_tolerance_match() {
+        let a = image(&[[0, 0, 0, 255], [255, 255, 0, 255]]);
+        let b = image(&[[2, 0, 0, 255], [253, 255, 1, 255]]);
+        let comparison = compare(&a, &b, Tolerance::default()).unwrap();
+        assert!(comparison.passed(Tolerance::default()));
+        assert_eq!(comparison.max_channel_difference, 2);
+    }
+
+    #[test]
+    fn differences_are_marked_in_diff() {
+        let a = image(&[[0, 0, 0, 255], [255, 255, 0, 255]]);
+        let b = image(&[[0, 0, 0, 255], [0, 0, 0, 255]]);
+        let comparison = compare(&a, &b, Tolerance::default()).unwrap();
+        assert_eq!(comparison.mismatched_pixels, 1);
+        assert!(!comparison.passed(Tolerance::default()));
+        assert!(comparison.passed(Tolerance { channel: 2, mismatched_pixels: 1 }));
+        assert_eq!(comparison.diff.get_pixel(1, 0), &Rgba([255, 0, 0, 255]));
+        assert_eq!(comparison.diff.get_pixel(0, 0), &Rgba([0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn different_sizes_fail() {
+        let a = image(&[[0, 0, 0, 255]]);
+        let b = image(&[[0, 0, 0, 255], [0, 0, 0, 255]]);
+        assert!(compare(&a, &b, Tolerance::default()).is_err());
+    }
+}
@@ -0,0 +1,996 @@
+use frost_vector::atlas::AtlasBuilder;
+use frost_vector::camera::Camera2D;
+use frost_vector::color::Color;
+use frost_vector::dash::Dash;
+use frost_vector::gradient::Gradient;
+use frost_vector::headless_device::HeadlessDevice;
+use frost_vector::math::{Affine2, Vector2};
+use frost_vector::path::{FillRule, Path};
+use frost_vector::render_common::{AntiAliasing, PreparedRenderBase, Projection};
+use frost_vector::render_target::RenderTarget;
+use frost_vector::renderer_1::{
+    BlendMode, FillMode, GroupHandle, LineCap, LineJoin, Renderer1, ShapeHandle, TextAlign,
+};
+use image::RgbaImage;
+use winit::dpi::PhysicalSize;
+
+mod common;
+
+use common::golden::{compare, Golden, Tolerance};
+
+const SIZE: PhysicalSize<u32> = PhysicalSize::new(128, 128);
+
+// Edges of the shapes can be rasterized slightly differently on other GPUs.
+const TOLERANCE: Tolerance = Tolerance {
+    channel: 2,
+    mismatched_pixels: 64,
+};
+
+fn golden() -> Golden {
+    Golden::new(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden"),
+        concat!(env!("CARGO_TARGET_TMPDIR"), "/golden"),
+    )
+}
+
+fn render(renderer: &Renderer1) -> RgbaImage {
+    let mut device = pollster::block_on(HeadlessDevice::new(SIZE));
+    device.render_to_image(renderer).unwrap()
+}
+
+#[test]
+fn circles() {
+    let mut renderer = Renderer1::default();
+    renderer.circle(32.0, 32.0, 24.0, 2.0);
+    renderer.circle(96.0, 32.0, 16.0, 8.0);
+    renderer.circle(32.0, 96.0, 8.0, 1.0);
+    renderer.circle(96.0, 96.0, 28.0, 28.0);
+
+    golden().check("renderer_1_circles", &render(&renderer), TOLERANCE).unwrap();
+}
+
+#[test]
+fn rectangles() {
+    let mut renderer = Renderer1::default();
+    renderer.rectangle(32.0, 32.0, 20.0, 12.0, 2.0);
+    renderer.rectangle(96.0, 32.0, 16.0, 24.0, 6.0);
+    renderer.rectangle(64.0, 96.0, 50.0, 20.0, 1.0);
+
+    golden().check("renderer_1_rectangles", &render(&renderer), TOLERANCE).unwrap();
+}
+
+#[test]
+fn overlapping_shapes() {
+    let mut renderer = Renderer1::default();
+    renderer.circles(&vec![(64.0, 64.0, 40.0, 4.0), (40.0, 40.0, 20.0, 3.0)]);
+    renderer.rectangles(&vec![(64.0, 64.0, 30.0, 30.0, 3.0), (90.0, 90.0, 20.0, 10.0, 2.0)]);
+
+    golden().check("renderer_1_overlapping_shapes", &render(&renderer), TOLERANCE).unwrap();
+}
+
+#[test]
+fn colored_shapes() {
+    let mut renderer = Renderer1::default();
+    renderer.circle(32.0, 32.0, 24.0, 4.0).stroke(Color::RED);
+    renderer.circle(96.0, 32.0, 24.0, 4.0).stroke(Color::WHITE).fill(Color::BLUE);
+    renderer.rectangle(32.0, 96.0, 20.0, 20.0, 4.0).stroke(Color::GREEN);
+    renderer
+        .rectangle(96.0, 96.0, 20.0, 20.0, 4.0)
+        .stroke(Color::rgb(1.0, 0.0, 1.0))
+        .fill(Color::rgb(0.0, 1.0, 1.0));
+
+    golden().check("renderer_1_colored_shapes", &render(&renderer), TOLERANCE).unwrap();
+}
+
+#[test]
+fn fill_modes() {
+    let mut renderer = Renderer1::default();
+    renderer.circle(32.0, 32.0, 24.0, 4.0).fill_mode(FillMode::Fill).fill(Color::RED);
+    renderer.circle(96.0, 32.0, 24.0, 4.0).stroke(Color::WHITE).fill(Color::RED);
+    renderer.rectangle(32.0, 96.0, 20.0, 20.0, 4.0).fill(Color::GREEN).fill_mode(FillMode::Fill);
+    renderer
+        .rectangle(96.0, 96.0, 20.0, 20.0, 4.0)
+        .fill(Color::GREEN)
+        .fill_mode(FillMode::FillAndStroke);
+
+    golden().check("renderer_1_fill_modes", &render(&renderer), TOLERANCE).unwrap();
+}
+
+#[test]
+fn line_caps() {
+    let mut renderer = Renderer1::default();
+    renderer.line(24.0, 104.0, 104.0, 104.0, 12.0).cap(LineCap::Butt);
+    renderer.line(24.0, 76.0, 104.0, 76.0, 12.0).cap(LineCap::Round).stroke(Color::RED);
+    renderer.line(24.0, 48.0, 104.0, 48.0, 12.0).cap(LineCap::Square).stroke(Color::GREEN);
+    renderer.line(16.0, 8.0, 112.0, 32.0, 1.0).stroke(Color::WHITE);
+    renderer.polyline(&[(64.0, 20.0)], 10.0).cap(LineCap::Round).stroke(Color::BLUE);
+
+    golden().check("renderer_1_line_caps", &render(&renderer), TOLERANCE).unwrap();
+}
+
+#[test]
+fn line_joins() {
+    let mut renderer = Renderer1::default();
+    let zigzag = |y: f32| [(12.0, y), (40.0, y + 24.0), (64.0, y), (88.0, y + 24.0), (116.0, y + 12.0)];
+    renderer.polyline(&zigzag(88.0), 8.0).join(LineJoin::Miter);
+    renderer.polyline(&zigzag(52.0), 8.0).join(LineJoin::Round).stroke(Color::RED);
+    renderer.polyline(&zigzag(16.0), 8.0).join(LineJoin::Bevel).stroke(Color::GREEN);
+    renderer
+        .polyline(&[(20.0, 4.0), (108.0, 4.0), (64.0, 12.0)], 3.0)
+        .close()
+        .stroke(Color::WHITE);
+
+    golden().check("renderer_1_line_joins", &render(&renderer), TOLERANCE).unwrap();
+}
+
+#[test]
+fn curves() {
+    let mut renderer = Renderer1::default();
+    renderer.quadratic((12.0, 80.0), (64.0, 150.0), (116.0, 80.0), 6.0).cap(LineCap::Round);
+    renderer
+        .cubic((12.0, 12.0), (12.0, 100.0), (116.0, -30.0), (116.0, 60.0), 4.0)
+        .stroke(Color::RED);
+    renderer
+        .cubic((20.0, 40.0), (60.0, 0.0), (70.0, 80.0), (108.0, 40.0), 2.0)
+        .tolerance(8.0)
+        .stroke(Color::WHITE);
+
+    golden().check("renderer_1_curves", &render(&renderer), TOLERANCE).unwrap();
+}
+
+#[test]
+fn ellipses_arcs_and_pies() {
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+    let mut renderer = Renderer1::default();
+    renderer.ellipse(32.0, 96.0, 28.0, 14.0, 0.0, 3.0);
+    renderer
+        .ellipse(96.0, 96.0, 28.0, 12.0, FRAC_PI_4, 3.0)
+        .stroke(Color::WHITE)
+        .fill(Color::BLUE);
+    renderer.arc(32.0, 32.0, 24.0, 0.0, 3.0 * FRAC_PI_2, 4.0).stroke(Color::RED);
+    renderer.arc(32.0, 32.0, 12.0, PI, FRAC_PI_4, 2.0).stroke(Color::WHITE);
+    renderer
+        .pie(96.0, 32.0, 26.0, FRAC_PI_4, 7.0 * FRAC_PI_4, 2.0)
+        .stroke(Color::WHITE)
+        .fill(Color::rgb(1.0, 0.6, 0.0));
+
+    golden().check("renderer_1_ellipses_arcs_and_pies", &render(&renderer), TOLERANCE).unwrap();
+}
+
+#[test]
+fn rounded_rectangles() {
+    let mut renderer = Renderer1::default();
+    renderer.rounded_rectangle(32.0, 96.0, 24.0, 20.0, [8.0; 4], 3.0);
+    renderer
+        .rounded_rectangle(96.0, 96.0, 24.0, 20.0, [16.0, 0.0, 4.0, 100.0], 3.0)
+        .stroke(Color::WHITE)
+        .fill(Color::BLUE);
+    renderer
+        .rounded_rectangle(64.0, 32.0, 52.0, 20.0, [20.0, 4.0, 20.0, 4.0], 2.0)
+        .fill(Color::RED)
+        .fill_mode(FillMode::Fill);
+
+    golden().check("renderer_1_rounded_rectangles", &render(&renderer), TOLERANCE).unwrap();
+}
+
+#[test]
+fn polygons() {
+    let mut renderer = Renderer1::default();
+    renderer.polygon(&[(8.0, 72.0), (56.0, 72.0), (32.0, 120.0)], 2.0);
+    let hexagon: Vec<_> = (0..6)
+        .map(|index| {
+            let angle = index as f32 * std::f32::consts::TAU / 6.0;
+            (96.0 + 26.0 * angle.cos(), 96.0 + 26.0 * angle.sin())
+        })
+        .collect();
+    renderer.polygon(&hexagon, 3.0).stroke(Color::WHITE).fill(Color::BLUE);
+    let star: Vec<_> = (0..10)
+        .map(|index| {
+            let angle = std::f32::consts::FRAC_PI_2 + index as f32 * std::f32::consts::PI / 5.0;
+            let radius = if index % 2 == 0 { 28.0 } else { 12.0 };
+            (32.0 + radius * angle.cos(), 32.0 + radius * angle.sin())
+        })
+        .collect();
+    renderer.polygon(&star, 2.0).fill(Color::RED).fill_mode(FillMode::Fill);
+    // Concave and clockwise.
+    renderer
+        .polygon(&[(72.0, 8.0), (72.0, 60.0), (88.0, 60.0), (88.0, 24.0), (120.0, 24.0), (120.0, 8.0)], 2.0)
+        .stroke(Color::GREEN)
+        .fill(Color::rgba(0.0, 1.0, 0.0, 0.5));
+
+    golden().check("renderer_1_polygons", &render(&renderer), TOLERANCE).unwrap();
+}
+
+#[test]
+fn transforms() {
+    let mut renderer = Renderer1::default();
+    renderer
+        .rectangle(32.0, 96.0, 20.0, 10.0, 2.0)
+        .transform(Affine2::rotation(std::f32::consts::FRAC_PI_6).around(32.0, 96.0));
+    renderer
+        .circle(96.0, 96.0, 16.0, 3.0)
+        .stroke(Color::WHITE)
+        .fill(Color::BLUE)
+        .transform(Affine2::skew(0.5, 0.0).around(96.0, 96.0));
+    renderer
+        .ellipse(32.0, 32.0, 20.0, 10.0, 0.0, 2.0)
+        .fill(Color::RED)
+        .transform(Affine2::scale(1.0, 2.0).around(32.0, 32.0));
+    let half_turn = Affine2::translation(96.0, 32.0) * Affine2::rotation(std::f32::consts::PI);
+    renderer
+        .polygon(&[(-12.0, -12.0), (12.0, -12.0), (0.0, 12.0)], 2.0)
+        .stroke(Color::GREEN)
+        .transform(half_turn * Affine2::scale(1.5, 1.5));
+    renderer
+        .line(-20.0, 0.0, 20.0, 0.0, 2.0)
+        .cap(LineCap::Round)
+        .transform(Affine2::translation(96.0, 32.0) * Affine2::rotation(std::f32::consts::FRAC_PI_4));
+
+    golden().check("renderer_1_transforms", &render(&renderer), TOLERANCE).unwrap();
+}
+
+// The caps and joins have to stay as wide as the segments when the line is
+// scaled by its own transform or by its group.
+#[test]
+fn scaled_lines() {
+    let mut renderer = Renderer1::default();
+    renderer
+        .line(-12.0, 0.0, 12.0, 0.0, 4.0)
+        .cap(LineCap::Square)
+        .transform(Affine2::translation(64.0, 104.0) * Affine2::scale(3.0, 3.0));
+    renderer
+        .polyline(&[(-10.0, -4.0), (0.0, 4.0), (10.0, -4.0)], 2.0)
+        .cap(LineCap::Round)
+        .join(LineJoin::Round)
+        .stroke(Color::RED)
+        .transform(Affine2::translation(36.0, 56.0) * Affine2::scale(2.0, 2.0));
+    renderer.push_group().transform(Affine2::translation(92.0, 56.0) * Affine2::scale(2.5, 2.5));
+    renderer
+        .polyline(&[(-10.0, -4.0), (0.0, 4.0), (10.0, -4.0)], 2.0)
+        .cap(LineCap::Square)
+        .join(LineJoin::Miter)
+        .stroke(Color::GREEN);
+    renderer.pop_group();
+    renderer.push_group().transform(Affine2::translation(64.0, 20.0) * Affine2::scale(0.5, 0.5));
+    renderer.line(-80.0, 0.0, 80.0, 0.0, 16.0).cap(LineCap::Round).stroke(Color::WHITE);
+    renderer.pop_group();
+
+    golden().check("renderer_1_scaled_lines", &render(&renderer), TOLERANCE).unwrap();
+}
+
+#[test]
+fn camera() {
+    let mut renderer = Renderer1::default();
+    renderer.rectangle(32.0, 32.0, 16.0, 16.0, 2.0);
+    renderer.circle(96.0, 32.0, 16.0, 2.0).fill(Color::RED);
+    renderer.ellipse(64.0, 96.0, 24.0, 12.0, 0.0, 2.0).fill(Color::BLUE);
+    renderer.line(8.0, 64.0, 120.0, 64.0, 2.0).stroke(Color::GREEN);
+
+    // Zoomed in and turned by 30 degrees around the middle, then moved right.
+    let mut camera = Camera2D::default();
+    let middle = Vector2 { x: 64.0, y: 64.0 };
+    camera.zoom_around(1.5, middle);
+    camera.rotate_around(std::f32::consts::FRAC_PI_6, middle);
+    camera.pan(8.0, 0.0);
+
+    let mut device = pollster::block_on(HeadlessDevice::new(SIZE));
+    let image = device.render_to_image_with_camera(&renderer, &camera).unwrap();
+    golden().check("renderer_1_camera", &image, TOLERANCE).unwrap();
+}
+
+// Scene with a shape of every kind; the handles are in the order the shapes
+// are added.
+fn retained_scene(renderer: &mut Renderer1) -> Vec<ShapeHandle> {
+    let mut path = Path::new();
+    path.move_to(72.0, 72.0).line_to(120.0, 72.0).line_to(96.0, 120.0).close();
+    vec![
+        renderer.circle(32.0, 32.0, 20.0, 2.0).handle(),
+        renderer.rectangle(96.0, 32.0, 20.0, 14.0, 2.0).handle(),
+        renderer.ellipse(32.0, 96.0, 24.0, 12.0, 0.0, 2.0).fill(Color::BLUE).handle(),
+        renderer.polygon(&[(8.0, 8.0), (56.0, 8.0), (32.0, 56.0)], 2.0).stroke(Color::GREEN).handle(),
+        renderer.line(8.0, 64.0, 120.0, 64.0, 2.0).stroke(Color::WHITE).handle(),
+        renderer.path(&path).fill(Color::RED).handle(),
+    ]
+}
+
+fn render_frame(device: &mut HeadlessDevice, projection: &Projection, prepared: &mut dyn PreparedRenderBase) -> RgbaImage {
+    device.capture_next_frame();
+    prepared.render(device, projection);
+    device.take_captured_frame().unwrap()
+}
+
+#[test]
+fn png_export_matches_the_rendered_image() {
+    let mut renderer = Renderer1::default();
+    retained_scene(&mut renderer);
+    let mut device = pollster::block_on(HeadlessDevice::new(SIZE));
+    let expected = device.render_to_image(&renderer).unwrap();
+
+    let mut projection = Projection::new(&device);
+    projection.update(&device, SIZE, &Camera2D::default());
+    let mut prepared = renderer.prepare_retained(&device, &projection.bind_group_layout);
+    // The missing directories are created.
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("png_export/frame.png");
+    let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    prepared.render_to_png(&mut device, &projection, &path).unwrap();
+    let exported = image::open(&path).unwrap().to_rgba8();
+    assert!(exported == expected);
+}
+
+#[test]
+fn retained_scene_updates() {
+    let mut device = pollster::block_on(HeadlessDevice::new(SIZE));
+    let projection = Projection::new(&device);
+
+    let mut renderer = Renderer1::default();
+    let handles = retained_scene(&mut renderer);
+    let mut prepared = renderer.prepare_retained(&device, &projection.bind_group_layout);
+    render_frame(&mut device, &projection, &mut prepared);
+
+    // Moved, restyled and removed after the first frame.
+    let scene = prepared.scene();
+    scene.edit_shape(handles[0]).unwrap().transform(Affine2::translation(8.0, 0.0)).fill(Color::RED);
+    scene.edit_shape(handles[1]).unwrap().stroke(Color::WHITE);
+    assert!(scene.remove(handles[3]));
+    assert!(!scene.remove(handles[3]));
+    assert!(scene.edit_shape(handles[3]).is_none());
+    scene.edit_line(handles[4]).unwrap().transform(Affine2::translation(0.0, -4.0));
+    scene.edit_path(handles[5]).unwrap().fill(Color::GREEN);
+    let updated = render_frame(&mut device, &projection, &mut prepared);
+
+    // The same scene built from scratch.
+    let mut expected = Renderer1::default();
+    let handles = retained_scene(&mut expected);
+    expected.edit_shape(handles[0]).unwrap().transform(Affine2::translation(8.0, 0.0)).fill(Color::RED);
+    expected.edit_shape(handles[1]).unwrap().stroke(Color::WHITE);
+    expected.remove(handles[3]);
+    expected.edit_line(handles[4]).unwrap().transform(Affine2::translation(0.0, -4.0));
+    expected.edit_path(handles[5]).unwrap().fill(Color::GREEN);
+    let mut expected = expected.prepare_retained(&device, &projection.bind_group_layout);
+    assert!(updated == render_frame(&mut device, &projection, &mut expected));
+
+    golden().check("renderer_1_retained_scene_updates", &updated, TOLERANCE).unwrap();
+}
+
+// Removed circles leave their places in the instances; the circles around them
+// are still drawn as if it was never added.
+#[test]
+fn removed_shapes_are_skipped() {
+    let mut device = pollster::block_on(HeadlessDevice::new(SIZE));
+    let projection = Projection::new(&device);
+    let circles = |renderer: &mut Renderer1, indices: &[usize]| -> Vec<ShapeHandle> {
+        let x = |index: usize| 20.0 + 28.0 * index as f32;
+        indices.iter().map(|&index| renderer.circle(x(index), 64.0, 12.0, 2.0).handle()).collect()
+    };
+
+    let mut renderer = Renderer1::default();
+    let handles = circles(&mut renderer, &[0, 1, 2, 3]);
+    let mut prepared = renderer.prepare_retained(&device, &projection.bind_group_layout);
+    render_frame(&mut device, &projection, &mut prepared);
+    assert!(prepared.scene().remove(handles[1]));
+    let updated = render_frame(&mut device, &projection, &mut prepared);
+
+    let mut expected = Renderer1::default();
+    circles(&mut expected, &[0, 2, 3]);
+    let mut expected = expected.prepare_retained(&device, &projection.bind_group_layout);
+    assert!(updated == render_frame(&mut device, &projection, &mut expected));
+}
+
+#[test]
+fn many_gradients() {
+    // More gradients than fit one line of the gradient texture.
+    let mut renderer = Renderer1::default();
+    for index in 0..16 {
+        let (x, y) = (16.0 + 32.0 * (index % 4) as f32, 16.0 + 32.0 * (index / 4) as f32);
+        let t = index as f32 / 15.0;
+        let gradient = Gradient::radial((x, y), 14.0)
+            .stop(0.0, Color::WHITE)
+            .stop(1.0, Color::rgb(t, 0.2, 1.0 - t));
+        renderer.circle(x, y, 14.0, 2.0).fill_gradient(&gradient).fill_mode(FillMode::Fill);
+    }
+    golden().check("renderer_1_many_gradients", &render(&renderer), TOLERANCE).unwrap();
+}
+
+#[test]
+fn gradients_of_removed_shapes_are_reused() {
+    let mut device = pollster::block_on(HeadlessDevice::new(SIZE));
+    let projection = Projection::new(&device);
+    let gradient = |x: f32, color: Color| Gradient::linear((x - 16.0, 0.0), (x + 16.0, 0.0)).stop(0.0, Color::WHITE).stop(1.0, color);
+
+    let mut renderer = Renderer1::default();
+    let removed = renderer.circle(32.0, 64.0, 16.0, 2.0).fill_gradient(&gradient(32.0, Color::RED)).handle();
+    let edited = renderer.circle(96.0, 64.0, 16.0, 2.0).fill_gradient(&gradient(96.0, Color::GREEN)).handle();
+    let mut prepared = renderer.prepare_retained(&device, &projection.bind_group_layout);
+    render_frame(&mut device, &projection, &mut prepared);
+    // The rows of both gradients are freed and taken by the new ones.
+    assert!(prepared.scene().remove(removed));
+    prepared.scene().edit_shape(edited).unwrap().fill_gradient(&gradient(96.0, Color::BLUE));
+    render_frame(&mut device, &projection, &mut prepared);
+    prepared.scene().circle(64.0, 96.0, 16.0, 2.0).fill_gradient(&gradient(64.0, Color::RED));
+    let updated = render_frame(&mut device, &projection, &mut prepared);
+
+    let mut expected = Renderer1::default();
+    expected.circle(96.0, 64.0, 16.0, 2.0).fill_gradient(&gradient(96.0, Color::BLUE));
+    expected.circle(64.0, 96.0, 16.0, 2.0).fill_gradient(&gradient(64.0, Color::RED));
+    let mut expected = expected.prepare_retained(&device, &projection.bind_group_layout);
+    assert!(updated == render_frame(&mut device, &projection, &mut expected));
+}
+
+#[test]
+fn dashes_of_removed_shapes_are_reused() {
+    let mut device = pollster::block_on(HeadlessDevice::new(SIZE));
+    let projection = Projection::new(&device);
+    let dashed = |renderer: &mut Renderer1, y: f32, length: f32| {
+        renderer.rectangle(16.0, y, 96.0, 24.0, 4.0).dash(&Dash::new(&[length, 4.0])).handle()
+    };
+    // Off the frame, so only the rows of their dash patterns show; more than
+    // fit one line of the dash texture.
+    let fillers = |renderer: &mut Renderer1| {
+        for index in 0..500 {
+            renderer.circle(-100.0, -100.0, 8.0, 2.0).dash(&Dash::new(&[1.0 + index as f32, 2.0]));
+        }
+    };
+
+    let mut renderer = Renderer1::default();
+    fillers(&mut renderer);
+    let removed = dashed(&mut renderer, 16.0, 6.0);
+    let mut prepared = renderer.prepare_retained(&device, &projection.bind_group_layout);
+    render_frame(&mut device, &projection, &mut prepared);
+    assert!(prepared.scene().remove(removed));
+    render_frame(&mut device, &projection, &mut prepared);
+    dashed(prepared.scene(), 88.0, 12.0);
+    let updated = render_frame(&mut device, &projection, &mut prepared);
+
+    let mut expected = Renderer1::default();
+    dashed(&mut expected, 88.0, 12.0);
+    let mut expected = expected.prepare_retained(&device, &projection.bind_group_layout);
+    assert!(updated == render_frame(&mut device, &projection, &mut expected));
+}
+
+#[test]
+fn z_order() {
+    let mut renderer = Renderer1::default();
+    // Shapes added later are drawn over the earlier ones of any kind.
+    renderer.rectangle(40.0, 88.0, 28.0, 28.0, 2.0).fill(Color::BLUE);
+    renderer.circle(64.0, 88.0, 24.0, 2.0).fill(Color::RED);
+    renderer.polygon(&[(64.0, 64.0), (120.0, 64.0), (92.0, 120.0)], 2.0).fill(Color::GREEN);
+    // The z-index lifts shapes over the ones added later and sinks the line
+    // under everything.
+    renderer.ellipse(40.0, 40.0, 28.0, 16.0, 0.0, 2.0).fill(Color::BLUE).z_index(1);
+    renderer.circle(72.0, 40.0, 24.0, 2.0).fill(Color::RED);
+    renderer.line(8.0, 8.0, 120.0, 120.0, 6.0).stroke(Color::WHITE).z_index(-1);
+
+    golden().check("renderer_1_z_order", &render(&renderer), TOLERANCE).unwrap();
+}
+
+fn star(cx: f32, cy: f32, radius: f32) -> Path {
+    let mut path = Path::new();
+    for index in 0..5 {
+        let angle = std::f32::consts::FRAC_PI_2 + index as f32 * 4.0 * std::f32::consts::PI / 5.0;
+        let (x, y) = (cx + radius * angle.cos(), cy + radius * angle.sin());
+        if index == 0 {
+            path.move_to(x, y);
+        } else {
+            path.line_to(x, y);
+        }
+    }
+    path.close();
+    path
+}
+
+// Square with a smaller square inside, both drawn in the same direction.
+fn nested_squares(cx: f32, cy: f32) -> Path {
+    let mut path = Path::new();
+    for half in [26.0, 12.0] {
+        path.move_to(cx - half, cy - half)
+            .line_to(cx + half, cy - half)
+            .line_to(cx + half, cy + half)
+            .line_to(cx - half, cy + half)
+            .close();
+    }
+    path
+}
+
+fn fill_rules_scene(anti_aliasing: AntiAliasing) -> Renderer1 {
+    let mut renderer = Renderer1::default();
+    renderer.anti_aliasing(anti_aliasing);
+    renderer.path(&star(32.0, 94.0, 30.0)).fill_rule(FillRule::NonZero);
+    renderer.path(&star(96.0, 94.0, 30.0)).fill_rule(FillRule::EvenOdd).fill(Color::RED);
+    renderer.path(&nested_squares(32.0, 32.0)).fill_rule(FillRule::NonZero).fill(Color::GREEN);
+    renderer.path(&nested_squares(96.0, 32.0)).fill_rule(FillRule::EvenOdd).fill(Color::BLUE);
+    renderer
+}
+
+#[test]
+fn path_fill_rules() {
+    let renderer = fill_rules_scene(AntiAliasing::Analytic);
+    golden().check("renderer_1_path_fill_rules", &render(&renderer), TOLERANCE).unwrap();
+}
+
+#[test]
+fn path_fill_rules_msaa() {
+    let renderer = fill_rules_scene(AntiAliasing::Msaa);
+    golden().check("renderer_1_path_fill_rules_msaa", &render(&renderer), TOLERANCE).unwrap();
+}
+
+#[test]
+fn paths_with_shapes() {
+    let mut renderer = Renderer1::default();
+    renderer.rectangle(64.0, 64.0, 56.0, 56.0, 2.0);
+    let mut drop = Path::new();
+    drop.move_to(64.0, 116.0)
+        .cubic_to(80.0, 80.0, 104.0, 64.0, 104.0, 44.0)
+        .quad_to(104.0, 12.0, 64.0, 12.0)
+        .quad_to(24.0, 12.0, 24.0, 44.0)
+        .cubic_to(24.0, 64.0, 48.0, 80.0, 64.0, 116.0);
+    renderer.path(&drop).fill(Color::rgba(0.0, 0.8, 1.0, 0.75));
+    renderer.circle(100.0, 100.0, 12.0, 3.0).stroke(Color::WHITE);
+
+    golden().check("renderer_1_paths_with_shapes", &render(&renderer), TOLERANCE).unwrap();
+}
+
+fn anti_aliasing_scene(anti_aliasing: AntiAliasing) -> Renderer1 {
+    let mut renderer = Renderer1::default();
+    renderer.anti_aliasing(anti_aliasing);
+    renderer.circle(40.0, 40.0, 30.0, 1.5).stroke(Color::WHITE);
+    renderer.circle(88.0, 88.0, 30.0, 3.0).fill(Color::BLUE);
+    renderer.rectangle(88.0, 40.0, 24.0, 16.0, 0.5).stroke(Color::WHITE);
+    // The slanted edges of paths are only smoothed by multisampling.
+    let mut path = Path::new();
+    path.move_to(8.0, 80.0).line_to(56.0, 92.0).line_to(20.0, 124.0).close();
+    renderer.path(&path).fill(Color::RED);
+    renderer
+}
+
+#[test]
+fn anti_aliasing_none() {
+    let renderer = anti_aliasing_scene(AntiAliasing::None);
+    golden().check("renderer_1_anti_aliasing_none", &render(&renderer), TOLERANCE).unwrap();
+}
+
+#[test]
+fn anti_aliasing_analytic() {
+    let renderer = anti_aliasing_scene(AntiAliasing::Analytic);
+    golden().check("renderer_1_anti_aliasing_analytic", &render(&renderer), TOLERANCE).unwrap();
+}
+
+#[test]
+fn anti_aliasing_msaa() {
+    let msaa = render(&anti_aliasing_scene(AntiAliasing::Msaa));
+    let analytic = render(&anti_aliasing_scene(AntiAliasing::Analytic));
+    assert!(!compare(&msaa, &analytic, TOLERANCE).unwrap().passed(TOLERANCE));
+    golden().check("renderer_1_anti_aliasing_msaa", &msaa, TOLERANCE).unwrap();
+}
+
+fn opacity_scene(anti_aliasing: AntiAliasing) -> Renderer1 {
+    let mut renderer = Renderer1::default();
+    renderer.anti_aliasing(anti_aliasing);
+    renderer.line(8.0, 64.0, 120.0, 64.0, 12.0).stroke(Color::WHITE);
+    // Translucent shapes of every kind over the line and each other.
+    renderer.circle(32.0, 96.0, 24.0, 4.0).stroke(Color::WHITE).fill(Color::RED).opacity(0.5);
+    renderer.rectangle(48.0, 80.0, 20.0, 20.0, 2.0).fill(Color::BLUE).fill_mode(FillMode::Fill).opacity(0.5);
+    renderer.polygon(&[(8.0, 8.0), (56.0, 8.0), (32.0, 56.0)], 4.0).fill(Color::GREEN).opacity(0.5);
+    renderer.line(8.0, 8.0, 56.0, 56.0, 6.0).stroke(Color::RED).opacity(0.75);
+    let mut path = Path::new();
+    path.move_to(72.0, 40.0).line_to(120.0, 40.0).line_to(96.0, 120.0).close();
+    renderer.path(&path).fill(Color::rgba(0.0, 1.0, 1.0, 0.8)).opacity(0.5);
+    // Overlapping shapes of the faded layer don't show through each other.
+    renderer.circle(88.0, 24.0, 16.0, 2.0).fill(Color::RED).z_index(1);
+    renderer.circle(104.0, 24.0, 16.0, 2.0).fill(Color::BLUE).z_index(1);
+    renderer.layer_opacity(1, 0.5);
+    renderer
+}
+
+#[test]
+fn opacity() {
+    let renderer = opacity_scene(AntiAliasing::Analytic);
+    golden().check("renderer_1_opacity", &render(&renderer), TOLERANCE).unwrap();
+}
+
+#[test]
+fn opacity_msaa() {
+    let renderer = opacity_scene(AntiAliasing::Msaa);
+    golden().check("renderer_1_opacity_msaa", &render(&renderer), TOLERANCE).unwrap();
+}
+
+fn blend_modes_scene(opacity: f32) -> Renderer1 {
+    let mut renderer = Renderer1::default();
+    let modes = [
+        BlendMode::Normal,
+        BlendMode::Additive,
+        BlendMode::Multiply,
+        BlendMode::Screen,
+        BlendMode::Darken,
+        BlendMode::Lighten,
+    ];
+    for (index, mode) in modes.into_iter().enumerate() {
+        let (x, y) = (24.0 + 40.0 * (index % 3) as f32, 96.0 - 64.0 * (index / 3) as f32);
+        renderer
+            .rectangle(x - 6.0, y, 12.0, 20.0, 2.0)
+            .fill(Color::rgb(0.2, 0.4, 0.8))
+            .fill_mode(FillMode::Fill);
+        // Every circle is a layer of its own.
+        let z_index = index as i32 + 1;
+        renderer.circle(x + 6.0, y, 14.0, 2.0).fill(Color::rgb(1.0, 0.5, 0.0)).z_index(z_index);
+        renderer.layer_blend_mode(z_index, mode);
+        renderer.layer_opacity(z_index, opacity);
+    }
+    // Translucent layer with the blend mode.
+    renderer.line(8.0, 64.0, 120.0, 64.0, 12.0).stroke(Color::RED).z_index(10);
+    renderer.layer_blend_mode(10, BlendMode::Additive);
+    renderer.layer_opacity(10, 0.5);
+    renderer
+}
+
+#[test]
+fn blend_modes() {
+    let renderer = blend_modes_scene(1.0);
+    golden().check("renderer_1_blend_modes", &render(&renderer), TOLERANCE).unwrap();
+}
+
+#[test]
+fn blend_modes_translucent() {
+    // `Darken` and `Lighten` are only approximated for the translucent layers.
+    let renderer = blend_modes_scene(0.5);
+    golden().check("renderer_1_blend_modes_translucent", &render(&renderer), TOLERANCE).unwrap();
+}
+
+#[test]
+fn gradients() {
+    let mut renderer = Renderer1::default();
+    let sunset = |gradient: Gradient| {
+        gradient
+            .stop(0.0, Color::rgb(1.0, 0.8, 0.0))
+            .stop(0.5, Color::rgb(1.0, 0.2, 0.4))
+            .stop(1.0, Color::rgb(0.2, 0.0, 0.6))
+    };
+    renderer
+        .circle(24.0, 24.0, 18.0, 4.0)
+        .fill_gradient(&sunset(Gradient::radial((24.0, 24.0), 18.0)))
+        .stroke_gradient(&Gradient::linear((6.0, 24.0), (42.0, 24.0)).stop(0.0, Color::BLACK).stop(1.0, Color::WHITE));
+    // The gradient moves with the shape.
+    renderer
+        .rectangle(0.0, 0.0, 18.0, 12.0, 2.0)
+        .fill_gradient(&sunset(Gradient::linear((-18.0, 0.0), (18.0, 0.0))))
+        .transform(Affine2::translation(84.0, 24.0) * Affine2::rotation(0.5));
+    renderer
+        .ellipse(24.0, 64.0, 20.0, 12.0, 0.3, 2.0)
+        .fill_gradient(&sunset(Gradient::conic((24.0, 64.0), 0.0)))
+        .fill_mode(FillMode::Fill);
+    renderer
+        .polygon(&[(64.0, 48.0), (104.0, 56.0), (96.0, 80.0), (56.0, 76.0)], 3.0)
+        .fill_gradient(&sunset(Gradient::linear((64.0, 48.0), (96.0, 80.0))))
+        .stroke(Color::WHITE);
+    let mut path = Path::new();
+    path.move_to(8.0, 120.0).quad_to(32.0, 72.0, 56.0, 120.0).close();
+    renderer
+        .path(&path)
+        .fill_gradient(&sunset(Gradient::radial((32.0, 120.0), 32.0)).stop(1.0, Color::TRANSPARENT));
+    renderer
+        .line(64.0, 108.0, 120.0, 108.0, 10.0)
+        .stroke_gradient(&sunset(Gradient::linear((64.0, 0.0), (120.0, 0.0))))
+        .cap(LineCap::Round)
+        .opacity(0.75);
+
+    golden().check("renderer_1_gradients", &render(&renderer), TOLERANCE).unwrap();
+}
+
+#[test]
+fn dashes() {
+    let mut renderer = Renderer1::default();
+    renderer.circle(32.0, 96.0, 24.0, 4.0).dash(&Dash::new(&[12.0, 6.0]));
+    renderer.circle(96.0, 96.0, 24.0, 6.0).dash(&Dash::dotted(12.0)).fill(Color::rgb(0.2, 0.4, 0.8));
+    renderer
+        .rectangle(32.0, 32.0, 24.0, 18.0, 4.0)
+        .dash(&Dash::new(&[10.0, 4.0, 2.0]).offset(5.0).cap(LineCap::Square));
+    renderer
+        .rounded_rectangle(96.0, 32.0, 24.0, 18.0, [10.0, 2.0, 6.0, 0.0], 3.0)
+        .dash(&Dash::new(&[6.0, 4.0]).cap(LineCap::Round))
+        .stroke(Color::rgb(1.0, 0.5, 0.0));
+
+    golden().check("renderer_1_dashes", &render(&renderer), TOLERANCE).unwrap();
+}
+
+fn clips_scene(anti_aliasing: AntiAliasing) -> Renderer1 {
+    let mut renderer = Renderer1::default();
+    renderer.anti_aliasing(anti_aliasing);
+    // Shapes cut by the rectangle, the stroke of the last circle only inside
+    // the diamond within it.
+    renderer.push_clip_rectangle(40.0, 88.0, 32.0, 24.0);
+    renderer.circle(16.0, 88.0, 20.0, 4.0).fill(Color::rgb(0.2, 0.4, 0.8));
+    renderer.line(0.0, 64.0, 128.0, 112.0, 6.0).stroke(Color::RED);
+    let mut diamond = Path::new();
+    diamond.move_to(48.0, 64.0).line_to(72.0, 88.0).line_to(48.0, 112.0).line_to(24.0, 88.0).close();
+    renderer.push_clip_path(&diamond, FillRule::NonZero);
+    renderer.circle(56.0, 88.0, 20.0, 8.0).stroke(Color::GREEN);
+    renderer.pop_clip();
+    renderer.pop_clip();
+
+    // Shapes inside the ring; the polygon is also cut by the rectangle.
+    let mut ring = Path::new();
+    ring.move_to(64.0, 8.0).cubic_to(100.0, 8.0, 100.0, 56.0, 64.0, 56.0).cubic_to(28.0, 56.0, 28.0, 8.0, 64.0, 8.0).close();
+    ring.move_to(64.0, 24.0).line_to(72.0, 32.0).line_to(64.0, 40.0).line_to(56.0, 32.0).close();
+    renderer.push_clip_path(&ring, FillRule::EvenOdd);
+    renderer.rectangle(64.0, 32.0, 40.0, 20.0, 1.0).fill(Color::rgb(1.0, 0.5, 0.0)).fill_mode(FillMode::Fill);
+    renderer.push_clip_rectangle(80.0, 32.0, 16.0, 32.0);
+    renderer.polygon(&[(40.0, 8.0), (120.0, 20.0), (88.0, 56.0)], 2.0).fill(Color::WHITE);
+    renderer.pop_clip();
+    renderer.pop_clip();
+
+    // Not clipped.
+    renderer.circle(112.0, 96.0, 12.0, 3.0);
+    renderer
+}
+
+#[test]
+fn clips() {
+    golden()
+        .check("renderer_1_clips", &render(&clips_scene(AntiAliasing::Analytic)), TOLERANCE)
+        .unwrap();
+}
+
+#[test]
+fn clips_msaa() {
+    golden()
+        .check("renderer_1_clips_msaa", &render(&clips_scene(AntiAliasing::Msaa)), TOLERANCE)
+        .unwrap();
+}
+
+// Circles cut by a rectangle clip on the left and a path clip on the right;
+// both clips are in the coordinates of the shapes, so they follow the camera.
+#[test]
+fn clips_follow_the_camera() {
+    let mut renderer = Renderer1::default();
+    renderer.push_clip_rectangle(32.0, 64.0, 16.0, 24.0);
+    renderer.circle(32.0, 64.0, 24.0, 2.0).fill(Color::RED);
+    renderer.pop_clip();
+    let mut diamond = Path::new();
+    diamond.move_to(96.0, 40.0).line_to(120.0, 64.0).line_to(96.0, 88.0).line_to(72.0, 64.0).close();
+    renderer.push_clip_path(&diamond, FillRule::NonZero);
+    renderer.circle(96.0, 64.0, 24.0, 2.0).fill(Color::GREEN);
+    renderer.pop_clip();
+
+    // Zoomed out and moved up, the rectangle is still cut by the scissor test.
+    let mut camera = Camera2D::default();
+    let middle = Vector2 { x: 64.0, y: 64.0 };
+    camera.zoom_around(0.75, middle);
+    camera.pan(0.0, 16.0);
+    let mut device = pollster::block_on(HeadlessDevice::new(SIZE));
+    let image = device.render_to_image_with_camera(&renderer, &camera).unwrap();
+    golden().check("renderer_1_clips_camera", &image, TOLERANCE).unwrap();
+
+    // Turned, the rectangle is masked like the path.
+    camera.rotate_around(std::f32::consts::FRAC_PI_6, middle);
+    let image = device.render_to_image_with_camera(&renderer, &camera).unwrap();
+    golden().check("renderer_1_clips_camera_rotated", &image, TOLERANCE).unwrap();
+}
+
+#[test]
+fn clip_masks_are_kept_across_frames() {
+    let mut device = pollster::block_on(HeadlessDevice::new(SIZE));
+    let projection = Projection::new(&device);
+    let clipped_circle = |renderer: &mut Renderer1| {
+        let mut diamond = Path::new();
+        diamond.move_to(100.0, 72.0).line_to(124.0, 96.0).line_to(100.0, 120.0).line_to(76.0, 96.0).close();
+        renderer.push_clip_path(&diamond, FillRule::NonZero);
+        renderer.circle(108.0, 96.0, 20.0, 2.0).fill(Color::RED);
+        renderer.pop_clip();
+    };
+
+    // The masks of the first frame are kept and a mask is added for the new
+    // clip path.
+    let mut prepared = clips_scene(AntiAliasing::Analytic).prepare_retained(&device, &projection.bind_group_layout);
+    render_frame(&mut device, &projection, &mut prepared);
+    render_frame(&mut device, &projection, &mut prepared);
+    clipped_circle(prepared.scene());
+    let updated = render_frame(&mut device, &projection, &mut prepared);
+
+    let mut expected = clips_scene(AntiAliasing::Analytic);
+    clipped_circle(&mut expected);
+    let mut expected = expected.prepare_retained(&device, &projection.bind_group_layout);
+    assert!(updated == render_frame(&mut device, &projection, &mut expected));
+}
+
+// Widget of every kind of shape, a nested group, a clip path and a clip
+// rectangle, drawn twice by two groups.
+fn widget(renderer: &mut Renderer1) {
+    let mut diamond = Path::new();
+    diamond.move_to(0.0, -20.0).line_to(20.0, 0.0).line_to(0.0, 20.0).line_to(-20.0, 0.0).close();
+    renderer.rectangle(0.0, 0.0, 24.0, 24.0, 2.0).fill(Color::rgb(0.2, 0.4, 0.8));
+    renderer.push_clip_path(&diamond, FillRule::NonZero);
+    renderer.circle(8.0, 8.0, 16.0, 2.0).fill(Color::RED);
+    renderer.push_group().transform(Affine2::scale(0.5, 0.5)).opacity(0.5);
+    renderer.polygon(&[(-24.0, -24.0), (24.0, -24.0), (0.0, 24.0)], 4.0).fill(Color::WHITE);
+    renderer.pop_group();
+    renderer.pop_clip();
+    renderer.push_clip_rectangle(0.0, -20.0, 12.0, 6.0);
+    renderer.ellipse(0.0, -20.0, 16.0, 4.0, 0.0, 2.0).fill(Color::GREEN);
+    renderer.pop_clip();
+    renderer.line(-24.0, 20.0, 24.0, 20.0, 3.0).stroke(Color::WHITE);
+    let mut triangle = Path::new();
+    triangle.move_to(16.0, 16.0).line_to(24.0, 16.0).line_to(24.0, 24.0).close();
+    renderer.path(&triangle).fill(Color::RED);
+}
+
+fn groups_scene(renderer: &mut Renderer1) -> GroupHandle {
+    renderer.push_group().transform(Affine2::translation(36.0, 36.0));
+    widget(renderer);
+    renderer.pop_group();
+    let handle = renderer
+        .push_group()
+        .transform(Affine2::translation(92.0, 92.0) * Affine2::rotation(std::f32::consts::FRAC_PI_4))
+        .opacity(0.75)
+        .handle();
+    widget(renderer);
+    renderer.pop_group();
+    // Not in a group.
+    renderer.circle(100.0, 28.0, 12.0, 3.0);
+    handle
+}
+
+#[test]
+fn groups() {
+    let mut renderer = Renderer1::default();
+    groups_scene(&mut renderer);
+    golden().check("renderer_1_groups", &render(&renderer), TOLERANCE).unwrap();
+}
+
+#[test]
+fn groups_move_their_shapes_when_edited() {
+    let mut device = pollster::block_on(HeadlessDevice::new(SIZE));
+    let projection = Projection::new(&device);
+
+    let mut renderer = Renderer1::default();
+    let handle = groups_scene(&mut renderer);
+    let mut prepared = renderer.prepare_retained(&device, &projection.bind_group_layout);
+    render_frame(&mut device, &projection, &mut prepared);
+    prepared.scene().edit_group(handle).unwrap().transform(Affine2::translation(92.0, 40.0)).opacity(1.0);
+    let updated = render_frame(&mut device, &projection, &mut prepared);
+
+    // The clips follow the group, the clip rectangle is no longer rotated.
+    let mut expected = Renderer1::default();
+    let handle = groups_scene(&mut expected);
+    expected.edit_group(handle).unwrap().transform(Affine2::translation(92.0, 40.0)).opacity(1.0);
+    let mut expected = expected.prepare_retained(&device, &projection.bind_group_layout);
+    assert!(updated == render_frame(&mut device, &projection, &mut expected));
+}
+
+#[test]
+fn pop_clip_keeps_the_clips_outside_the_group() {
+    let scene = |extra_pop: bool| {
+        let mut renderer = Renderer1::default();
+        renderer.push_clip_rectangle(64.0, 64.0, 24.0, 24.0);
+        renderer.push_group().transform(Affine2::translation(8.0, 0.0));
+        if extra_pop {
+            renderer.pop_clip();
+        }
+        renderer.circle(56.0, 64.0, 40.0, 2.0).fill(Color::RED);
+        renderer.pop_group();
+        renderer.pop_clip();
+        renderer
+    };
+    assert!(render(&scene(true)) == render(&scene(false)));
+}
+
+// PNG with the quarters red, green, blue and white from the top left corner
+// clockwise, and a transparent pixel in the middle.
+fn quarters_png() -> Vec<u8> {
+    let image = RgbaImage::from_fn(16, 16, |x, y| match (x < 8, y < 8) {
+        _ if (7..9).contains(&x) && (7..9).contains(&y) => image::Rgba([0, 0, 0, 0]),
+        (true, true) => image::Rgba([255, 0, 0, 255]),
+        (false, true) => image::Rgba([0, 255, 0, 255]),
+        (false, false) => image::Rgba([0, 0, 255, 255]),
+        (true, false) => image::Rgba([255, 255, 255, 255]),
+    });
+    let mut bytes = Vec::new();
+    image::DynamicImage::ImageRgba8(image)
+        .write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageOutputFormat::Png)
+        .unwrap();
+    bytes
+}
+
+#[test]
+fn images() {
+    let mut renderer = Renderer1::default();
+    let quarters = renderer.load_image(&quarters_png()).unwrap();
+    let stripes = renderer.add_image(image::DynamicImage::ImageRgba8(RgbaImage::from_fn(4, 1, |x, _| {
+        image::Rgba([255, 255, 0, 255 - 60 * x as u8])
+    })));
+    renderer.image(quarters, 32.0, 96.0, 24.0, 24.0);
+    renderer.image(quarters, 96.0, 96.0, 20.0, 20.0).rotation(std::f32::consts::FRAC_PI_6).tint(Color::rgb(1.0, 1.0, 0.5));
+    // Only the top left quarter.
+    renderer.image(quarters, 32.0, 32.0, 16.0, 16.0).source_rect(0.0, 0.0, 8.0, 8.0);
+    // Over the circle, under the rectangle.
+    renderer.circle(96.0, 32.0, 20.0, 4.0);
+    renderer.image(stripes, 96.0, 32.0, 28.0, 8.0).opacity(0.75);
+    renderer.rectangle(96.0, 32.0, 8.0, 20.0, 2.0).z_index(1);
+
+    golden().check("renderer_1_images", &render(&renderer), TOLERANCE).unwrap();
+}
+
+#[test]
+#[should_panic(expected = "wasn't added to this renderer")]
+fn images_of_other_renderers_are_rejected() {
+    let mut other = Renderer1::default();
+    let texture = other.load_image(&quarters_png()).unwrap();
+    Renderer1::default().image(texture, 64.0, 64.0, 16.0, 16.0);
+}
+
+#[test]
+fn atlas_images() {
+    // Icons of different sizes: a square, a cross and a stripe.
+    let square = RgbaImage::from_pixel(8, 8, image::Rgba([255, 128, 0, 255]));
+    let cross = RgbaImage::from_fn(12, 12, |x, y| {
+        let on = (5..7).contains(&x) || (5..7).contains(&y);
+        image::Rgba(if on { [255, 255, 255, 255] } else { [0, 0, 0, 0] })
+    });
+    let stripe = RgbaImage::from_fn(16, 4, |x, _| image::Rgba([0, 16 * x as u8, 255, 255]));
+    let mut builder = AtlasBuilder::new(64);
+    for icon in [square, cross, stripe] {
+        builder.add(&image::DynamicImage::ImageRgba8(icon));
+    }
+    let atlas = builder.build();
+    assert_eq!(atlas.pages.len(), 1);
+
+    let mut renderer = Renderer1::default();
+    let icons = renderer.add_atlas(atlas);
+    for row in 0..4 {
+        for column in 0..4 {
+            let (x, y) = (16.0 + 32.0 * column as f32, 16.0 + 32.0 * row as f32);
+            renderer.atlas_image(icons[(row + column) % icons.len()], x, y, 12.0, 12.0);
+        }
+    }
+    renderer.atlas_image(icons[1], 64.0, 64.0, 24.0, 24.0).rotation(std::f32::consts::FRAC_PI_4).tint(Color::RED);
+
+    golden().check("renderer_1_atlas_images", &render(&renderer), TOLERANCE).unwrap();
+}
+
+const FONT: &[u8] = include_bytes!("fonts/DejaVuSansMono.ttf");
+
+fn text_scene(renderer: &mut Renderer1) -> ShapeHandle {
+    let font = renderer.load_font(FONT).unwrap();
+    renderer.text(font, "Left", 16.0, 8.0, 112.0);
+    renderer.text(font, "Center", 16.0, 64.0, 92.0).align(TextAlign::Center).color(Color::rgb(0.0, 1.0, 1.0));
+    renderer.text(font, "Right", 16.0, 120.0, 72.0).align(TextAlign::Right).color(Color::RED);
+    renderer.rectangle(64.0, 28.0, 56.0, 20.0, 0.0).fill(Color::BLUE).fill_mode(FillMode::Fill);
+    renderer.text(font, "Two\nlines", 12.0, 64.0, 34.0).align(TextAlign::Center).color(Color::WHITE);
+    renderer
+        .text(font, "Turned", 10.0, 0.0, 0.0)
+        .color(Color::GREEN)
+        .transform(Affine2::translation(12.0, 12.0) * Affine2::rotation(std::f32::consts::FRAC_PI_2))
+        .handle()
+}
+
+#[test]
+fn text() {
+    let mut renderer = Renderer1::default();
+    text_scene(&mut renderer);
+    golden().check("renderer_1_text", &render(&renderer), TOLERANCE).unwrap();
+}
+
+#[test]
+#[should_panic(expected = "wasn't loaded to this renderer")]
+fn fonts_of_other_renderers_are_rejected() {
+    let font = Renderer1::default().load_font(FONT).unwrap();
+    Renderer1::default().text(font, "Text", 16.0, 8.0, 64.0);
+}
+
+#[test]
+fn texts_get_new_glyphs_when_edited() {
+    let mut device = pollster::block_on(HeadlessDevice::new(SIZE));
+    let projection = Projection::new(&device);
+
+    let mut renderer = Renderer1::default();
+    let handle = text_scene(&mut renderer);
+    let mut prepared = renderer.prepare_retained(&device, &projection.bind_group_layout);
+    render_frame(&mut device, &projection, &mut prepared);
+    prepared.scene().edit_text(handle).unwrap().text("Quick!");
+    let updated = render_frame(&mut device, &projection, &mut prepared);
+
+    let mut expected = Renderer1::default();
+    let handle = text_scene(&mut expected);
+    expected.edit_text(handle).unwrap().text("Quick!");
+    let mut expected = expected.prepare_retained(&device, &projection.bind_group_layout);
+    assert!(updated == render_frame(&mut device, &projection, &mut expected));
+}